@@ -1,10 +1,12 @@
 use std::convert::Infallible;
 
 use jms_base::{kv, mq::{MessageQueueSubscriber, MessageQueueChannel}};
-use jms_core_lib::{db::{DBDuration, Singleton, Table}, models::{Alliance, AllianceParseError, AllianceStationId, JmsComponent}};
+use jms_core_lib::{db::{generate_id, DBDuration, Singleton, Table}, models::{Alliance, AllianceParseError, AllianceStationId, JmsComponent, MatchTiming, Timeout, TimeoutKind}};
 
 pub const ARENA_STATE_KEY: &'static str = "arena:state";
 pub const ARENA_MATCH_KEY: &'static str = "arena:match";
+pub const ARENA_ARM_BLOCKERS_KEY: &'static str = "arena:arm_blockers";
+pub const ARENA_TIMEOUT_KEY: &'static str = "arena:timeout";
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 #[serde(tag = "state")]
@@ -53,7 +55,17 @@ pub struct SerialisedLoadedMatch {
   pub remaining_max: DBDuration,
   pub match_time: Option<DBDuration>,
   pub endgame: bool,
-  pub state: MatchPlayState
+  pub state: MatchPlayState,
+  pub timing: MatchTiming,
+}
+
+/* TIMEOUTS */
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct SerialisedTimeout {
+  pub timeout: Timeout,
+  pub remaining: DBDuration,
+  pub remaining_max: DBDuration,
 }
 
 /* ALLIANCE STATIONS */
@@ -65,10 +77,32 @@ pub struct AllianceStation {
   pub bypass: bool,
   pub estop: bool,
   pub astop: bool,
-  pub physical_estop: bool, /* TODO: Implement */
+  // Physical stop buttons on the alliance station, as reported by the field electronics. These latch until reset by the FTA.
+  pub physical_estop: bool,
+  pub physical_astop: bool,
+  pub stop_fault: Option<StopButtonFault>,
   pub ds_eth_ok: Option<bool>
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum StopButtonFault {
+  // The stop circuit has read as open ever since the electronics came online - in Normally Closed mode, the wiring is cut or the button is missing.
+  EstopCircuitOpen,
+  AstopCircuitOpen,
+  // The station electronics were seen, but have stopped reporting.
+  Offline,
+}
+
+impl std::fmt::Display for StopButtonFault {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      StopButtonFault::EstopCircuitOpen => write!(f, "E-Stop wiring open"),
+      StopButtonFault::AstopCircuitOpen => write!(f, "A-Stop wiring open"),
+      StopButtonFault::Offline => write!(f, "Stop buttons offline"),
+    }
+  }
+}
+
 impl AllianceStation {
   pub fn default(id: AllianceStationId) -> Self {
     Self {
@@ -78,6 +112,8 @@ impl AllianceStation {
       estop: false,
       astop: false,
       physical_estop: false,
+      physical_astop: false,
+      stop_fault: None,
       ds_eth_ok: None
     }
   }
@@ -133,14 +169,176 @@ impl Singleton for ArenaEntryCondition {
   const KEY: &'static str = "arena.entry_state";
 }
 
+/* MATCH ARM */
+
+// A reason the match can't be armed. These can be overridden by an FTA with a forced arm.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(tag = "blocker")]
+pub enum ArmBlocker {
+  NoDriverStation { station: AllianceStationId, team: usize },
+  DriverStationNotReady { station: AllianceStationId, team: usize, reason: String },
+  NoRobotComms { station: AllianceStationId, team: usize },
+  WrongStation { station: AllianceStationId, team: usize, actual: AllianceStationId },
+  EntryCondition { condition: ArenaEntryCondition },
+  HookPending { hook: String },
+  HookFailed { hook: String, reason: String },
+  PhysicalStop { station: AllianceStationId },
+  StopFault { station: AllianceStationId, fault: StopButtonFault },
+}
+
+impl std::fmt::Display for ArmBlocker {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ArmBlocker::NoDriverStation { station, team } => write!(f, "{} ({}): Driver Station not connected", station.to_string(), team),
+      ArmBlocker::DriverStationNotReady { station, team, reason } => write!(f, "{} ({}): Driver Station {}", station.to_string(), team, reason),
+      ArmBlocker::NoRobotComms { station, team } => write!(f, "{} ({}): No robot comms", station.to_string(), team),
+      ArmBlocker::WrongStation { station, team, actual } => write!(f, "{} ({}): Driver Station is in {}", station.to_string(), team, actual.to_string()),
+      ArmBlocker::EntryCondition { condition } => write!(f, "Field entry condition is {:?}", condition),
+      ArmBlocker::HookPending { hook } => write!(f, "Waiting on {}", hook),
+      ArmBlocker::HookFailed { hook, reason } => write!(f, "{} failed: {}", hook, reason),
+      ArmBlocker::PhysicalStop { station } => write!(f, "{}: Physical E-Stop needs to be reset", station.to_string()),
+      ArmBlocker::StopFault { station, fault } => write!(f, "{}: {}", station.to_string(), fault),
+    }
+  }
+}
+
+/* AUDIT */
+
+// The arena journal records everything that happens on the field, so it can be reviewed after the event.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(tag = "event")]
+pub enum ArenaAuditEvent {
+  Signal { signal: ArenaSignal, error: Option<String> },
+  StateChange { from: ArenaState, to: ArenaState },
+  StationEstop { station: AllianceStationId, estop: bool, astop: bool },
+  StationBypass { station: AllianceStationId, bypass: bool },
+  MatchLoad,
+  MatchUnload,
+  HookFailed { hook: String, reason: String },
+  MatchArmForced { blockers: Vec<ArmBlocker> },
+  TimeoutStart { kind: TimeoutKind, duration: DBDuration },
+  TimeoutEnd { early: bool },
+  PhysicalStop { station: AllianceStationId, astop: bool },
+  StopFault { station: AllianceStationId, fault: Option<StopButtonFault> },
+  PhysicalStopReset,
+}
+
+impl ArenaAuditEvent {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      ArenaAuditEvent::Signal { .. } => "Signal",
+      ArenaAuditEvent::StateChange { .. } => "StateChange",
+      ArenaAuditEvent::StationEstop { .. } => "StationEstop",
+      ArenaAuditEvent::StationBypass { .. } => "StationBypass",
+      ArenaAuditEvent::MatchLoad => "MatchLoad",
+      ArenaAuditEvent::MatchUnload => "MatchUnload",
+      ArenaAuditEvent::HookFailed { .. } => "HookFailed",
+      ArenaAuditEvent::MatchArmForced { .. } => "MatchArmForced",
+      ArenaAuditEvent::TimeoutStart { .. } => "TimeoutStart",
+      ArenaAuditEvent::TimeoutEnd { .. } => "TimeoutEnd",
+      ArenaAuditEvent::PhysicalStop { .. } => "PhysicalStop",
+      ArenaAuditEvent::StopFault { .. } => "StopFault",
+      ArenaAuditEvent::PhysicalStopReset => "PhysicalStopReset",
+    }
+  }
+}
+
+impl std::fmt::Display for ArenaAuditEvent {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ArenaAuditEvent::Signal { signal, error: None } => write!(f, "Signal {:?}", signal),
+      ArenaAuditEvent::Signal { signal, error: Some(e) } => write!(f, "Signal {:?} (rejected: {})", signal, e),
+      ArenaAuditEvent::StateChange { from, to } => write!(f, "State {:?} -> {:?}", from, to),
+      ArenaAuditEvent::StationEstop { station, estop, astop } => write!(f, "{} E-Stop: {}, A-Stop: {}", station.to_string(), estop, astop),
+      ArenaAuditEvent::StationBypass { station, bypass } => write!(f, "{} Bypass: {}", station.to_string(), bypass),
+      ArenaAuditEvent::MatchLoad => write!(f, "Match Loaded"),
+      ArenaAuditEvent::MatchUnload => write!(f, "Match Unloaded"),
+      ArenaAuditEvent::HookFailed { hook, reason } => write!(f, "Hook {} failed: {}", hook, reason),
+      ArenaAuditEvent::MatchArmForced { blockers } => write!(f, "Match Arm Forced, overriding: {}", blockers.iter().map(|b| b.to_string()).collect::<Vec<_>>().join("; ")),
+      ArenaAuditEvent::TimeoutStart { kind: TimeoutKind::Field, duration } => write!(f, "Field Timeout ({}s)", duration.0.num_seconds()),
+      ArenaAuditEvent::TimeoutStart { kind: TimeoutKind::Alliance { alliance }, duration } => write!(f, "Alliance {} Timeout ({}s)", alliance, duration.0.num_seconds()),
+      ArenaAuditEvent::TimeoutEnd { early: true } => write!(f, "Timeout Ended Early"),
+      ArenaAuditEvent::TimeoutEnd { early: false } => write!(f, "Timeout Ended"),
+      ArenaAuditEvent::PhysicalStop { station, astop: false } => write!(f, "{} Physical E-Stop pressed", station.to_string()),
+      ArenaAuditEvent::PhysicalStop { station, astop: true } => write!(f, "{} Physical A-Stop pressed", station.to_string()),
+      ArenaAuditEvent::StopFault { station, fault: Some(fault) } => write!(f, "{} Stop Fault: {}", station.to_string(), fault),
+      ArenaAuditEvent::StopFault { station, fault: None } => write!(f, "{} Stop Fault cleared", station.to_string()),
+      ArenaAuditEvent::PhysicalStopReset => write!(f, "Physical Stops Reset"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ArenaAuditEntry {
+  pub id: String,
+  pub time: chrono::DateTime<chrono::Local>,
+  pub source: String,
+  pub match_id: Option<String>,
+  pub event: ArenaAuditEvent,
+}
+
+impl Table for ArenaAuditEntry {
+  const PREFIX: &'static str = "db:arena:audit";
+  type Err = Infallible;
+  type Id = String;
+
+  fn id(&self) -> Self::Id {
+    self.id.clone()
+  }
+}
+
+impl ArenaAuditEntry {
+  pub fn new(source: String, match_id: Option<String>, event: ArenaAuditEvent) -> Self {
+    Self { id: generate_id(), time: chrono::Local::now(), source, match_id, event }
+  }
+
+  pub fn record(source: String, match_id: Option<String>, event: ArenaAuditEvent, kv: &kv::KVConnection) -> anyhow::Result<Self> {
+    let entry = Self::new(source, match_id, event);
+    entry.insert(kv)?;
+    Ok(entry)
+  }
+
+  // Record an event from outside the arena, against whichever match is currently loaded.
+  pub fn record_current(source: String, event: ArenaAuditEvent, kv: &kv::KVConnection) -> anyhow::Result<Self> {
+    let match_id = kv.json_get::<SerialisedLoadedMatch>(ARENA_MATCH_KEY, "$").ok().map(|m| m.match_id);
+    Self::record(source, match_id, event, kv)
+  }
+
+  pub fn sorted(kv: &kv::KVConnection) -> anyhow::Result<Vec<Self>> {
+    let mut v = Self::all(kv)?;
+    v.sort_by(|a, b| a.time.cmp(&b.time));
+    Ok(v)
+  }
+
+  pub fn for_match(match_id: &str, kv: &kv::KVConnection) -> anyhow::Result<Vec<Self>> {
+    Ok(Self::sorted(kv)?.into_iter().filter(|e| e.match_id.as_deref() == Some(match_id)).collect())
+  }
+
+  // A per-match timeline, as CSV
+  pub fn to_csv(entries: &[Self]) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+    let mut out = "time,match,source,event,detail\n".to_owned();
+    for e in entries {
+      out += &format!(
+        "{},{},{},{},{}\n",
+        e.time.to_rfc3339(), quote(e.match_id.as_deref().unwrap_or("")), quote(&e.source), e.event.kind(), quote(&e.event.to_string())
+      );
+    }
+    out
+  }
+}
+
 /* RPC */
 
 #[jms_macros::service]
 pub trait ArenaRPC {
   async fn signal(signal: ArenaSignal, source: String) -> Result<(), String>;
 
-  async fn load_match(id: String) -> Result<(), String>;
-  async fn unload_match() -> Result<(), String>;
+  async fn load_match(id: String, source: String) -> Result<(), String>;
+  async fn unload_match(source: String) -> Result<(), String>;
+
+  async fn start_timeout(kind: TimeoutKind, duration: Option<DBDuration>, source: String) -> Result<(), String>;
+  async fn end_timeout(source: String) -> Result<(), String>;
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -2,12 +2,16 @@ pub mod matches;
 
 use std::{time::{Duration, Instant}, collections::HashMap};
 
-use jms_arena_lib::{AllianceStation, ArenaEntryCondition, ArenaHookDB, ArenaRPC, ArenaSignal, ArenaState, HookReply, MatchPlayState, ARENA_STATE_KEY};
+use jms_arena_lib::{AllianceStation, ArenaAuditEntry, ArenaAuditEvent, ArenaEntryCondition, ArenaHookDB, ArenaRPC, ArenaSignal, ArenaState, ArmBlocker, HookReply, MatchPlayState, SerialisedTimeout, ARENA_ARM_BLOCKERS_KEY, ARENA_STATE_KEY, ARENA_TIMEOUT_KEY};
 use jms_base::{kv::KVConnection, mq::{MessageQueueChannel, MessageQueue, MessageQueueSubscriber}, logging::JMSLogger};
-use jms_core_lib::{models::{AllianceStationId, self, JmsComponent, Match, Alliance}, db::{Table, Singleton}, scoring::scores::MatchScore};
-use log::{info, error};
+use jms_core_lib::{models::{AllianceStationId, self, JmsComponent, Match, Alliance, PlayoffAlliance, Timeout, TimeoutKind}, db::{DBDuration, Table, Singleton}, scoring::scores::MatchScore};
+use jms_driverstation_lib::{DriverStationConnection, DriverStationReport, DriverStationSettings, DriverStationVersions};
+use log::{info, error, warn};
 use matches::LoadedMatch;
 
+// Arm readiness reads a lot of the DB, so it's only checked this often while in Prestart
+const ARM_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
 struct Arena {
   kv: KVConnection,
   mq: MessageQueueChannel,
@@ -17,12 +21,19 @@ struct Arena {
   last_state_change: Instant,
 
   current_match: Option<LoadedMatch>,
+  timeout: Option<Timeout>,
 
   stations: HashMap<AllianceStationId, AllianceStation>,
   component: JmsComponent,
 
   hook_cache: Vec<ArenaHookDB>,
-  hook_replies: HashMap<String, HookReply>
+  hook_replies: HashMap<String, HookReply>,
+
+  // Who sent the signal currently being processed, if any
+  signal_source: Option<String>,
+
+  arm_checked: Option<Instant>,
+  last_arm_blockers: Option<Vec<ArmBlocker>>,
 }
 
 impl Arena {
@@ -33,19 +44,35 @@ impl Arena {
       last_state: None,
 
       current_match: None,
+      timeout: None,
       last_state_change: Instant::now(),
 
       stations: HashMap::new(),
       component: JmsComponent::new("jms.arena", "JMS-Arena", "A", 500),
 
       hook_cache: vec![],
-      hook_replies: HashMap::new()
+      hook_replies: HashMap::new(),
+
+      signal_source: None,
+
+      arm_checked: None,
+      last_arm_blockers: None,
     }
   }
 
+  fn journal(&self, event: ArenaAuditEvent) -> anyhow::Result<()> {
+    self.journal_as(self.signal_source.clone().unwrap_or("arena".to_owned()), event)
+  }
+
+  fn journal_as(&self, source: String, event: ArenaAuditEvent) -> anyhow::Result<()> {
+    ArenaAuditEntry::record(source, self.current_match.as_ref().map(|m| m.match_id.clone()), event, &self.kv)?;
+    Ok(())
+  }
+
   pub async fn set_state(&mut self, new_state: ArenaState) -> anyhow::Result<()> {
     if new_state != self.state {
       info!("Arena State Change {:?} -> {:?}...", self.state, new_state);
+      self.journal(ArenaAuditEvent::StateChange { from: self.state, to: new_state })?;
       self.last_state = Some(self.state);
       self.state = new_state;
 
@@ -83,7 +110,12 @@ impl Arena {
     info!("Resetting Alliance Stations");
     self.stations.clear();
     for stn in AllianceStationId::all() {
-      let stn_inst = AllianceStation::default(stn);
+      let mut stn_inst = AllianceStation::default(stn);
+      // Physical stops stay latched across matches until the FTA resets them
+      if let Ok(existing) = AllianceStation::get(&stn, &self.kv) {
+        stn_inst.physical_estop = existing.physical_estop;
+        stn_inst.stop_fault = existing.stop_fault;
+      }
       stn_inst.insert(&self.kv)?;
       self.stations.insert(stn, stn_inst);
     }
@@ -94,11 +126,13 @@ impl Arena {
     for hook in &self.hook_cache {
       if hook.state == self.state.into() {
         if hook.timeout < (Instant::now() - self.last_state_change) {
+          self.journal(ArenaAuditEvent::HookFailed { hook: hook.id.clone(), reason: "Timed Out".to_owned() })?;
           anyhow::bail!("Hook Timed Out: {}", hook.id);
         }
 
         if let Some(hr) = self.hook_replies.get(&hook.id) {
           if let Some(fail) = &hr.failure {
+            self.journal(ArenaAuditEvent::HookFailed { hook: hook.id.clone(), reason: fail.clone() })?;
             anyhow::bail!("Hook Failed: {} - {}", hook.id, fail)
           }
         } else {
@@ -109,6 +143,131 @@ impl Arena {
     Ok(true)
   }
 
+  // Stations running software older than the minimum versions, if out of date software is set to block Prestart.
+  fn outdated_stations(&self) -> anyhow::Result<Vec<String>> {
+    let settings = DriverStationSettings::get(&self.kv)?;
+    if !settings.block_outdated {
+      return Ok(vec![]);
+    }
+
+    let mut outdated = vec![];
+    for stn in AllianceStation::sorted(&self.kv)? {
+      let team = match stn.team {
+        Some(team) if !stn.bypass => team,
+        _ => continue
+      };
+
+      if let Ok(versions) = DriverStationVersions::get(&(team as u16), &self.kv) {
+        let out = versions.outdated(&settings);
+        if !out.is_empty() {
+          outdated.push(format!("{} ({}): {}", stn.id.to_string(), team, out.join(", ")));
+        }
+      }
+    }
+    Ok(outdated)
+  }
+
+  // Everything stopping the match from being armed. Bypassed and empty stations aren't checked.
+  pub fn arm_blockers(&self) -> anyhow::Result<Vec<ArmBlocker>> {
+    let mut blockers = vec![];
+
+    for stn in AllianceStation::sorted(&self.kv)? {
+      let team = match stn.team {
+        Some(team) if !stn.bypass => team,
+        _ => continue
+      };
+
+      if stn.physical_estop {
+        blockers.push(ArmBlocker::PhysicalStop { station: stn.id });
+      }
+      if let Some(fault) = stn.stop_fault {
+        blockers.push(ArmBlocker::StopFault { station: stn.id, fault });
+      }
+
+      match DriverStationReport::get(&(team as u16), &self.kv) {
+        Ok(report) => {
+          if let Some(actual) = report.actual_station.filter(|a| *a != stn.id) {
+            blockers.push(ArmBlocker::WrongStation { station: stn.id, team, actual });
+          }
+          if !report.robot_ping {
+            blockers.push(ArmBlocker::NoRobotComms { station: stn.id, team });
+          }
+        },
+        // No control packets, but the connection record can tell us why
+        Err(_) => match DriverStationConnection::get(&stn.id, &self.kv) {
+          Ok(conn) if conn.team as usize == team => blockers.push(ArmBlocker::DriverStationNotReady { station: stn.id, team, reason: conn.describe() }),
+          _ => blockers.push(ArmBlocker::NoDriverStation { station: stn.id, team }),
+        },
+      }
+    }
+
+    let condition = ArenaEntryCondition::get(&self.kv)?;
+    if condition != ArenaEntryCondition::Unsafe {
+      blockers.push(ArmBlocker::EntryCondition { condition });
+    }
+
+    for hook in self.hook_cache.iter().filter(|h| h.state == self.state) {
+      match self.hook_replies.get(&hook.id) {
+        None => blockers.push(ArmBlocker::HookPending { hook: hook.id.clone() }),
+        Some(HookReply { failure: Some(reason), .. }) => blockers.push(ArmBlocker::HookFailed { hook: hook.id.clone(), reason: reason.clone() }),
+        Some(_) => ()
+      }
+    }
+
+    Ok(blockers)
+  }
+
+  pub async fn arm(&mut self, force: bool) -> anyhow::Result<()> {
+    let blockers = self.arm_blockers()?;
+    if !blockers.is_empty() {
+      if !force {
+        anyhow::bail!("Can't arm the match: {}", blockers.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(", "));
+      }
+
+      warn!("Match Arm forced by {:?}, overriding: {:?}", self.signal_source, blockers);
+      self.journal(ArenaAuditEvent::MatchArmForced { blockers })?;
+    }
+
+    self.set_state(ArenaState::MatchArmed).await
+  }
+
+  pub async fn start_timeout(&mut self, kind: TimeoutKind, duration: Option<DBDuration>, source: String) -> anyhow::Result<()> {
+    if self.state != ArenaState::Idle {
+      anyhow::bail!("Timeouts can only be called between matches");
+    }
+    if self.timeout.is_some() {
+      anyhow::bail!("A timeout is already running!");
+    }
+
+    let config = models::MatchTimingConfig::get(&self.kv)?;
+    let duration = match &kind {
+      TimeoutKind::Field => duration.map(|d| d.0).unwrap_or(config.field_timeout.0),
+      TimeoutKind::Alliance { alliance } => {
+        if !PlayoffAlliance::exists(alliance, &self.kv)? {
+          anyhow::bail!("Alliance {} doesn't exist", alliance);
+        }
+        if Timeout::alliance_timeout_used(*alliance, &self.kv)? {
+          anyhow::bail!("Alliance {} has already used their timeout", alliance);
+        }
+        config.alliance_timeout.0
+      }
+    };
+
+    info!("Timeout {:?} for {}s called by {}", kind, duration.num_seconds(), source);
+    let timeout = Timeout::new(kind.clone(), source.clone(), duration);
+    timeout.insert(&self.kv)?;
+    self.journal_as(source, ArenaAuditEvent::TimeoutStart { kind, duration: duration.into() })?;
+    self.timeout = Some(timeout);
+    Ok(())
+  }
+
+  pub async fn end_timeout(&mut self, source: String) -> anyhow::Result<()> {
+    let mut timeout = self.timeout.take().ok_or(anyhow::anyhow!("There's no timeout running!"))?;
+    timeout.end_time = chrono::Local::now();
+    timeout.insert(&self.kv)?;
+    self.journal_as(source, ArenaAuditEvent::TimeoutEnd { early: true })
+  }
+
   pub async fn spin_once(&mut self, signal: Option<ArenaSignal>) -> anyhow::Result<()> {
     let first = self.last_state != Some(self.state);
     self.last_state = Some(self.state);
@@ -117,21 +276,27 @@ impl Arena {
       self.set_state(ArenaState::Estop).await?;
     }
 
-    // Process physical E-Stop button for each station
+    // Process physical E-Stop and A-Stop buttons for each station. The E-Stop is latched by the field electronics until the
+    // FTA resets it, whereas the A-Stop only applies during Auto and is dropped at any other time.
     {
-      let mut physical_astop = false;
-      if let Some(current_match) = &self.current_match {
-        if current_match.state == MatchPlayState::Auto {
-          physical_astop = true;
-        }
-      }
+      let in_auto = self.current_match.as_ref().map(|m| m.state == MatchPlayState::Auto).unwrap_or(false);
 
       for stn_id in self.stations.keys() {
         if AllianceStation::get_physical_estop(stn_id.clone(), &self.kv)? {
-          if physical_astop {
+          if !AllianceStation::get_estop(stn_id.clone(), &self.kv)? {
+            self.journal(ArenaAuditEvent::StationEstop { station: stn_id.clone(), estop: true, astop: false })?;
+          }
+          AllianceStation::set_estop_by_id(stn_id.clone(), true, &self.kv)?;
+        }
+
+        if AllianceStation::get_physical_astop(stn_id.clone(), &self.kv)? {
+          if in_auto {
+            if !AllianceStation::get_astop(stn_id.clone(), &self.kv)? {
+              self.journal(ArenaAuditEvent::StationEstop { station: stn_id.clone(), estop: false, astop: true })?;
+            }
             AllianceStation::set_astop_by_id(stn_id.clone(), true, &self.kv)?;
           } else {
-            AllianceStation::set_estop_by_id(stn_id.clone(), true, &self.kv)?;
+            AllianceStation::set_physical_astop_by_id(stn_id.clone(), false, &self.kv)?;
           }
         }
       }
@@ -172,14 +337,21 @@ impl Arena {
           for stn in self.stations.values_mut() {
             stn.set_estop(false, &self.kv)?;
             stn.set_astop(false, &self.kv)?;
-            stn.set_physical_estop(false, &self.kv)?;
             stn.set_bypass(false, &self.kv)?;
           }
         }
 
         if signal == Some(ArenaSignal::Prestart) {
+          if self.timeout.is_some() {
+            anyhow::bail!("Cannot Prestart during a timeout!");
+          }
+
           match &self.current_match {
             Some(m) if m.state == MatchPlayState::Waiting => {
+              let outdated = self.outdated_stations()?;
+              if !outdated.is_empty() {
+                anyhow::bail!("Cannot Prestart with out of date software (bypass the station to continue): {}", outdated.join("; "));
+              }
               self.set_state(ArenaState::Prestart { ready: false }).await?;
             },
             Some(m) => anyhow::bail!("Cannot Prestart when Match is in state: {:?}", m.state),
@@ -188,6 +360,11 @@ impl Arena {
         }
       },
       ArenaState::Prestart { ready: false } => {
+        // Hooks that haven't replied yet are blockers, so only a forced arm can skip past them
+        if let Some(ArenaSignal::MatchArm { force }) = signal {
+          return self.arm(force).await;
+        }
+
         match self.ready().await {
           Ok(true) => { self.set_state(ArenaState::Prestart { ready: true }).await? },
           Ok(false) => (),
@@ -197,10 +374,7 @@ impl Arena {
       ArenaState::Prestart { ready: true } => {
         match signal {
           Some(sig) => match sig {
-            ArenaSignal::MatchArm { force: _ } => {
-              // TODO: Force
-              self.set_state(ArenaState::MatchArmed).await?;
-            },
+            ArenaSignal::MatchArm { force } => self.arm(force).await?,
             ArenaSignal::PrestartUndo => self.set_state(ArenaState::Idle).await?,
             _ => ()
           },
@@ -220,6 +394,12 @@ impl Arena {
         let current_match = self.current_match.as_mut().ok_or(anyhow::anyhow!("No match present!"))?;
         if first {
           current_match.start()?;
+
+          // Record when the match actually started so we can track how far behind the schedule is. Only the one field is
+          // written, since the scheduler may be re-timing the same match.
+          if Match::exists(&current_match.match_id, &self.kv)? {
+            Match::set_actual_start_time_by_id(current_match.match_id.clone(), Some(chrono::Local::now()), &self.kv)?;
+          }
         }
 
         if current_match.update().await? && current_match.state == MatchPlayState::Pause {
@@ -247,6 +427,39 @@ impl Arena {
       None => self.kv.del("arena:match")?,
     }
 
+    // Timeouts run down alongside the arena state machine
+    if self.timeout.as_ref().map(|t| t.remaining() <= chrono::Duration::zero()).unwrap_or(false) {
+      self.timeout = None;
+      self.journal(ArenaAuditEvent::TimeoutEnd { early: false })?;
+    }
+
+    match &self.timeout {
+      Some(t) => self.kv.json_set(ARENA_TIMEOUT_KEY, "$", &SerialisedTimeout {
+        timeout: t.clone(),
+        remaining: t.remaining().into(),
+        remaining_max: (t.end_time - t.start_time).into()
+      })?,
+      None => self.kv.del(ARENA_TIMEOUT_KEY)?,
+    }
+
+    let blockers = match self.state {
+      ArenaState::Prestart { .. } if self.arm_checked.is_some_and(|t| t.elapsed() < ARM_CHECK_INTERVAL) => None,
+      ArenaState::Prestart { .. } => {
+        self.arm_checked = Some(Instant::now());
+        Some(self.arm_blockers()?)
+      },
+      _ => {
+        self.arm_checked = None;
+        Some(vec![])
+      }
+    };
+
+    // Only written when they change
+    if let Some(blockers) = blockers.filter(|b| self.last_arm_blockers.as_ref() != Some(b)) {
+      self.kv.json_set(ARENA_ARM_BLOCKERS_KEY, "$", &blockers)?;
+      self.last_arm_blockers = Some(blockers);
+    }
+
     Ok(())
   }
 }
@@ -259,15 +472,24 @@ impl ArenaRPC for Arena {
 
   async fn signal(&mut self, signal: ArenaSignal, source: String) -> Result<(), String> {
     info!("Signal: {:?} from {}", signal, source);
-    self.spin_once(Some(signal)).await.map_err(|e| format!("{}", e))
+    let mut entry = ArenaAuditEntry::new(source.clone(), self.current_match.as_ref().map(|m| m.match_id.clone()), ArenaAuditEvent::Signal { signal, error: None });
+
+    self.signal_source = Some(source);
+    let result = self.spin_once(Some(signal)).await.map_err(|e| format!("{}", e));
+    self.signal_source = None;
+
+    entry.event = ArenaAuditEvent::Signal { signal, error: result.clone().err() };
+    entry.insert(&self.kv).map_err(|e| e.to_string())?;
+    result
   }
 
-  async fn load_match(&mut self, id: String) -> Result<(), String> {
+  async fn load_match(&mut self, id: String, source: String) -> Result<(), String> {
     let m = models::Match::get(&id, &self.kv).map_err(|e| e.to_string())?;
     match self.state {
       ArenaState::Idle { .. } => {
         // Load match
-        self.current_match = Some(LoadedMatch::new(m.id()));
+        self.current_match = Some(LoadedMatch::new(m.id(), models::MatchTimingConfig::timing(m.match_type, &self.kv).map_err(|e| e.to_string())?));
+        ArenaAuditEntry::record(source, Some(m.id()), ArenaAuditEvent::MatchLoad, &self.kv).map_err(|e| e.to_string())?;
 
         // Set teams
         for (i, team) in m.blue_teams.into_iter().enumerate() {
@@ -287,11 +509,21 @@ impl ArenaRPC for Arena {
     }
   }
 
-  async fn unload_match(&mut self) -> Result<(), String> {
+  async fn start_timeout(&mut self, kind: TimeoutKind, duration: Option<DBDuration>, source: String) -> Result<(), String> {
+    Arena::start_timeout(self, kind, duration, source).await.map_err(|e| e.to_string())
+  }
+
+  async fn end_timeout(&mut self, source: String) -> Result<(), String> {
+    Arena::end_timeout(self, source).await.map_err(|e| e.to_string())
+  }
+
+  async fn unload_match(&mut self, source: String) -> Result<(), String> {
     info!("Unloading Match...");
     match self.state {
       ArenaState::Idle { .. } => {
-        self.current_match = None;
+        if let Some(m) = self.current_match.take() {
+          ArenaAuditEntry::record(source, Some(m.match_id), ArenaAuditEvent::MatchUnload, &self.kv).map_err(|e| e.to_string())?;
+        }
 
         self.reset_stations().await.map_err(|e| e.to_string())?;
         MatchScore::delete(&self.kv).map_err(|e| e.to_string())?;
@@ -311,6 +543,9 @@ impl Arena {
 
     self.component.insert(&self.kv)?;
 
+    // Pick up a timeout that was running when we were last shut down
+    self.timeout = Timeout::all(&self.kv)?.into_iter().find(|t| t.end_time > chrono::Local::now());
+
     loop {
       tokio::select! {
         msg = rpc.next() => self.rpc_process(msg).await?,
@@ -3,7 +3,7 @@ use std::time::Instant;
 use chrono::Duration;
 use jms_arena_lib::{MatchPlayState, SerialisedLoadedMatch, ARENA_MATCH_KEY};
 use jms_base::kv::KVConnection;
-use jms_core_lib::db::DBDuration;
+use jms_core_lib::{db::DBDuration, models::MatchTiming};
 use log::{warn, info};
 
 pub struct LoadedMatch {
@@ -17,10 +17,11 @@ pub struct LoadedMatch {
   pub remaining: Duration,
   pub remaining_max: Duration,
   pub endgame: bool,
+  pub timing: MatchTiming,
 }
 
 impl LoadedMatch {
-  pub fn new(match_id: String) -> Self {
+  pub fn new(match_id: String, timing: MatchTiming) -> Self {
     Self {
       match_id,
       state: MatchPlayState::Waiting,
@@ -30,7 +31,8 @@ impl LoadedMatch {
 
       remaining: Duration::zero(),
       remaining_max: Duration::zero(),
-      endgame: false
+      endgame: false,
+      timing
     }
   }
 
@@ -68,7 +70,7 @@ impl LoadedMatch {
     match self.state {
       MatchPlayState::Waiting => (),
       MatchPlayState::Warmup => {
-        remaining_max = Duration::seconds(3);
+        remaining_max = self.timing.warmup.0;
         remaining = remaining_max - elapsed;
         if remaining <= Duration::zero() {
           self.do_change_state(MatchPlayState::Auto);
@@ -78,29 +80,29 @@ impl LoadedMatch {
         if first {
           self.match_start_time = Some(Instant::now());
         }
-        remaining_max = Duration::seconds(15);
+        remaining_max = self.timing.auto.0;
         remaining = remaining_max - elapsed;
         if remaining <= Duration::zero() {
           self.do_change_state(MatchPlayState::Pause);
         }
       }
       MatchPlayState::Pause => {
-        remaining_max = Duration::seconds(3);
+        remaining_max = self.timing.pause.0;
         remaining = remaining_max - elapsed;
         if remaining <= Duration::zero() {
           self.do_change_state(MatchPlayState::Teleop);
         }
       }
       MatchPlayState::Teleop => {
-        remaining_max = Duration::seconds(2*60 + 15);
+        remaining_max = self.timing.teleop.0;
         remaining = remaining_max - elapsed;
         if remaining <= Duration::zero() {
           self.do_change_state(MatchPlayState::Cooldown);
         }
-        endgame = remaining <= Duration::seconds(20);
+        endgame = remaining <= self.timing.endgame.0;
       }
       MatchPlayState::Cooldown => {
-        remaining_max = Duration::seconds(3);
+        remaining_max = self.timing.cooldown.0;
         remaining = remaining_max - elapsed;
         if remaining <= Duration::zero() {
           self.do_change_state(MatchPlayState::Complete);
@@ -131,7 +133,8 @@ impl LoadedMatch {
       remaining_max: DBDuration(self.remaining_max),
       match_time: self.match_start_time.map(|mt| DBDuration(Duration::from_std(Instant::now() - mt).unwrap())),
       endgame: self.endgame,
-      state: self.state
+      state: self.state,
+      timing: self.timing.clone(),
     };
     kv.json_set(ARENA_MATCH_KEY, "$", &serialised)?;
     Ok(())
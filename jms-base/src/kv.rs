@@ -45,6 +45,11 @@ impl KVConnection {
     Ok(us)
   }
 
+  pub fn json_arr_append<V: serde::Serialize>(&self, key: &str, path: &str, value: &V) -> anyhow::Result<()> {
+    let _: redis::Value = self.redis.lock().unwrap().json_arr_append(key, path, value)?;
+    Ok(())
+  }
+
   pub fn hset<V: ToRedisArgs>(&self, key: &str, field: &str, value: V) -> anyhow::Result<()> {
     self.redis.lock().unwrap().hset(key, field, value)?;
     Ok(())
@@ -0,0 +1,168 @@
+use jms_base::kv;
+
+use crate::db::{Singleton, Table};
+
+use super::{PlayoffAlliance, TeamRanking};
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(tag = "action")]
+pub enum AllianceSelectionAction {
+  Pick { alliance: usize, team: usize },
+  Decline { alliance: usize, team: usize },
+}
+
+// An action taken during alliance selection, along with the state from before it was taken so it can be undone.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct AllianceSelectionStep {
+  pub action: AllianceSelectionAction,
+  pub round: usize,
+  pub picking: Option<usize>,
+  pub declined: Vec<usize>,
+  pub alliances: Vec<PlayoffAlliance>,
+}
+
+// A live alliance selection session. Picks are made in serpentine order - round 1 goes 1 to N, round 2 goes N to 1,
+// and the optional round 3 goes 1 to N again. A team that declines can't be picked for the rest of the session, but
+// may still be promoted to captain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct AllianceSelection {
+  pub active: bool,
+  pub rounds: usize,
+  pub round: usize,
+  pub picking: Option<usize>,
+  pub declined: Vec<usize>,
+  pub history: Vec<AllianceSelectionStep>,
+}
+
+impl Singleton for AllianceSelection {
+  const KEY: &'static str = "alliance_selection";
+}
+
+impl AllianceSelection {
+  pub fn start(n_alliances: usize, rounds: usize, kv: &kv::KVConnection) -> anyhow::Result<Self> {
+    if rounds < 1 || rounds > 3 {
+      anyhow::bail!("Alliance Selection must have between 1 and 3 rounds of picks");
+    }
+
+    PlayoffAlliance::create_all(n_alliances, kv)?;
+
+    let selection = Self {
+      active: true,
+      rounds,
+      round: 1,
+      picking: Some(1),
+      declined: vec![],
+      history: vec![],
+    };
+    selection.update(kv)?;
+    Ok(selection)
+  }
+
+  // Teams that may currently be picked, in ranking order. Captains may only be picked by a higher seeded alliance during the first round.
+  pub fn available(&self, kv: &kv::KVConnection) -> anyhow::Result<Vec<usize>> {
+    let picking = match (self.active, self.picking) {
+      (true, Some(picking)) => picking,
+      _ => return Ok(vec![])
+    };
+
+    let alliances = PlayoffAlliance::sorted(kv)?;
+    Ok(TeamRanking::sorted(kv)?.into_iter().map(|r| r.team).filter(|t| {
+      !self.declined.contains(t) && alliances.iter().all(|a| match a.teams.iter().position(|x| x == t) {
+        None => true,
+        Some(0) => self.round == 1 && a.number > picking,
+        Some(_) => false
+      })
+    }).collect())
+  }
+
+  fn step(&self, action: AllianceSelectionAction, kv: &kv::KVConnection) -> anyhow::Result<AllianceSelectionStep> {
+    Ok(AllianceSelectionStep {
+      action,
+      round: self.round,
+      picking: self.picking,
+      declined: self.declined.clone(),
+      alliances: PlayoffAlliance::sorted(kv)?,
+    })
+  }
+
+  pub fn pick(&mut self, team: usize, kv: &kv::KVConnection) -> anyhow::Result<()> {
+    let picking = self.picking.filter(|_| self.active).ok_or(anyhow::anyhow!("Alliance Selection isn't running!"))?;
+    if !self.available(kv)?.contains(&team) {
+      anyhow::bail!("Team {} can't be picked", team);
+    }
+
+    // A captain, and one pick for each round
+    let mut alliance = PlayoffAlliance::get(&picking, kv)?;
+    if alliance.teams.len() > self.rounds {
+      anyhow::bail!("Alliance {} is already full", picking);
+    }
+
+    let step = self.step(AllianceSelectionAction::Pick { alliance: picking, team }, kv)?;
+
+    // If the team was a captain, their alliance is vacated and everyone below moves up.
+    let captain_of = step.alliances.iter().find(|a| a.teams.first() == Some(&team)).cloned();
+    alliance.teams.push(team);
+    alliance.insert(kv)?;
+
+    if let Some(mut vacated) = captain_of {
+      vacated.teams.clear();
+      vacated.insert(kv)?;
+      PlayoffAlliance::promote(kv)?;
+    }
+
+    self.history.push(step);
+    self.advance(kv)?;
+    self.update(kv)
+  }
+
+  pub fn decline(&mut self, team: usize, kv: &kv::KVConnection) -> anyhow::Result<()> {
+    let picking = self.picking.filter(|_| self.active).ok_or(anyhow::anyhow!("Alliance Selection isn't running!"))?;
+    if !self.available(kv)?.contains(&team) {
+      anyhow::bail!("Team {} isn't available to decline", team);
+    }
+
+    let step = self.step(AllianceSelectionAction::Decline { alliance: picking, team }, kv)?;
+    self.declined.push(team);
+    self.history.push(step);
+    self.update(kv)
+  }
+
+  pub fn undo(&mut self, kv: &kv::KVConnection) -> anyhow::Result<()> {
+    let step = self.history.pop().ok_or(anyhow::anyhow!("Nothing to undo!"))?;
+
+    PlayoffAlliance::clear(kv)?;
+    for alliance in &step.alliances {
+      alliance.insert(kv)?;
+    }
+
+    self.active = true;
+    self.round = step.round;
+    self.picking = step.picking;
+    self.declined = step.declined;
+    self.update(kv)
+  }
+
+  // Move to the next alliance in serpentine order, finishing the session after the last round.
+  fn advance(&mut self, kv: &kv::KVConnection) -> anyhow::Result<()> {
+    let n = PlayoffAlliance::all(kv)?.len();
+    let next = match (self.picking, self.round % 2) {
+      (Some(p), 1) if p < n => Some((self.round, p + 1)),
+      (Some(p), 0) if p > 1 => Some((self.round, p - 1)),
+      (Some(_), 1) => Some((self.round + 1, n)),
+      (Some(_), _) => Some((self.round + 1, 1)),
+      (None, _) => None
+    };
+
+    match next {
+      Some((round, picking)) if round <= self.rounds => {
+        self.round = round;
+        self.picking = Some(picking);
+      },
+      _ => {
+        self.active = false;
+        self.picking = None;
+      }
+    }
+    Ok(())
+  }
+}
@@ -1,15 +1,24 @@
-use std::num::ParseIntError;
+use std::{num::ParseIntError, collections::HashMap};
 
 use jms_base::kv;
 
-use crate::db::Table;
+use crate::{db::{Singleton, Table}, scoring::scores::ScoringConfig};
 
-use super::TeamRanking;
+use super::{Alliance, AllianceSelection, CommittedMatchScores, Match, MatchType, TeamRanking};
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct PlayoffAlliance {
   pub number: usize,
   pub teams: Vec<usize>,
+  #[serde(default)]
+  pub backup: Option<BackupTeam>,
+}
+
+// A backup robot called in from the pool of unpicked teams to stand in for a team on the alliance.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct BackupTeam {
+  pub team: usize,
+  pub replaces: usize,
 }
 
 #[async_trait::async_trait]
@@ -43,11 +52,13 @@ impl PlayoffAlliance {
         PlayoffAlliance {
           number: i,
           teams: vec![ team ],
+          backup: None,
         }.insert(kv)?;
       } else {
         PlayoffAlliance {
           number: i,
-          teams: vec![]
+          teams: vec![],
+          backup: None,
         }.insert(kv)?;
       }
     }
@@ -55,6 +66,48 @@ impl PlayoffAlliance {
     Ok(())
   }
 
+  // The teams fielded in playoff matches, with the backup (if any) standing in for the team it replaces.
+  pub fn lineup(&self) -> Vec<usize> {
+    self.teams.iter().map(|&t| match &self.backup {
+      Some(backup) if backup.replaces == t => backup.team,
+      _ => t
+    }).collect()
+  }
+
+  // Teams that aren't on an alliance, haven't already been called in as a backup, and didn't decline an invitation during
+  // alliance selection, in ranking order.
+  pub fn backup_pool(kv: &kv::KVConnection) -> anyhow::Result<Vec<usize>> {
+    let alliances = Self::all(kv)?;
+    let unavailable: Vec<usize> = alliances.iter()
+      .flat_map(|a| a.teams.iter().copied().chain(a.backup.as_ref().map(|b| b.team)))
+      .chain(AllianceSelection::get(kv)?.declined)
+      .collect();
+
+    Ok(TeamRanking::sorted(kv)?.into_iter().map(|r| r.team).filter(|t| !unavailable.contains(t)).collect())
+  }
+
+  // Call in a backup for the given team. If no team is given, the highest ranked team in the pool is used.
+  pub fn call_backup(number: usize, replaces: usize, team: Option<usize>, kv: &kv::KVConnection) -> anyhow::Result<PlayoffAlliance> {
+    let mut alliance = Self::get(&number, kv)?;
+    if alliance.backup.is_some() {
+      anyhow::bail!("Alliance {} has already called in a backup!", number);
+    }
+    if !alliance.teams.contains(&replaces) {
+      anyhow::bail!("Team {} is not on Alliance {}", replaces, number);
+    }
+
+    let pool = Self::backup_pool(kv)?;
+    let team = match team {
+      Some(t) if pool.contains(&t) => t,
+      Some(t) => anyhow::bail!("Team {} is not available as a backup", t),
+      None => *pool.first().ok_or(anyhow::anyhow!("There are no teams available as a backup"))?
+    };
+
+    alliance.backup = Some(BackupTeam { team, replaces });
+    alliance.insert(kv)?;
+    Ok(alliance)
+  }
+
   pub fn promote(kv: &kv::KVConnection) -> anyhow::Result<()> {
     // let alliances = Self::table(store)?.all()?;
     let alliances = Self::sorted(kv)?;
@@ -96,3 +149,121 @@ impl PlayoffAlliance {
     Ok(())
   }
 }
+
+// Standings for the Round Robin playoff format, where each alliance plays each other alliance once
+// and the top two go through to the Finals.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct RoundRobinStanding {
+  pub alliance: usize,
+  pub played: usize,
+  pub win: usize,
+  pub loss: usize,
+  pub tie: usize,
+  pub points: usize,
+  pub total_score: usize,
+}
+
+impl RoundRobinStanding {
+  pub fn new(alliance: usize) -> Self {
+    Self { alliance, played: 0, win: 0, loss: 0, tie: 0, points: 0, total_score: 0 }
+  }
+
+  // Sorted by points (2 for a win, 1 for a tie), then total score, then alliance seed.
+  pub fn calculate(n_alliances: usize, matches: &[Match], scores: &HashMap<String, CommittedMatchScores>, config: &ScoringConfig) -> anyhow::Result<Vec<RoundRobinStanding>> {
+    let mut standings: Vec<RoundRobinStanding> = (1..=n_alliances).map(Self::new).collect();
+
+    for m in matches.iter().filter(|m| m.match_type == MatchType::Playoff && m.played) {
+      let score = match scores.get(&m.id).and_then(|s| s.scores.last()) {
+        Some(score) => score,
+        None => continue
+      };
+      let (red, blue) = match (m.red_alliance, m.blue_alliance) {
+        (Some(red), Some(blue)) => (red, blue),
+        _ => continue
+      };
+
+      let snapshot = score.clone().derive(config)?;
+      let winner = score.winner(config)?;
+
+      for (alliance, colour, total) in [ (red, Alliance::Red, snapshot.red.derived.total_score()), (blue, Alliance::Blue, snapshot.blue.derived.total_score()) ] {
+        if let Some(standing) = standings.iter_mut().find(|s| s.alliance == alliance) {
+          standing.played += 1;
+          standing.total_score += total;
+          match winner {
+            Some(w) if w == colour => { standing.win += 1; standing.points += 2 },
+            Some(_) => standing.loss += 1,
+            None => { standing.tie += 1; standing.points += 1 },
+          }
+        }
+      }
+    }
+
+    standings.sort_by(|a, b| b.points.cmp(&a.points).then(b.total_score.cmp(&a.total_score)).then(a.alliance.cmp(&b.alliance)));
+    Ok(standings)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use crate::{models::{CommittedMatchScores, Match, MatchType}, scoring::{crescendo::CrescendoLiveScore, game::Game, scores::{LiveScore, MatchScore, ScoringConfig}}};
+
+  use super::{BackupTeam, PlayoffAlliance, RoundRobinStanding};
+
+  #[test]
+  fn lineup() {
+    let mut alliance = PlayoffAlliance { number: 1, teams: vec![ 4788, 5333, 6510 ], backup: None };
+    assert_eq!(alliance.lineup(), vec![ 4788, 5333, 6510 ]);
+
+    // The backup takes the place of the team it replaces, which is what's sent to the arena and TBA
+    alliance.backup = Some(BackupTeam { team: 9000, replaces: 5333 });
+    assert_eq!(alliance.lineup(), vec![ 4788, 9000, 6510 ]);
+  }
+
+  fn playoff(set: usize, red: usize, blue: usize, played: bool) -> Match {
+    Match {
+      id: Match::gen_id(MatchType::Playoff, 1, set, 1),
+      name: Match::gen_name(MatchType::Playoff, 1, set, 1),
+      start_time: chrono::Local::now() + chrono::Duration::minutes(set as i64),
+      match_type: MatchType::Playoff,
+      round: 1, set_number: set, match_number: 1,
+      blue_teams: vec![], blue_alliance: Some(blue), red_teams: vec![], red_alliance: Some(red),
+      dqs: vec![], surrogates: vec![], replay_reason: None,
+      actual_start_time: None, original_start_time: None,
+      played, ready: true
+    }
+  }
+
+  fn scored(m: &Match, red: isize, blue: isize) -> (String, CommittedMatchScores) {
+    let live = |adjustment| LiveScore::Crescendo2024(CrescendoLiveScore { adjustment, ..CrescendoLiveScore::new(3) });
+    (m.id.clone(), CommittedMatchScores { match_id: m.id.clone(), scores: vec![ MatchScore { red: live(red), blue: live(blue) } ], last_update: chrono::Local::now() })
+  }
+
+  #[test]
+  fn round_robin_standings() {
+    let config = ScoringConfig::new(Game::Crescendo2024);
+    let matches = vec![ playoff(1, 1, 2, true), playoff(2, 2, 3, true), playoff(3, 3, 1, true), playoff(4, 1, 4, false) ];
+    let scores: HashMap<_, _> = [ scored(&matches[0], 20, 10), scored(&matches[1], 5, 5), scored(&matches[2], 30, 0) ].into_iter().collect();
+
+    let standings = RoundRobinStanding::calculate(4, &matches, &scores, &config).unwrap();
+    assert_eq!(standings, vec![
+      RoundRobinStanding { alliance: 3, played: 2, win: 1, loss: 0, tie: 1, points: 3, total_score: 35 },
+      RoundRobinStanding { alliance: 1, played: 2, win: 1, loss: 1, tie: 0, points: 2, total_score: 20 },
+      RoundRobinStanding { alliance: 2, played: 2, win: 0, loss: 1, tie: 1, points: 1, total_score: 15 },
+      // Unplayed matches don't count
+      RoundRobinStanding::new(4),
+    ]);
+  }
+
+  #[test]
+  fn round_robin_standings_tiebreak() {
+    let config = ScoringConfig::new(Game::Crescendo2024);
+    let matches = vec![ playoff(1, 1, 2, true), playoff(2, 2, 3, true), playoff(3, 3, 1, true) ];
+
+    // Everyone wins once, so it comes down to total score, then seed
+    let scores: HashMap<_, _> = [ scored(&matches[0], 20, 10), scored(&matches[1], 30, 10), scored(&matches[2], 20, 10) ].into_iter().collect();
+    let standings = RoundRobinStanding::calculate(3, &matches, &scores, &config).unwrap();
+    assert_eq!(standings.iter().map(|s| (s.alliance, s.points, s.total_score)).collect::<Vec<_>>(), vec![ (2, 2, 40), (1, 2, 30), (3, 2, 30) ]);
+  }
+}
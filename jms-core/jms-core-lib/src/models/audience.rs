@@ -13,6 +13,7 @@ pub enum AudienceDisplayScene {
   PlayoffBracket,
   Award(/* Award ID */ String),
   CustomMessage(String),
+  Timeout,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
@@ -1,4 +1,4 @@
-use crate::db::Singleton;
+use crate::{db::Singleton, scoring::game::Game};
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct EventDetails {
@@ -7,7 +7,9 @@ pub struct EventDetails {
   pub webcasts: Vec<String>,
   pub av_chroma_key: String,
   pub av_event_colour: String,
-  pub event_logo: Option<String>    // Encoded as a HTML5 img src
+  pub event_logo: Option<String>,   // Encoded as a HTML5 img src
+  #[serde(default)]
+  pub game: Game
 }
 
 #[async_trait::async_trait]
@@ -17,6 +19,6 @@ impl Singleton for EventDetails {
 
 impl Default for EventDetails {
   fn default() -> Self {
-    Self { code: None, event_name: None, webcasts: vec![], av_chroma_key: "#f0f".to_owned(), av_event_colour: "#e9ab01".to_owned(), event_logo: None }
+    Self { code: None, event_name: None, webcasts: vec![], av_chroma_key: "#f0f".to_owned(), av_event_colour: "#e9ab01".to_owned(), event_logo: None, game: Game::default() }
   }
 }
\ No newline at end of file
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use jms_base::kv;
+
+use crate::db::{DBDuration, Singleton};
+
+use super::MatchType;
+
+// The length of each phase of a match. The endgame threshold is the time remaining in Teleop at which endgame begins.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct MatchTiming {
+  pub warmup: DBDuration,
+  pub auto: DBDuration,
+  pub pause: DBDuration,
+  pub teleop: DBDuration,
+  pub cooldown: DBDuration,
+  pub endgame: DBDuration,
+}
+
+impl Default for MatchTiming {
+  fn default() -> Self {
+    Self {
+      warmup: chrono::Duration::seconds(3).into(),
+      auto: chrono::Duration::seconds(15).into(),
+      pause: chrono::Duration::seconds(3).into(),
+      teleop: chrono::Duration::seconds(2*60 + 15).into(),
+      cooldown: chrono::Duration::seconds(3).into(),
+      endgame: chrono::Duration::seconds(20).into(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct MatchTimingConfig {
+  pub default: MatchTiming,
+  #[serde(default)]
+  pub overrides: HashMap<MatchType, MatchTiming>,
+  #[serde(default = "default_alliance_timeout")]
+  pub alliance_timeout: DBDuration,
+  #[serde(default = "default_field_timeout")]
+  pub field_timeout: DBDuration,
+}
+
+fn default_alliance_timeout() -> DBDuration { chrono::Duration::minutes(6).into() }
+fn default_field_timeout() -> DBDuration { chrono::Duration::minutes(5).into() }
+
+impl Default for MatchTimingConfig {
+  fn default() -> Self {
+    Self { default: MatchTiming::default(), overrides: HashMap::new(), alliance_timeout: default_alliance_timeout(), field_timeout: default_field_timeout() }
+  }
+}
+
+impl Singleton for MatchTimingConfig {
+  const KEY: &'static str = "db:match_timing";
+}
+
+impl MatchTimingConfig {
+  pub fn for_match_type(&self, match_type: MatchType) -> MatchTiming {
+    self.overrides.get(&match_type).cloned().unwrap_or(self.default.clone())
+  }
+
+  pub fn timing(match_type: MatchType, kv: &kv::KVConnection) -> anyhow::Result<MatchTiming> {
+    Ok(Self::get(kv)?.for_match_type(match_type))
+  }
+
+  pub fn validate(&self) -> anyhow::Result<()> {
+    for (name, timing) in std::iter::once(("Default".to_owned(), &self.default)).chain(self.overrides.iter().map(|(k, v)| (k.to_string(), v))) {
+      let phases = [ &timing.warmup, &timing.auto, &timing.pause, &timing.teleop, &timing.cooldown, &timing.endgame ];
+      if phases.iter().any(|p| p.0 < chrono::Duration::zero()) {
+        anyhow::bail!("{} Match Timing has a negative phase length", name);
+      }
+      if timing.endgame.0 > timing.teleop.0 {
+        anyhow::bail!("{} Match Timing has an endgame longer than teleop", name);
+      }
+    }
+    if self.alliance_timeout.0 <= chrono::Duration::zero() || self.field_timeout.0 <= chrono::Duration::zero() {
+      anyhow::bail!("Timeouts must be longer than zero");
+    }
+    Ok(())
+  }
+}
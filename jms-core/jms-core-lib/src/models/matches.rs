@@ -62,6 +62,14 @@ impl AllianceStationId {
     }
   }
 
+  pub fn from_ds_number(n: u8) -> Option<Self> {
+    match n {
+      0..=2 => Some(Self::new(Alliance::Red, n as usize + 1)),
+      3..=5 => Some(Self::new(Alliance::Blue, n as usize - 2)),
+      _ => None
+    }
+  }
+
   pub fn to_id(&self) -> String {
     format!("{}{}", self.alliance.to_string().to_lowercase(), self.station)
   }
@@ -105,6 +113,7 @@ pub enum MatchType {
   Test, Qualification, Playoff, Final
 }
 
+#[derive(jms_macros::DbPartialUpdate)]
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct Match {
   pub id: String,
@@ -121,6 +130,15 @@ pub struct Match {
   pub red_teams: Vec<Option<usize>>,
   pub red_alliance: Option<usize>,
   pub dqs: Vec<usize>,
+  #[serde(default)]
+  pub surrogates: Vec<usize>,
+  #[serde(default)]
+  pub replay_reason: Option<String>,
+  // When the match actually started (from the arena), and the start time it was given before being re-timed.
+  #[serde(default)]
+  pub actual_start_time: Option<chrono::DateTime<Local>>,
+  #[serde(default)]
+  pub original_start_time: Option<chrono::DateTime<Local>>,
 
   pub played: bool,
   pub ready: bool,
@@ -133,7 +151,7 @@ impl Match {
   // We also have a generic "Playoff" type, that covers Bracket, Double Bracket, and Round-Robin through the use of a 'round' number as opposed
   // to the qf, sf marking that you may be used to. We separate finals into their own type since they may be different to the playoff type (finals are bracket, 
   // where playoffs may be a bracket, double bracket, or round robin). 
-  // TODO: Implement Qualification replay elsewhere, only include the latest replay (match) number in rankings.
+  // Only the latest played replay (match) number of a Qualification set is included in rankings, see superseded_by_replay.
 
   pub fn gen_id(ty: MatchType, round: usize, set: usize, match_n: usize) -> String {
     match ty {
@@ -158,6 +176,50 @@ impl Match {
     self.red_teams.iter().chain(self.blue_teams.iter()).find(|&ot| (*ot) == Some(team)).is_some()
   }
 
+  // The start time as originally scheduled, before any re-timing
+  pub fn scheduled_start_time(&self) -> chrono::DateTime<Local> {
+    self.original_start_time.unwrap_or(self.start_time)
+  }
+
+  pub fn is_surrogate(&self, team: usize) -> bool {
+    self.surrogates.contains(&team)
+  }
+
+  pub fn replay(&self, reason: String, start_time: chrono::DateTime<Local>) -> anyhow::Result<Match> {
+    if self.match_type != MatchType::Qualification {
+      anyhow::bail!("Only Qualification matches may be replayed!");
+    }
+
+    let match_number = self.match_number + 1;
+    Ok(Match {
+      id: Match::gen_id(self.match_type, self.round, self.set_number, match_number),
+      name: Match::gen_name(self.match_type, self.round, self.set_number, match_number),
+      start_time,
+      match_type: self.match_type,
+      round: self.round,
+      set_number: self.set_number,
+      match_number,
+      blue_teams: self.blue_teams.clone(),
+      blue_alliance: self.blue_alliance,
+      red_teams: self.red_teams.clone(),
+      red_alliance: self.red_alliance,
+      dqs: vec![],
+      surrogates: self.surrogates.clone(),
+      replay_reason: Some(reason),
+      actual_start_time: None,
+      original_start_time: None,
+      played: false,
+      ready: true
+    })
+  }
+
+  // A Qualification match is superseded once a replay of it (same set, higher match number) has been played
+  pub fn superseded_by_replay(&self, matches: &[Match]) -> bool {
+    self.match_type == MatchType::Qualification && matches.iter().any(|m| {
+      m.match_type == MatchType::Qualification && m.set_number == self.set_number && m.match_number > self.match_number && m.played
+    })
+  }
+
   pub fn reset(&mut self) {
     self.played = false;
   }
@@ -203,13 +265,29 @@ pub struct PlayoffMode {
   pub n_alliances: usize,
   pub awards: Vec<String>,
   pub time_per_award: DBDuration,
-  pub minimum_round_break: DBDuration
+  pub minimum_round_break: DBDuration,
+  // Best-of-N for each set in a (single) Bracket. Double Brackets and Round Robins are always a single match per set.
+  #[serde(default = "PlayoffMode::default_best_of")]
+  pub playoff_best_of: usize,
+  #[serde(default = "PlayoffMode::default_best_of")]
+  pub final_best_of: usize,
+}
+
+impl PlayoffMode {
+  fn default_best_of() -> usize {
+    3
+  }
+
+  pub fn wins_required(best_of: usize) -> usize {
+    best_of.max(1) / 2 + 1
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub enum PlayoffModeType {
   Bracket,
-  DoubleBracket
+  DoubleBracket,
+  RoundRobin
 }
 
 impl Default for PlayoffMode {
@@ -219,7 +297,9 @@ impl Default for PlayoffMode {
       n_alliances: 8,
       awards: vec![],
       time_per_award: DBDuration(chrono::Duration::minutes(5)),
-      minimum_round_break: DBDuration(chrono::Duration::minutes(8))
+      minimum_round_break: DBDuration(chrono::Duration::minutes(8)),
+      playoff_best_of: Self::default_best_of(),
+      final_best_of: Self::default_best_of(),
     }
   }
 }
@@ -258,11 +338,11 @@ impl CommittedMatchScores {
         let is_blue = m.blue_teams.contains(&Some(*dq));
 
         if is_red {
-          score.red.is_dq = true;
+          score.red.disqualify();
         }
 
         if is_blue {
-          score.blue.is_dq = true;
+          score.blue.disqualify();
         }
       }
     }
@@ -280,4 +360,65 @@ impl CommittedMatchScores {
 
     Ok(())
   }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Match, MatchType};
+
+  fn qual(set: usize) -> Match {
+    Match {
+      id: Match::gen_id(MatchType::Qualification, 1, set, 1),
+      name: Match::gen_name(MatchType::Qualification, 1, set, 1),
+      start_time: chrono::Local::now(),
+      match_type: MatchType::Qualification,
+      round: 1, set_number: set, match_number: 1,
+      blue_teams: vec![ Some(4), Some(5), Some(6) ], blue_alliance: None,
+      red_teams: vec![ Some(1), Some(2), Some(3) ], red_alliance: None,
+      dqs: vec![ 2 ], surrogates: vec![ 6 ], replay_reason: None,
+      actual_start_time: Some(chrono::Local::now()), original_start_time: None,
+      played: true, ready: true
+    }
+  }
+
+  #[test]
+  fn replay() {
+    let original = qual(5);
+    let replay = original.replay("Field Fault".to_owned(), chrono::Local::now()).unwrap();
+
+    assert_eq!((replay.id.as_str(), replay.name.as_str()), ("qm5m2", "Qualification 5 (replay 1)"));
+    assert_eq!((replay.set_number, replay.match_number), (5, 2));
+    assert_eq!((&replay.red_teams, &replay.blue_teams, &replay.surrogates), (&original.red_teams, &original.blue_teams, &original.surrogates));
+    assert_eq!(replay.replay_reason.as_deref(), Some("Field Fault"));
+    // DQs and timing belong to the original playing
+    assert!(replay.dqs.is_empty() && replay.actual_start_time.is_none() && !replay.played);
+
+    let replay2 = replay.replay("Field Fault".to_owned(), chrono::Local::now()).unwrap();
+    assert_eq!((replay2.id.as_str(), replay2.match_number), ("qm5m3", 3));
+
+    let mut playoff = qual(1);
+    playoff.match_type = MatchType::Playoff;
+    assert!(playoff.replay("Field Fault".to_owned(), chrono::Local::now()).is_err());
+  }
+
+  #[test]
+  fn superseded_by_replay() {
+    let original = qual(5);
+    let mut replay = original.replay("Field Fault".to_owned(), chrono::Local::now()).unwrap();
+    let other = qual(6);
+
+    // Not until the replay has been played
+    assert!(!original.superseded_by_replay(&[ original.clone(), replay.clone(), other.clone() ]));
+
+    replay.played = true;
+    let matches = [ original.clone(), replay.clone(), other.clone() ];
+    assert!(original.superseded_by_replay(&matches));
+    assert!(!replay.superseded_by_replay(&matches));
+    assert!(!other.superseded_by_replay(&matches));
+
+    // Only Qualifications are replayed
+    let mut playoff = original.clone();
+    playoff.match_type = MatchType::Playoff;
+    assert!(!playoff.superseded_by_replay(&matches));
+  }
 }
\ No newline at end of file
@@ -16,6 +16,15 @@ pub use schedule::*;
 mod alliances;
 pub use alliances::*;
 
+mod alliance_selection;
+pub use alliance_selection::*;
+
+mod match_timing;
+pub use match_timing::*;
+
+mod timeouts;
+pub use timeouts::*;
+
 mod awards;
 pub use awards::*;
 
@@ -3,24 +3,30 @@ use std::{num::ParseIntError, collections::HashMap};
 use jms_base::kv;
 use rand::Rng;
 
-use crate::{db::{Singleton, Table}, scoring::scores::{DerivedScore, ScoringConfig, WinStatus}};
+use crate::{db::{Singleton, Table}, scoring::{game::{RankingPointBreakdown, TiebreakSort, Tiebreaker}, scores::{ScoringConfig, WinStatus}}};
 
-use super::MatchType;
+use super::{Alliance, CommittedMatchScores, EventDetails, Match, MatchType};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct TeamRanking {
   pub team: usize,
 
   pub rp: usize,
-  pub auto_points: isize,
-  pub endgame_points: isize,
-  pub teleop_points: isize,
+  pub tiebreakers: Vec<isize>,    // Sum of each of the game's tiebreak keys, see GameScoring::TIEBREAKERS
+  // Each tiebreak key for every match, best first, for tiebreakers sorted by TiebreakSort::BestMatches
+  #[serde(default)]
+  pub tiebreaker_matches: Vec<Vec<isize>>,
+  // How each tiebreaker is compared, from the game at the time the rankings were updated
+  #[serde(default)]
+  pub tiebreak_sorts: Vec<TiebreakSort>,
   pub random_num: usize,
 
   pub win: usize,
   pub loss: usize,
   pub tie: usize,
   pub played: usize,
+  #[serde(default)]
+  pub dqs: usize,
 }
 
 impl Table for TeamRanking {
@@ -33,70 +39,134 @@ impl Table for TeamRanking {
   }
 }
 
+// What a team got out of a single qualification match, for explaining where they are in the rankings.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct TeamRankingMatch {
+  pub team: usize,
+  pub match_id: String,
+  pub match_name: String,
+  pub alliance: Alliance,
+  pub score: usize,
+  pub opponent_score: usize,
+  pub win_status: WinStatus,
+  pub rp: RankingPointBreakdown,
+  pub tiebreakers: Vec<isize>,
+  pub dq: bool,
+  // Surrogate appearances are shown, but don't count towards rankings
+  pub surrogate: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct TeamRankingDetail {
+  pub team: usize,
+  pub rank: Option<usize>,
+  pub ranking: Option<TeamRanking>,
+  pub tiebreakers: Vec<Tiebreaker>,
+  pub matches: Vec<TeamRankingMatch>,
+}
+
 impl TeamRanking {
   pub fn update(kv: &kv::KVConnection) -> anyhow::Result<()> {
-    let mut rankings_map = HashMap::new();
+    let sorts = EventDetails::get(kv)?.game.tiebreakers().iter().map(|t| t.sort).collect::<Vec<_>>();
+    let rankings_map = Self::rank(&Self::contributions(kv)?, &sorts);
 
-    let matches = super::Match::all_map(kv)?;
-    let scores = super::CommittedMatchScores::all(kv)?;
+    Self::clear(kv)?;
+    let r: anyhow::Result<Vec<()>> = rankings_map.into_values().map(|r| r.insert(kv)).collect();
+    r?;
+    Ok(())
+  }
 
-    for score in scores {
-      if let Some(m) = matches.get(&score.match_id) {
-        if m.match_type == MatchType::Qualification {
-          if let Some(score) = score.scores.last() {
-            let config = ScoringConfig::get(kv)?;
-            let score_red = score.red.derive(&score.blue, config);
-            let score_blue = score.blue.derive(&score.red, config);
+  // Every team's result in every qualification match that's been played, in match order
+  pub fn contributions(kv: &kv::KVConnection) -> anyhow::Result<Vec<TeamRankingMatch>> {
+    Self::contributions_from(&Match::all(kv)?, &CommittedMatchScores::all(kv)?, &ScoringConfig::get(kv)?)
+  }
 
-            for team in m.red_teams.iter().filter_map(|t| t.as_ref()) {
-              Self::update_single(*team, &score_red, m.dqs.contains(team), &mut rankings_map);
-            }
+  fn contributions_from(matches: &[Match], scores: &[CommittedMatchScores], config: &ScoringConfig) -> anyhow::Result<Vec<TeamRankingMatch>> {
+    let by_id = matches.iter().map(|m| (&m.id, m)).collect::<HashMap<_, _>>();
 
-            for team in m.blue_teams.iter().filter_map(|t| t.as_ref()) {
-              Self::update_single(*team, &score_blue, m.dqs.contains(team), &mut rankings_map);
+    let mut contributions = vec![];
+    let mut played = scores.iter().filter_map(|s| by_id.get(&s.match_id).map(|&m| (m, s))).collect::<Vec<_>>();
+    played.sort_by_key(|(m, _)| m.start_time);
+
+    for (m, score) in played {
+      if m.match_type == MatchType::Qualification && !m.superseded_by_replay(matches) {
+        if let Some(score) = score.scores.last() {
+          let score_red = score.red.derive(&score.blue, config)?;
+          let score_blue = score.blue.derive(&score.red, config)?;
+
+          for (alliance, teams, derived, other) in [
+            (Alliance::Red, &m.red_teams, &score_red, &score_blue),
+            (Alliance::Blue, &m.blue_teams, &score_blue, &score_red),
+          ] {
+            for team in teams.iter().filter_map(|t| *t) {
+              contributions.push(TeamRankingMatch {
+                team,
+                match_id: m.id.clone(),
+                match_name: m.name.clone(),
+                alliance,
+                score: derived.total_score(),
+                opponent_score: other.total_score(),
+                win_status: derived.win_status(),
+                rp: derived.ranking_points(),
+                tiebreakers: derived.tiebreak_keys(),
+                dq: m.dqs.contains(&team),
+                surrogate: m.is_surrogate(team),
+              });
             }
           }
         }
       }
     }
 
-    Self::clear(kv)?;
-    let r: anyhow::Result<Vec<()>> = rankings_map.into_values().map(|r| r.insert(kv)).collect();
-    r?;
-    Ok(())
+    Ok(contributions)
+  }
+
+  // Surrogate appearances don't count towards rankings
+  fn rank(contributions: &[TeamRankingMatch], sorts: &[TiebreakSort]) -> HashMap<usize, TeamRanking> {
+    let mut rankings_map = HashMap::new();
+    for contribution in contributions.iter().filter(|c| !c.surrogate) {
+      Self::update_single(contribution, sorts, &mut rankings_map);
+    }
+    rankings_map
   }
 
-  fn update_single(team: usize, score: &DerivedScore, is_dq: bool, current_rankings: &mut HashMap<usize, TeamRanking>) {
+  fn update_single(contribution: &TeamRankingMatch, sorts: &[TiebreakSort], current_rankings: &mut HashMap<usize, TeamRanking>) {
+    let team = contribution.team;
     let mut rng = rand::thread_rng();
     let mut existing = current_rankings.get(&team).cloned().unwrap_or(TeamRanking {
-      team, rp: 0, auto_points: 0,
-      endgame_points: 0, teleop_points: 0,
+      team, rp: 0, tiebreakers: vec![], tiebreaker_matches: vec![], tiebreak_sorts: sorts.to_vec(),
       random_num: rng.gen(),
-      win: 0, loss: 0, tie: 0, played: 0
+      win: 0, loss: 0, tie: 0, played: 0, dqs: 0
     });
 
-    if !is_dq {
-      existing.rp += score.total_bonus_rp;
-      existing.auto_points += score.mode_score.auto;
-      existing.teleop_points += score.mode_score.teleop;
-      existing.endgame_points += score.endgame_points;
+    if !contribution.dq {
+      existing.rp += contribution.rp.total();
+
+      let keys = &contribution.tiebreakers;
+      existing.tiebreakers.resize(existing.tiebreakers.len().max(keys.len()), 0);
+      existing.tiebreaker_matches.resize(existing.tiebreaker_matches.len().max(keys.len()), vec![]);
+      for (i, key) in keys.iter().enumerate() {
+        existing.tiebreakers[i] += key;
+        let values = &mut existing.tiebreaker_matches[i];
+        let pos = values.partition_point(|v| v >= key);
+        values.insert(pos, *key);
+      }
       
-      match score.win_status {
+      match contribution.win_status {
         WinStatus::WIN => {
-          existing.rp += 2;
           existing.win += 1;
         },
         WinStatus::LOSS => {
           existing.loss += 1;
         },
         WinStatus::TIE => {
-          existing.rp += 1;
           existing.tie += 1;
         },
       }
     } else {
       // DQ counts as a loss for that team in particular
       existing.loss += 1;
+      existing.dqs += 1;
     }
     
     // Need to increase this even if DQ'd since otherwise the average score screws up
@@ -110,6 +180,19 @@ impl TeamRanking {
     v.sort();
     Ok(v)
   }
+
+  pub fn detail(team: usize, kv: &kv::KVConnection) -> anyhow::Result<TeamRankingDetail> {
+    let sorted = Self::sorted(kv)?;
+    let rank = sorted.iter().position(|r| r.team == team);
+
+    Ok(TeamRankingDetail {
+      team,
+      rank: rank.map(|r| r + 1),
+      ranking: rank.map(|r| sorted[r].clone()),
+      tiebreakers: EventDetails::get(kv)?.game.tiebreakers().to_vec(),
+      matches: Self::contributions(kv)?.into_iter().filter(|c| c.team == team).collect(),
+    })
+  }
 }
 
 fn cmp_f64(a: f64, b: f64) -> std::cmp::Ordering {
@@ -147,17 +230,138 @@ impl Ord for TeamRanking {
     let n_self = self.played;
     let n_other = other.played;
 
-    // Game Manual Table 11-2
-    cmp_f64(avg(self.rp as isize, n_self), avg(other.rp as isize, n_other))
-      .then(cmp_f64(avg(self.auto_points, n_self), avg(other.auto_points, n_other)))
-      .then(cmp_f64(
-        avg(self.endgame_points, n_self),
-        avg(other.endgame_points, n_other),
-      ))
-      .then(cmp_f64(
-        avg(self.teleop_points, n_self),
-        avg(other.teleop_points, n_other),
-      ))
-      .then(cmp_f64(self.random_num as f64, other.random_num as f64))
+    // Average RP, then each of the game's tiebreakers in order (e.g. Game Manual Table 11-2 for 2024)
+    let mut ordering = cmp_f64(avg(self.rp as isize, n_self), avg(other.rp as isize, n_other));
+    for i in 0..self.tiebreakers.len().max(other.tiebreakers.len()) {
+      let (sum_self, sum_other) = (self.tiebreakers.get(i).copied().unwrap_or(0), other.tiebreakers.get(i).copied().unwrap_or(0));
+      ordering = ordering.then(match self.tiebreak_sorts.get(i).copied().unwrap_or_default() {
+        TiebreakSort::Average => cmp_f64(avg(sum_self, n_self), avg(sum_other, n_other)),
+        TiebreakSort::Total => sum_other.cmp(&sum_self),
+        TiebreakSort::BestMatches => {
+          // Only as many matches as both teams have are compared, so having played more isn't an advantage in itself
+          let (best_self, best_other) = (self.tiebreaker_matches.get(i).map(|v| v.as_slice()).unwrap_or(&[]), other.tiebreaker_matches.get(i).map(|v| v.as_slice()).unwrap_or(&[]));
+          let n = best_self.len().min(best_other.len());
+          best_other[..n].cmp(&best_self[..n])
+        },
+      });
+    }
+
+    ordering.then(cmp_f64(self.random_num as f64, other.random_num as f64))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{models::{Alliance, CommittedMatchScores, Match, MatchType}, scoring::{crescendo::CrescendoLiveScore, game::{Game, TiebreakSort}, scores::{LiveScore, MatchScore, ScoringConfig, WinStatus}}};
+
+  use super::TeamRanking;
+
+  fn ranking(team: usize, random_num: usize, sort: TiebreakSort, matches: Vec<isize>) -> TeamRanking {
+    TeamRanking {
+      team, rp: 2 * matches.len(), tiebreakers: vec![ matches.iter().sum() ], tiebreaker_matches: vec![ matches.clone() ], tiebreak_sorts: vec![ sort ],
+      random_num, win: matches.len(), loss: 0, tie: 0, played: matches.len(), dqs: 0
+    }
+  }
+
+  fn teams(mut rankings: Vec<TeamRanking>) -> Vec<usize> {
+    rankings.sort();
+    rankings.into_iter().map(|r| r.team).collect()
+  }
+
+  #[test]
+  fn tiebreak_sorts() {
+    // 1 has the higher average, 2 has the higher total
+    assert_eq!(teams(vec![ ranking(1, 0, TiebreakSort::Average, vec![ 30 ]), ranking(2, 0, TiebreakSort::Average, vec![ 20, 20 ]) ]), vec![ 1, 2 ]);
+    assert_eq!(teams(vec![ ranking(1, 0, TiebreakSort::Total, vec![ 30 ]), ranking(2, 0, TiebreakSort::Total, vec![ 20, 20 ]) ]), vec![ 2, 1 ]);
+
+    // Best match first, then the next best
+    assert_eq!(teams(vec![ ranking(1, 0, TiebreakSort::BestMatches, vec![ 30, 5 ]), ranking(2, 0, TiebreakSort::BestMatches, vec![ 30, 10 ]) ]), vec![ 2, 1 ]);
+    assert_eq!(teams(vec![ ranking(1, 0, TiebreakSort::BestMatches, vec![ 30, 5 ]), ranking(2, 0, TiebreakSort::BestMatches, vec![ 25, 25 ]) ]), vec![ 1, 2 ]);
+
+    // An extra match doesn't count for anything, so this comes down to the random number
+    assert_eq!(teams(vec![ ranking(1, 10, TiebreakSort::BestMatches, vec![ 30 ]), ranking(2, 5, TiebreakSort::BestMatches, vec![ 30, 10 ]) ]), vec![ 1, 2 ]);
+    assert_eq!(teams(vec![ ranking(1, 5, TiebreakSort::BestMatches, vec![ 30 ]), ranking(2, 10, TiebreakSort::BestMatches, vec![ 30, 10 ]) ]), vec![ 2, 1 ]);
+  }
+
+  fn qual(set: usize, red: [usize; 3], blue: [usize; 3]) -> Match {
+    Match {
+      id: Match::gen_id(MatchType::Qualification, 1, set, 1),
+      name: Match::gen_name(MatchType::Qualification, 1, set, 1),
+      start_time: chrono::Local::now() + chrono::Duration::minutes(set as i64),
+      match_type: MatchType::Qualification,
+      round: 1, set_number: set, match_number: 1,
+      blue_teams: blue.map(Some).to_vec(), blue_alliance: None,
+      red_teams: red.map(Some).to_vec(), red_alliance: None,
+      dqs: vec![], surrogates: vec![], replay_reason: None,
+      actual_start_time: None, original_start_time: None,
+      played: true, ready: true
+    }
+  }
+
+  fn scored(m: &Match, red_adjust: isize, blue_adjust: isize) -> CommittedMatchScores {
+    let live = |adjustment| LiveScore::Crescendo2024(CrescendoLiveScore { adjustment, ..CrescendoLiveScore::new(3) });
+    CommittedMatchScores { match_id: m.id.clone(), scores: vec![ MatchScore { red: live(red_adjust), blue: live(blue_adjust) } ], last_update: chrono::Local::now() }
+  }
+
+  #[test]
+  fn contributions() {
+    let config = ScoringConfig::new(Game::Crescendo2024);
+    let mut qm2 = qual(2, [ 1, 2, 3 ], [ 4, 5, 6 ]);
+    qm2.dqs = vec![ 3 ];
+    let qm1 = qual(1, [ 4, 5, 6 ], [ 1, 2, 3 ]);
+    let unplayed = qual(3, [ 1, 2, 3 ], [ 4, 5, 6 ]);
+
+    let matches = vec![ qm2.clone(), qm1.clone(), unplayed ];
+    let scores = vec![ scored(&qm2, 20, 10), scored(&qm1, 5, 5) ];
+    let contributions = TeamRanking::contributions_from(&matches, &scores, &config).unwrap();
+
+    // Every team in every match with a score, in match order
+    assert_eq!(contributions.len(), 12);
+    assert!(contributions[..6].iter().all(|c| c.match_id == qm1.id && c.win_status == WinStatus::TIE && c.rp.total() == 1));
+
+    let team1 = contributions[6..].iter().find(|c| c.team == 1).unwrap();
+    assert_eq!((team1.alliance, team1.score, team1.opponent_score, team1.win_status.clone(), team1.rp.total()), (Alliance::Red, 20, 10, WinStatus::WIN, 2));
+    assert_eq!(team1.tiebreakers[1], 20);
+    assert!(!team1.dq);
+    assert!(contributions[6..].iter().find(|c| c.team == 3).unwrap().dq);
+    assert_eq!(contributions[6..].iter().find(|c| c.team == 4).unwrap().rp.total(), 0);
+  }
+
+  #[test]
+  fn replays() {
+    let config = ScoringConfig::new(Game::Crescendo2024);
+    let original = qual(1, [ 1, 2, 3 ], [ 4, 5, 6 ]);
+    let mut replay = original.replay("Field Fault".to_owned(), original.start_time + chrono::Duration::minutes(30)).unwrap();
+    let mut scores = vec![ scored(&original, 20, 10) ];
+
+    // The original counts until the replay has been played
+    let contributions = TeamRanking::contributions_from(&[ original.clone(), replay.clone() ], &scores, &config).unwrap();
+    assert!(contributions.len() == 6 && contributions.iter().all(|c| c.match_id == original.id));
+
+    replay.played = true;
+    scores.push(scored(&replay, 5, 15));
+    let contributions = TeamRanking::contributions_from(&[ original.clone(), replay.clone() ], &scores, &config).unwrap();
+    assert!(contributions.len() == 6 && contributions.iter().all(|c| c.match_id == replay.id));
+    assert_eq!(contributions.iter().find(|c| c.team == 1).unwrap().win_status, WinStatus::LOSS);
+  }
+
+  #[test]
+  fn surrogates() {
+    let config = ScoringConfig::new(Game::Crescendo2024);
+    let qm1 = qual(1, [ 1, 2, 3 ], [ 4, 5, 6 ]);
+    let mut qm2 = qual(2, [ 4, 5, 3 ], [ 1, 2, 6 ]);
+    qm2.surrogates = vec![ 3 ];
+
+    let scores = vec![ scored(&qm1, 0, 10), scored(&qm2, 20, 10) ];
+    let contributions = TeamRanking::contributions_from(&[ qm1, qm2 ], &scores, &config).unwrap();
+    assert_eq!(contributions.iter().filter(|c| c.surrogate).map(|c| c.team).collect::<Vec<_>>(), vec![ 3 ]);
+
+    // Team 3's surrogate win doesn't count, but their teammates' does
+    let rankings = TeamRanking::rank(&contributions, &[ TiebreakSort::Total ]);
+    assert_eq!(rankings.len(), 6);
+    let team3 = &rankings[&3];
+    assert_eq!((team3.played, team3.win, team3.loss, team3.rp), (1, 0, 1, 0));
+    let team4 = &rankings[&4];
+    assert_eq!((team4.played, team4.win, team4.loss, team4.rp), (2, 2, 0, 4));
   }
 }
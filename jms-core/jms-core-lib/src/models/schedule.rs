@@ -3,7 +3,9 @@ use std::convert::Infallible;
 use jms_base::kv;
 use uuid::Uuid;
 
-use crate::db::{Table, DBDuration};
+use crate::db::{Singleton, Table, DBDuration};
+
+use super::{Match, MatchType};
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 #[serde(tag = "type")]
@@ -75,3 +77,47 @@ impl ScheduleBlock {
     Ok(v)
   }
 }
+
+// How far the field is running behind (positive) or ahead (negative) of the original schedule. This is the larger of
+// the difference between when the last match actually started and when it was scheduled, and how overdue the next
+// match is if it hasn't started yet. Re-timed matches are measured against their original start time.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ScheduleSlip {
+  pub delta: DBDuration,
+  pub last_started: Option<String>,
+}
+
+impl ScheduleSlip {
+  pub fn compute(matches: &[Match]) -> Self {
+    let now = chrono::Local::now();
+
+    let last_started = matches.iter()
+      .filter(|m| m.match_type != MatchType::Test)
+      .filter_map(|m| m.actual_start_time.map(|t| (t, m)))
+      .max_by_key(|(t, _)| *t);
+
+    let mut delta = last_started.map(|(t, m)| t - m.scheduled_start_time()).unwrap_or(chrono::Duration::zero());
+
+    let next = matches.iter()
+      .filter(|m| m.match_type != MatchType::Test && !m.played && m.actual_start_time.is_none())
+      .min_by_key(|m| m.start_time);
+    if let Some(next) = next {
+      if now > next.scheduled_start_time() {
+        delta = delta.max(now - next.scheduled_start_time());
+      }
+    }
+
+    Self { delta: delta.into(), last_started: last_started.map(|(_, m)| m.name.clone()) }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct RetimeSettings {
+  // Re-time the remaining Qualification matches after each match is committed
+  pub auto_retime: bool,
+}
+
+// Not under db:schedule, which holds the schedule blocks
+impl Singleton for RetimeSettings {
+  const KEY: &'static str = "db:schedule_retime";
+}
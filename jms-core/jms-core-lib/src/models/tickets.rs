@@ -31,3 +31,22 @@ impl Table for SupportTicket {
     self.id.clone()
   }
 }
+
+impl SupportTicket {
+  pub fn new(team: usize, match_id: Option<String>, issue_type: String, author: String) -> Self {
+    Self {
+      id: uuid::Uuid::new_v4().to_string(),
+      team,
+      match_id,
+      issue_type,
+      notes: vec![TicketComment {
+        author: author.clone(),
+        time: chrono::Local::now(),
+        comment: format!("Ticket Opened by {}", author)
+      }],
+      author,
+      assigned_to: None,
+      resolved: false
+    }
+  }
+}
@@ -0,0 +1,58 @@
+use std::convert::Infallible;
+
+use jms_base::kv;
+
+use crate::db::{generate_id, Table};
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(tag = "type")]
+pub enum TimeoutKind {
+  Field,
+  Alliance { alliance: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Timeout {
+  pub id: String,
+  pub kind: TimeoutKind,
+  pub called_by: String,
+  pub start_time: chrono::DateTime<chrono::Local>,
+  pub end_time: chrono::DateTime<chrono::Local>,
+}
+
+impl Table for Timeout {
+  const PREFIX: &'static str = "db:timeouts";
+  type Err = Infallible;
+  type Id = String;
+
+  fn id(&self) -> Self::Id {
+    self.id.clone()
+  }
+}
+
+impl Timeout {
+  pub fn new(kind: TimeoutKind, called_by: String, duration: chrono::Duration) -> Self {
+    let now = chrono::Local::now();
+    Self { id: generate_id(), kind, called_by, start_time: now, end_time: now + duration }
+  }
+
+  pub fn sorted(kv: &kv::KVConnection) -> anyhow::Result<Vec<Self>> {
+    let mut v = Self::all(kv)?;
+    v.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+    Ok(v)
+  }
+
+  pub fn remaining(&self) -> chrono::Duration {
+    (self.end_time - chrono::Local::now()).max(chrono::Duration::zero())
+  }
+
+  // Each playoff alliance gets a single timeout for the whole tournament
+  pub fn alliance_timeout_used(alliance: usize, kv: &kv::KVConnection) -> anyhow::Result<bool> {
+    Ok(Self::all(kv)?.iter().any(|t| t.kind == TimeoutKind::Alliance { alliance }))
+  }
+
+  // The next playoff match can't start until every timeout has finished.
+  pub fn playoff_hold(kv: &kv::KVConnection) -> anyhow::Result<Option<chrono::DateTime<chrono::Local>>> {
+    Ok(Self::all(kv)?.iter().map(|t| t.end_time).max())
+  }
+}
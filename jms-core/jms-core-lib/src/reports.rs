@@ -3,12 +3,22 @@ use crate::models::MatchType;
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct ReportData {
   data: Vec<u8>,
-  mime: String
+  mime: String,
+  // Reports with a filename are downloaded rather than opened in the browser
+  #[serde(default)]
+  filename: Option<String>
 }
 
 impl ReportData {
-  pub fn pdf(data: Vec<u8>) -> Self { Self { data, mime: "application/pdf".to_owned() } }
-  pub fn csv(data: Vec<u8>) -> Self { Self { data, mime: "text/csv".to_owned() } }
+  pub fn pdf(data: Vec<u8>) -> Self { Self { data, mime: "application/pdf".to_owned(), filename: None } }
+  pub fn csv(data: Vec<u8>) -> Self { Self { data, mime: "text/csv".to_owned(), filename: None } }
+  pub fn binary(data: Vec<u8>) -> Self { Self { data, mime: "application/octet-stream".to_owned(), filename: None } }
+  pub fn zip(data: Vec<u8>) -> Self { Self { data, mime: "application/zip".to_owned(), filename: None } }
+
+  pub fn with_filename(mut self, filename: String) -> Self {
+    self.filename = Some(filename);
+    self
+  }
 }
 
 #[jms_macros::service]
@@ -1,7 +1,90 @@
-#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+use std::num::ParseIntError;
+
+use crate::{db::Table, models::Match};
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct QualsMatchGeneratorParams {
   pub team_anneal_steps: usize,
   pub station_anneal_steps: usize,
+  // The same seed, teams, and schedule blocks will always produce the same schedule. If not provided, one is picked at random.
+  #[serde(default)]
+  pub seed: Option<u64>,
+  // Generate the quality report without committing the matches, so different seeds can be compared.
+  #[serde(default)]
+  pub dry_run: bool,
+  // Minimum number of matches between a team's appearances, where 1 allows back-to-back matches. Soft constraints are
+  // penalised by the annealer, while hard constraints reject any schedule that breaks them.
+  #[serde(default)]
+  pub min_turnaround: usize,
+  #[serde(default)]
+  pub hard_turnaround: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct QualsTeamReport {
+  pub team: usize,
+  pub matches: usize,
+  pub station_counts: Vec<usize>,
+  pub min_turnaround: Option<usize>,
+}
+
+// Quality of a generated Qualification Schedule. Partner / Opponent counts are the number of times any two teams
+// share an alliance / face each other, and turnarounds are the number of matches between appearances (1 is back-to-back).
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct QualsScheduleReport {
+  pub seed: u64,
+  pub committed: bool,
+  pub generated_at: chrono::DateTime<chrono::Local>,
+  pub params: QualsMatchGeneratorParams,
+  pub num_teams: usize,
+  pub num_matches: usize,
+  pub team_balance_score: f64,
+  pub station_balance_score: f64,
+  pub min_partner: usize,
+  pub max_partner: usize,
+  pub min_opponent: usize,
+  pub max_opponent: usize,
+  // Largest difference between the number of times a team is in its most and least used station
+  pub max_station_imbalance: usize,
+  pub min_turnaround: Option<usize>,
+  // Appearances that come sooner than the requested minimum turnaround
+  pub turnaround_violations: usize,
+  pub teams: Vec<QualsTeamReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum ScheduleDiffKind {
+  Added,
+  Removed,
+  Changed,
+  Unchanged,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ScheduleDiffEntry {
+  pub kind: ScheduleDiffKind,
+  pub name: String,
+  pub before: Option<Match>,
+  pub after: Option<Match>,
+}
+
+// The result of importing a Qualification Schedule. When importing as a dry run, nothing is written and the diff
+// describes what would happen to the existing schedule.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct QualsImportResult {
+  pub committed: bool,
+  pub warnings: Vec<String>,
+  pub diff: Vec<ScheduleDiffEntry>,
+}
+
+impl Table for QualsScheduleReport {
+  const PREFIX: &'static str = "db:quals_report";
+  type Id = u64;
+  type Err = ParseIntError;
+
+  fn id(&self) -> Self::Id {
+    self.seed
+  }
 }
 
 pub const MATCH_GENERATOR_JOB_KEY: &'static str = "job:match_gen:working";
@@ -9,6 +92,8 @@ pub const MATCH_GENERATOR_JOB_KEY: &'static str = "job:match_gen:working";
 #[jms_macros::service]
 pub trait MatchGeneratorRPC {
   async fn start_qual_gen(params: QualsMatchGeneratorParams) -> Result<(), String>;
+  async fn import_quals(data: String, dry_run: bool) -> Result<QualsImportResult, String>;
+  async fn retime_schedule() -> Result<usize, String>;
   async fn reset_playoffs() -> Result<(), String>;
   async fn update_playoffs() -> Result<(), String>;
 }
\ No newline at end of file
@@ -0,0 +1,138 @@
+use crate::{db::DBDuration, models::Alliance};
+
+use super::{crescendo::Crescendo2024, scores::WinStatus};
+
+// A game is the set of rules that turns what happened on the field into points, ranking points and tiebreakers.
+// Each season gets its own implementation of GameScoring, and the event selects which one is used through EventDetails.
+// Everything outside of the game implementation (rankings, TBA, field electronics, reports) should only go through this
+// trait (or the dispatching enums in scores.rs) so we can run off-season events on a different game year.
+pub trait GameScoring {
+  type Config: std::fmt::Debug + Clone + Default + PartialEq + Eq + serde::Serialize + serde::de::DeserializeOwned + schemars::JsonSchema;
+  type Live: std::fmt::Debug + Clone + PartialEq + Eq + serde::Serialize + serde::de::DeserializeOwned + schemars::JsonSchema;
+  type Derived: std::fmt::Debug + Clone + PartialEq + Eq + serde::Serialize + serde::de::DeserializeOwned + schemars::JsonSchema;
+  type Update: std::fmt::Debug + Clone + serde::de::DeserializeOwned + schemars::JsonSchema;
+
+  const NAME: &'static str;
+
+  // The values returned by tiebreak_keys, in the order they are applied (after average ranking points).
+  const TIEBREAKERS: &'static [Tiebreaker];
+
+  fn new_live(num_teams: usize) -> Self::Live;
+  fn randomise(num_teams: usize) -> Self::Live;
+
+  // TODO: This needs better error handling - currently all inputs are assumed to be correct
+  fn update(live: &mut Self::Live, update: Self::Update);
+
+  // Updates that may be submitted by the Human Player of an alliance, as opposed to a referee.
+  fn is_human_player_update(_update: &Self::Update) -> bool { false }
+
+  fn disqualify(live: &mut Self::Live);
+
+  fn derive(live: &Self::Live, other_alliance: &Self::Live, config: &Self::Config) -> Self::Derived;
+
+  fn total_score(derived: &Self::Derived) -> usize;
+  fn win_status(derived: &Self::Derived) -> WinStatus;
+  fn ranking_points(derived: &Self::Derived) -> RankingPointBreakdown;
+  fn tiebreak_keys(derived: &Self::Derived) -> Vec<isize>;
+  fn indicators(derived: &Self::Derived) -> ScoreIndicators;
+
+  // Score breakdown for a single alliance, in the format TBA expects for this game year.
+  fn tba_breakdown(live: &Self::Live, derived: &Self::Derived) -> serde_json::Value;
+
+  // Game-specific data sent to the driver stations of an alliance (e.g. 2020's Position Control colour), if the game has any.
+  fn game_data(_match_id: &str, _alliance: Alliance) -> Option<String> { None }
+}
+
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, strum::EnumIter, strum::Display, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum Game {
+  #[default]
+  Crescendo2024
+}
+
+impl Game {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Game::Crescendo2024 => Crescendo2024::NAME,
+    }
+  }
+
+  pub fn tiebreakers(&self) -> &'static [Tiebreaker] {
+    match self {
+      Game::Crescendo2024 => Crescendo2024::TIEBREAKERS,
+    }
+  }
+
+  pub fn game_data(&self, match_id: &str, alliance: Alliance) -> Option<String> {
+    match self {
+      Game::Crescendo2024 => Crescendo2024::game_data(match_id, alliance),
+    }
+  }
+
+  pub fn descriptor(&self) -> GameDescriptor {
+    GameDescriptor {
+      game: *self,
+      name: self.name().to_owned(),
+      tiebreakers: self.tiebreakers().to_vec()
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct GameDescriptor {
+  pub game: Game,
+  pub name: String,
+  pub tiebreakers: Vec<Tiebreaker>,
+}
+
+// How a tiebreaker's per-match values are compared between teams. Higher is always better.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum TiebreakSort {
+  #[default]
+  Average,
+  Total,
+  // Best match first, then the next best, and so on
+  BestMatches,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct Tiebreaker {
+  pub name: std::borrow::Cow<'static, str>,
+  pub sort: TiebreakSort,
+}
+
+impl Tiebreaker {
+  pub const fn new(name: &'static str, sort: TiebreakSort) -> Self {
+    Self { name: std::borrow::Cow::Borrowed(name), sort }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct RankingPointBreakdown {
+  pub win_rp: usize,
+  pub bonus_rp: Vec<(String, usize)>,
+}
+
+impl RankingPointBreakdown {
+  pub fn total_bonus(&self) -> usize {
+    self.bonus_rp.iter().map(|(_, rp)| *rp).sum()
+  }
+
+  pub fn total(&self) -> usize {
+    self.win_rp + self.total_bonus()
+  }
+}
+
+// Game-agnostic status used by displays that don't know about the specifics of the game, e.g. the alliance station lighting.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ScoreIndicators {
+  pub coopertition: bool,
+  pub bonus_remaining: Option<DBDuration>,
+  pub progress: Option<ProgressIndicator>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct ProgressIndicator {
+  pub current: usize,
+  pub target: usize,
+  pub achieved: bool,
+}
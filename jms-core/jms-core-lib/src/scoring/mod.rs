@@ -1 +1,3 @@
+pub mod game;
 pub mod scores;
+pub mod crescendo;
@@ -1,14 +1,17 @@
 use std::collections::HashMap;
 
-use genpdf::{style, elements::{TableLayout, PageBreak}};
+use genpdf::{style, elements::{Break, Paragraph, TableLayout, PageBreak}, Element};
 use jms_base::kv;
 use jms_core_lib::{models::{self, Team}, db::{Singleton, Table}, reports::ReportData};
 
 use super::{pdf_table, report_pdf, render_header};
 
-fn render_team(team: Option<&Option<usize>>, highlight: Option<usize>, alliance: models::Alliance, teams: &HashMap<usize, Team>) -> style::StyledString {
+fn render_team(m: &models::Match, team: Option<&Option<usize>>, highlight: Option<usize>, alliance: models::Alliance, teams: &HashMap<usize, Team>) -> style::StyledString {
   let team = team.and_then(|t| t.clone());
-  let t_string = team.map_or("".to_owned(), |t| teams.get(&t).map(|team| team.display_number.clone()).unwrap_or(format!("{}", t)));
+  let mut t_string = team.map_or("".to_owned(), |t| teams.get(&t).map(|team| team.display_number.clone()).unwrap_or(format!("{}", t)));
+  if team.map(|t| m.is_surrogate(t)).unwrap_or(false) {
+    t_string += "*";
+  }
 
   let text_style = match (team, highlight) {
     (Some(a), Some(b)) if a == b => style::Style::new().bold().with_font_size(12).and(match alliance {
@@ -34,18 +37,25 @@ fn render_match_table(matches: &Vec<models::Match>, teams: &HashMap<usize, Team>
           style::Style::new(),
         ),
         style::StyledString::new(m.name.clone(), style::Style::new()),
-        render_team(m.blue_teams.get(0), team_highlight, models::Alliance::Blue, teams),
-        render_team(m.blue_teams.get(1), team_highlight, models::Alliance::Blue, teams),
-        render_team(m.blue_teams.get(2), team_highlight, models::Alliance::Blue, teams),
-        render_team(m.red_teams.get(0), team_highlight, models::Alliance::Red, teams),
-        render_team(m.red_teams.get(1), team_highlight, models::Alliance::Red, teams),
-        render_team(m.red_teams.get(2), team_highlight, models::Alliance::Red, teams),
+        render_team(m, m.blue_teams.get(0), team_highlight, models::Alliance::Blue, teams),
+        render_team(m, m.blue_teams.get(1), team_highlight, models::Alliance::Blue, teams),
+        render_team(m, m.blue_teams.get(2), team_highlight, models::Alliance::Blue, teams),
+        render_team(m, m.red_teams.get(0), team_highlight, models::Alliance::Red, teams),
+        render_team(m, m.red_teams.get(1), team_highlight, models::Alliance::Red, teams),
+        render_team(m, m.red_teams.get(2), team_highlight, models::Alliance::Red, teams),
       ]
     })
     .collect();
   pdf_table(weights, headers, rows)
 }
 
+fn render_surrogate_note(doc: &mut genpdf::Document, matches: &Vec<models::Match>) {
+  if matches.iter().any(|m| !m.surrogates.is_empty()) {
+    doc.push(Break::new(1));
+    doc.push(Paragraph::new("* Surrogate - this match does not count towards the team's ranking").styled(style::Style::new().italic().with_font_size(10)));
+  }
+}
+
 pub fn match_report(mtype: models::MatchType, kv: &kv::KVConnection) -> Result<ReportData, Box<dyn std::error::Error>> {
   let mut buf = vec![];
 
@@ -59,6 +69,7 @@ pub fn match_report(mtype: models::MatchType, kv: &kv::KVConnection) -> Result<R
   let mut doc = report_pdf(&title, &event_name, true);
 
   doc.push(render_match_table(&matches, &teams, None));
+  render_surrogate_note(&mut doc, &matches);
   doc.render(&mut buf)?;
 
   Ok(ReportData::pdf(buf))
@@ -83,6 +94,7 @@ pub fn match_report_per_team(mtype: models::MatchType, kv: &kv::KVConnection) ->
       &event_name,
     );
     doc.push(render_match_table(&matches, &teams, Some(team.number)));
+    render_surrogate_note(&mut doc, &matches);
     if i != teams.len() - 1 {
       doc.push(PageBreak::new());
     }
@@ -10,24 +10,34 @@ pub fn rankings_report(kv: &kv::KVConnection) -> Result<ReportData, Box<dyn std:
   let event_name = event_details.event_name.unwrap_or("Unnamed Event".to_owned());
   let rankings = models::TeamRanking::sorted(kv)?;
 
+  let tiebreakers = event_details.game.tiebreakers();
+
   let mut doc = report_pdf("Qualification Rankings Report", &event_name, true);
 
-  let weights = vec![3, 3, 3, 3, 3, 5, 3, 3];
-  let headers = vec!["Rank", "Team", "Played", "RP", "Auto", "Endgame", "Teleop", "W-L-T"];
+  let mut weights = vec![3, 3, 3, 3];
+  let mut headers = vec!["Rank", "Team", "Played", "RP"];
+  for tiebreaker in tiebreakers {
+    weights.push(3);
+    headers.push(&tiebreaker.name);
+  }
+  weights.push(3);
+  headers.push("W-L-T");
+
   let rows: Vec<Vec<String>> = rankings
     .iter()
     .enumerate()
     .map(|(i, r)| {
-      vec![
+      let mut row = vec![
         format!("{}", i + 1),
         Team::display_number(r.team, kv),
         format!("{}", r.played),
         format!("{}", r.rp),
-        format!("{}", r.auto_points),
-        format!("{}", r.endgame_points),
-        format!("{}", r.teleop_points),
-        format!("{}-{}-{}", r.win, r.loss, r.tie),
-      ]
+      ];
+      for i in 0..tiebreakers.len() {
+        row.push(format!("{}", r.tiebreakers.get(i).copied().unwrap_or(0)));
+      }
+      row.push(format!("{}-{}-{}", r.win, r.loss, r.tie));
+      row
     })
     .collect();
   let table = pdf_table(weights, headers, rows);
@@ -4,9 +4,9 @@ use jms_core_lib::{db::Table, models::{Alliance, CommittedMatchScores, Match, Ma
 
 use crate::schedule::playoffs::PlayoffAllianceDescriptor;
 
-use super::playoffs::{IncompleteMatch, GenerationUpdate, PlayoffScheduleItem};
+use super::playoffs::{IncompleteMatch, GenerationUpdate, PlayoffScheduleItem, PlayoffTemplate};
 
-pub const DOUBLE_BRACKET_TEMPLATE: [PlayoffScheduleItem; 27] = [
+pub const DOUBLE_BRACKET_TEMPLATE: [PlayoffScheduleItem; 23] = [
   // Round 1
   // Upper
   PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(1), blue: PlayoffAllianceDescriptor::Alliance(8) }),
@@ -46,57 +46,90 @@ pub const DOUBLE_BRACKET_TEMPLATE: [PlayoffScheduleItem; 27] = [
   PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 5, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::LoserOf(MatchType::Playoff, 4, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 4, 2) }),
   PlayoffScheduleItem::TiebreakerSlot,
   PlayoffScheduleItem::AwardsBreak,
+];
 
-  // Finals
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Final, round: 1, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 4, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 5, 1) }),
-  PlayoffScheduleItem::AwardsBreak,
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Final, round: 1, set: 1, match_num: 2, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 4, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 5, 1) }),
-  PlayoffScheduleItem::TiebreakerSlot,
+pub const DOUBLE_BRACKET_FINAL: IncompleteMatch = IncompleteMatch { ty: MatchType::Final, round: 1, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 4, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 5, 1) };
+
+// Sets of the single bracket. Each is expanded into the required number of matches by best_of_sets.
+pub const SINGLE_BRACKET_QUARTERS: [IncompleteMatch; 4] = [
+  IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(1), blue: PlayoffAllianceDescriptor::Alliance(8) },
+  IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 2, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(4), blue: PlayoffAllianceDescriptor::Alliance(5) },
+  IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 3, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(2), blue: PlayoffAllianceDescriptor::Alliance(7) },
+  IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 4, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(3), blue: PlayoffAllianceDescriptor::Alliance(6) },
 ];
 
-pub const SINGLE_BRACKET_TEMPLATE: [PlayoffScheduleItem; 24] = [
-  // Round 1 - Quarters
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(1), blue: PlayoffAllianceDescriptor::Alliance(8) }),
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 2, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(4), blue: PlayoffAllianceDescriptor::Alliance(5) }),
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 3, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(2), blue: PlayoffAllianceDescriptor::Alliance(7) }),
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 4, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(3), blue: PlayoffAllianceDescriptor::Alliance(6) }),
-  PlayoffScheduleItem::TiebreakerSlot,
-  PlayoffScheduleItem::AwardsBreak,
+pub const SINGLE_BRACKET_SEMIS: [IncompleteMatch; 2] = [
+  IncompleteMatch { ty: MatchType::Playoff, round: 2, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 2) },
+  IncompleteMatch { ty: MatchType::Playoff, round: 2, set: 2, match_num: 1, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 3), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 4) },
+];
 
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 1, match_num: 2, red: PlayoffAllianceDescriptor::Alliance(1), blue: PlayoffAllianceDescriptor::Alliance(8) }),
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 2, match_num: 2, red: PlayoffAllianceDescriptor::Alliance(4), blue: PlayoffAllianceDescriptor::Alliance(5) }),
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 3, match_num: 2, red: PlayoffAllianceDescriptor::Alliance(2), blue: PlayoffAllianceDescriptor::Alliance(7) }),
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 1, set: 4, match_num: 2, red: PlayoffAllianceDescriptor::Alliance(3), blue: PlayoffAllianceDescriptor::Alliance(6) }),
-  PlayoffScheduleItem::TiebreakerSlot,
-  PlayoffScheduleItem::AwardsBreak,
+pub const SINGLE_BRACKET_FINAL: IncompleteMatch = IncompleteMatch { ty: MatchType::Final, round: 1, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 2, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 2, 2) };
 
-  // Round 2 - Semis
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 2, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 2) }),
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 2, set: 2, match_num: 1, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 3), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 4) }),
-  PlayoffScheduleItem::TiebreakerSlot,
-  PlayoffScheduleItem::AwardsBreak,
+// Expand a round of sets into enough matches for one alliance to take each set. Matches are interleaved between sets
+// (i.e. match 1 of each set, then match 2 of each set), with tiebreakers queued at the end of each pass.
+pub fn best_of_sets(sets: &[IncompleteMatch], best_of: usize) -> Vec<PlayoffScheduleItem> {
+  let n_matches = PlayoffMode::wins_required(best_of);
+  let is_final = sets.iter().all(|s| s.ty == MatchType::Final);
 
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 2, set: 1, match_num: 2, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 2) }),
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Playoff, round: 2, set: 2, match_num: 2, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 3), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 1, 4) }),
-  PlayoffScheduleItem::TiebreakerSlot,
-  PlayoffScheduleItem::AwardsBreak,
+  let mut items = vec![];
+  for match_num in 1..=n_matches {
+    for set in sets {
+      items.push(PlayoffScheduleItem::Match(IncompleteMatch { match_num, ..set.clone() }));
+    }
 
-  // Finals
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Final, round: 1, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 2, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 2, 2) }),
-  PlayoffScheduleItem::AwardsBreak,
-  PlayoffScheduleItem::Match(IncompleteMatch { ty: MatchType::Final, round: 1, set: 1, match_num: 2, red: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 2, 1), blue: PlayoffAllianceDescriptor::WinnerOf(MatchType::Playoff, 2, 2) }),
-  PlayoffScheduleItem::TiebreakerSlot,
-];
+    if is_final {
+      // Finals don't need a break after the last match, and only need tiebreakers at the end
+      match match_num == n_matches {
+        true => items.push(PlayoffScheduleItem::TiebreakerSlot),
+        false => items.push(PlayoffScheduleItem::AwardsBreak),
+      }
+    } else {
+      items.push(PlayoffScheduleItem::TiebreakerSlot);
+      items.push(PlayoffScheduleItem::AwardsBreak);
+    }
+  }
+  items
+}
+
+pub fn single_bracket_template(playoff_mode: &PlayoffMode) -> PlayoffTemplate {
+  let mut items = vec![];
+  items.extend(best_of_sets(&SINGLE_BRACKET_QUARTERS, playoff_mode.playoff_best_of));
+  items.extend(best_of_sets(&SINGLE_BRACKET_SEMIS, playoff_mode.playoff_best_of));
+  items.extend(best_of_sets(&[SINGLE_BRACKET_FINAL], playoff_mode.final_best_of));
+
+  PlayoffTemplate {
+    items,
+    playoff_wins: Some(PlayoffMode::wins_required(playoff_mode.playoff_best_of)),
+    final_wins: PlayoffMode::wins_required(playoff_mode.final_best_of)
+  }
+}
+
+pub fn double_bracket_template(playoff_mode: &PlayoffMode) -> PlayoffTemplate {
+  let mut items = DOUBLE_BRACKET_TEMPLATE.to_vec();
+  items.extend(best_of_sets(&[DOUBLE_BRACKET_FINAL], playoff_mode.final_best_of));
+
+  PlayoffTemplate {
+    items,
+    playoff_wins: Some(1),
+    final_wins: PlayoffMode::wins_required(playoff_mode.final_best_of)
+  }
+}
 
-pub fn bracket_update(playoff_mode: &PlayoffMode, matches: &Vec<Match>, scores: &HashMap<String, CommittedMatchScores>, config: ScoringConfig) -> anyhow::Result<GenerationUpdate> {
+pub fn bracket_update(playoff_mode: &PlayoffMode, matches: &Vec<Match>, scores: &HashMap<String, CommittedMatchScores>, config: &ScoringConfig) -> anyhow::Result<GenerationUpdate> {
   if playoff_mode.n_alliances > 8 {
     anyhow::bail!("Brackets do not currently support >8 alliances!");
   }
 
-  let bracket: &[PlayoffScheduleItem] = match playoff_mode.mode {
-    PlayoffModeType::Bracket => &SINGLE_BRACKET_TEMPLATE,
-    PlayoffModeType::DoubleBracket => &DOUBLE_BRACKET_TEMPLATE,
+  let template = match playoff_mode.mode {
+    PlayoffModeType::DoubleBracket => double_bracket_template(playoff_mode),
+    _ => single_bracket_template(playoff_mode),
   };
+  template_update(playoff_mode, &template, matches, scores, config)
+}
+
+// Run through a template, resolving alliances, byes, and tiebreakers from the matches played so far.
+pub fn template_update(playoff_mode: &PlayoffMode, template: &PlayoffTemplate, matches: &Vec<Match>, scores: &HashMap<String, CommittedMatchScores>, config: &ScoringConfig) -> anyhow::Result<GenerationUpdate> {
+  let bracket = &template.items;
   let mut refined_bracket = vec![];   /* Contains tiebreakers, removes byes */
 
   let mut winners: HashMap<(MatchType, /* round */ usize, /* set */ usize), PlayoffAllianceDescriptor> = HashMap::new();
@@ -149,15 +182,25 @@ pub fn bracket_update(playoff_mode: &PlayoffMode, matches: &Vec<Match>, scores:
         let set_matches = matches.iter().filter(|m| m.match_type == incomplete_match.ty && m.round == incomplete_match.round && m.set_number == incomplete_match.set);
         let played = set_matches.clone().filter(|m| m.played);
 
-        let match_wins = played.clone().filter_map(|x| scores.get(&x.id()).and_then(|x| x.scores.last()).and_then(|s| s.winner(config)));
+        let mut match_wins = vec![];
+        for m in played.clone() {
+          if let Some(score) = scores.get(&m.id()).and_then(|x| x.scores.last()) {
+            if let Some(winner) = score.winner(config)? {
+              match_wins.push(winner);
+            }
+          }
+        }
 
-        let red_wins = match_wins.clone().filter(|x| *x == Alliance::Red).count();
-        let blue_wins = match_wins.clone().filter(|x| *x == Alliance::Blue).count();
+        let red_wins = match_wins.iter().filter(|x| **x == Alliance::Red).count();
+        let blue_wins = match_wins.iter().filter(|x| **x == Alliance::Blue).count();
 
-        let n_wins_required = match (&playoff_mode.mode, &incomplete_match.ty) {
-          (_, MatchType::Final) => 2,
-          (PlayoffModeType::Bracket, _) => 2,
-          (PlayoffModeType::DoubleBracket, _) => 1,
+        let n_wins_required = match incomplete_match.ty {
+          MatchType::Final => template.final_wins,
+          _ => match template.playoff_wins {
+            Some(wins) => wins,
+            // This set doesn't need a winner (e.g. round robin), so there's nothing to propagate or tiebreak
+            None => continue
+          }
         };
 
         let first_match_in_set = set_matches.clone().next();
@@ -173,10 +216,14 @@ pub fn bracket_update(playoff_mode: &PlayoffMode, matches: &Vec<Match>, scores:
           // We don't have a winner yet, and there are no outstanding matches - queue a tiebreaker
           let max_match_num = played.clone().map(|x| x.match_number).max().unwrap_or(0);
           // TODO: Defer this to the end of the round. Maybe reintroduce tiebreaker allowance?
-          tiebreaker_queue.push(PlayoffScheduleItem::Match(IncompleteMatch { 
+          let tiebreaker = PlayoffScheduleItem::Match(IncompleteMatch { 
             ty: incomplete_match.ty, round: incomplete_match.round, set: incomplete_match.set, match_num: max_match_num + 1, 
             red: incomplete_match.red, blue: incomplete_match.blue
-          }))
+          });
+          // Best-of-N sets appear once per match in the template, but only need the one tiebreaker
+          if !tiebreaker_queue.contains(&tiebreaker) {
+            tiebreaker_queue.push(tiebreaker);
+          }
         } else if let Some((winner, loser)) = winner_loser {
           if incomplete_match.ty == MatchType::Final {
             // The tournament has been won
@@ -197,3 +244,102 @@ pub fn bracket_update(playoff_mode: &PlayoffMode, matches: &Vec<Match>, scores:
 
   Ok(GenerationUpdate::MatchUpdates(refined_bracket))
 }
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use jms_core_lib::{models::{CommittedMatchScores, Match, MatchType, PlayoffMode}, scoring::{crescendo::CrescendoLiveScore, game::Game, scores::{LiveScore, MatchScore, ScoringConfig}}};
+
+  use crate::schedule::playoffs::{GenerationUpdate, IncompleteMatch, PlayoffAllianceDescriptor, PlayoffScheduleItem, PlayoffTemplate};
+
+  use super::{best_of_sets, template_update, SINGLE_BRACKET_FINAL, SINGLE_BRACKET_SEMIS};
+
+  const FINAL: IncompleteMatch = IncompleteMatch { ty: MatchType::Final, round: 1, set: 1, match_num: 1, red: PlayoffAllianceDescriptor::Alliance(1), blue: PlayoffAllianceDescriptor::Alliance(2) };
+
+  fn match_nums(items: &[PlayoffScheduleItem]) -> Vec<Option<(usize, usize)>> {
+    items.iter().map(|i| match i {
+      PlayoffScheduleItem::Match(m) => Some((m.set, m.match_num)),
+      _ => None
+    }).collect()
+  }
+
+  #[test]
+  fn sets_best_of() {
+    // Matches are interleaved between sets, with tiebreakers and a break after each pass
+    let items = best_of_sets(&SINGLE_BRACKET_SEMIS, 3);
+    assert_eq!(match_nums(&items), vec![ Some((1, 1)), Some((2, 1)), None, None, Some((1, 2)), Some((2, 2)), None, None ]);
+    assert_eq!(items[2], PlayoffScheduleItem::TiebreakerSlot);
+    assert_eq!(items[3], PlayoffScheduleItem::AwardsBreak);
+
+    assert_eq!(match_nums(&best_of_sets(&SINGLE_BRACKET_SEMIS, 1)), vec![ Some((1, 1)), Some((2, 1)), None, None ]);
+
+    // Finals only need tiebreakers at the end
+    let finals = best_of_sets(&[SINGLE_BRACKET_FINAL], 5);
+    assert_eq!(finals, vec![
+      PlayoffScheduleItem::Match(SINGLE_BRACKET_FINAL),
+      PlayoffScheduleItem::AwardsBreak,
+      PlayoffScheduleItem::Match(IncompleteMatch { match_num: 2, ..SINGLE_BRACKET_FINAL }),
+      PlayoffScheduleItem::AwardsBreak,
+      PlayoffScheduleItem::Match(IncompleteMatch { match_num: 3, ..SINGLE_BRACKET_FINAL }),
+      PlayoffScheduleItem::TiebreakerSlot,
+    ]);
+  }
+
+  fn final_match(n: usize, red: isize, blue: isize) -> (Match, CommittedMatchScores) {
+    let m = Match {
+      id: Match::gen_id(MatchType::Final, 1, 1, n),
+      name: Match::gen_name(MatchType::Final, 1, 1, n),
+      start_time: chrono::Local::now() + chrono::Duration::minutes(n as i64),
+      match_type: MatchType::Final,
+      round: 1, set_number: 1, match_number: n,
+      blue_teams: vec![], blue_alliance: Some(2), red_teams: vec![], red_alliance: Some(1),
+      dqs: vec![], surrogates: vec![], replay_reason: None,
+      actual_start_time: None, original_start_time: None,
+      played: true, ready: true
+    };
+    let live = |adjustment| LiveScore::Crescendo2024(CrescendoLiveScore { adjustment, ..CrescendoLiveScore::new(3) });
+    let scores = CommittedMatchScores { match_id: m.id.clone(), scores: vec![ MatchScore { red: live(red), blue: live(blue) } ], last_update: chrono::Local::now() };
+    (m, scores)
+  }
+
+  // Run a best of 3 final through the template with the given (red, blue) scores
+  fn best_of_3(results: &[(isize, isize)]) -> GenerationUpdate {
+    let template = PlayoffTemplate { items: best_of_sets(&[FINAL], 3), playoff_wins: None, final_wins: PlayoffMode::wins_required(3) };
+    let (mut matches, scores): (Vec<Match>, Vec<CommittedMatchScores>) = results.iter().enumerate().map(|(i, &(red, blue))| final_match(i + 1, red, blue)).unzip();
+    // The scheduled matches that are yet to be played
+    for n in (matches.len() + 1)..=2 {
+      matches.push(Match { played: false, ..final_match(n, 0, 0).0 });
+    }
+    let scores: HashMap<String, CommittedMatchScores> = scores.into_iter().map(|s| (s.match_id.clone(), s)).collect();
+    template_update(&PlayoffMode::default(), &template, &matches, &scores, &ScoringConfig::new(Game::Crescendo2024)).unwrap()
+  }
+
+  fn tiebreakers(update: GenerationUpdate) -> Vec<usize> {
+    match update {
+      GenerationUpdate::MatchUpdates(items) => items.into_iter().filter_map(|i| match i {
+        PlayoffScheduleItem::Match(m) if m.match_num > 2 => Some(m.match_num),
+        _ => None
+      }).collect(),
+      GenerationUpdate::TournamentWon { .. } => panic!("The tournament shouldn't be won yet")
+    }
+  }
+
+  #[test]
+  fn series_completion() {
+    assert!(matches!(best_of_3(&[ (10, 0), (10, 0) ]), GenerationUpdate::TournamentWon { winner: 1, finalist: 2 }));
+
+    // Level after two matches, so a third is needed
+    assert_eq!(tiebreakers(best_of_3(&[ (10, 0), (0, 10) ])), vec![ 3 ]);
+    assert!(matches!(best_of_3(&[ (10, 0), (0, 10), (0, 10) ]), GenerationUpdate::TournamentWon { winner: 2, finalist: 1 }));
+
+    // A tied match doesn't count as a win for either alliance
+    assert_eq!(tiebreakers(best_of_3(&[ (5, 5), (10, 0) ])), vec![ 3 ]);
+    assert_eq!(tiebreakers(best_of_3(&[ (5, 5), (10, 0), (5, 5) ])), vec![ 4 ]);
+    assert!(matches!(best_of_3(&[ (5, 5), (10, 0), (5, 5), (10, 0) ]), GenerationUpdate::TournamentWon { winner: 1, finalist: 2 }));
+
+    // Nothing to decide until the scheduled matches have been played
+    assert_eq!(tiebreakers(best_of_3(&[ (10, 0) ])), Vec::<usize>::new());
+    assert_eq!(tiebreakers(best_of_3(&[ (5, 5) ])), Vec::<usize>::new());
+  }
+}
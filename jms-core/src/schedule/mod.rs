@@ -1,13 +1,16 @@
 use jms_base::{kv::KVConnection, mq::MessageQueueChannel};
-use jms_core_lib::schedule::generators::{MatchGeneratorRPC, QualsMatchGeneratorParams, MATCH_GENERATOR_JOB_KEY};
+use jms_core_lib::schedule::generators::{MatchGeneratorRPC, QualsImportResult, QualsMatchGeneratorParams, MATCH_GENERATOR_JOB_KEY};
 use log::{info, error};
 
-use self::{quals::QualsMatchGenerator, playoffs::PlayoffMatchGenerator};
+use self::{quals::QualsMatchGenerator, quals_import::QualsScheduleImporter, playoffs::PlayoffMatchGenerator, retime::ScheduleRetimer};
 
 pub mod bracket;
 pub mod quals_randomiser;
 pub mod quals;
+pub mod quals_import;
 pub mod playoffs;
+pub mod round_robin;
+pub mod retime;
 
 pub struct GeneratorService {
   pub kv: KVConnection,
@@ -40,6 +43,17 @@ impl MatchGeneratorRPC for GeneratorService {
     Ok(())
   }
 
+  async fn import_quals(&mut self, data: String, dry_run: bool) -> Result<QualsImportResult, String> {
+    if self.kv.get::<bool>(MATCH_GENERATOR_JOB_KEY).map_err(|e| e.to_string())? {
+      return Err("Quals Generator is currently running!".to_owned())
+    }
+    QualsScheduleImporter::import(&data, dry_run, &self.kv).map_err(|e| e.to_string())
+  }
+
+  async fn retime_schedule(&mut self) -> Result<usize, String> {
+    ScheduleRetimer::retime(&self.kv).map_err(|e| e.to_string())
+  }
+
   async fn reset_playoffs(&mut self) -> Result<(), String> {
     PlayoffMatchGenerator::reset(&self.kv).map_err(|e| e.to_string())
   }
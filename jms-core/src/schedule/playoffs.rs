@@ -1,9 +1,9 @@
 use chrono::Duration;
 use jms_base::kv;
-use jms_core_lib::{db::{Singleton, Table}, models::{Award, AwardRecipient, CommittedMatchScores, Match, MatchType, PlayoffAlliance, PlayoffMode, PlayoffModeType, ScheduleBlock, ScheduleBlockType, Team}, scoring::scores::ScoringConfig};
+use jms_core_lib::{db::{Singleton, Table}, models::{Award, AwardRecipient, CommittedMatchScores, Match, MatchType, PlayoffAlliance, PlayoffMode, PlayoffModeType, ScheduleBlock, ScheduleBlockType, Team, Timeout}, scoring::scores::ScoringConfig};
 use log::{info, warn};
 
-use super::bracket::bracket_update;
+use super::{bracket::bracket_update, round_robin::round_robin_update};
 
 #[derive(Debug)]
 pub enum GenerationUpdate {
@@ -19,6 +19,7 @@ pub enum PlayoffAllianceDescriptor {
   Alliance(usize),
   WinnerOf(MatchType, /* round */ usize, /* set */ usize),
   LoserOf(MatchType, /* round */ usize, /* set */ usize),
+  RoundRobinRank(usize),
   Bye
 }
 
@@ -29,6 +30,14 @@ pub enum PlayoffScheduleItem {
   AwardsBreak,
 }
 
+#[derive(Debug, Clone)]
+pub struct PlayoffTemplate {
+  pub items: Vec<PlayoffScheduleItem>,
+  // Wins required to take a Playoff set. None if sets don't need a winner (e.g. round robin), in which case there are no tiebreakers.
+  pub playoff_wins: Option<usize>,
+  pub final_wins: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct HydratedPlayoffScheduleItem {
   pub duration: Option<chrono::Duration>,
@@ -80,7 +89,8 @@ impl PlayoffMatchGenerator {
 
     let config = ScoringConfig::get(kv)?;
     let update = match playoff_mode.mode {
-      PlayoffModeType::Bracket | PlayoffModeType::DoubleBracket => bracket_update(&playoff_mode, &matches, &scores, config)?
+      PlayoffModeType::Bracket | PlayoffModeType::DoubleBracket => bracket_update(&playoff_mode, &matches, &scores, &config)?,
+      PlayoffModeType::RoundRobin => round_robin_update(&playoff_mode, &matches, &scores, &config)?,
     };
 
     match update {
@@ -121,6 +131,9 @@ impl PlayoffMatchGenerator {
           }
         }
 
+        // Timeouts hold up the rest of the playoffs
+        let hold = Timeout::playoff_hold(kv)?;
+
         let mut blocks_mut = blocks.clone();
         let mut this_block = blocks_mut.next();
         let mut offset = this_block.as_ref().map(|x| x.start_time).unwrap_or(chrono::Local::now());
@@ -143,17 +156,22 @@ impl PlayoffMatchGenerator {
           match item.item {
             PlayoffScheduleItem::Match(m) => {
               let (red_teams, red_alliance) = match &m.red {
-                PlayoffAllianceDescriptor::Alliance(a) => (alliances.get(a).map(|x| x.teams.clone()).unwrap_or(vec![]), Some(*a)),
+                PlayoffAllianceDescriptor::Alliance(a) => (alliances.get(a).map(|x| x.lineup()).unwrap_or(vec![]), Some(*a)),
                 _ => (vec![], None)
               };
 
               let (blue_teams, blue_alliance) = match &m.blue {
-                PlayoffAllianceDescriptor::Alliance(a) => (alliances.get(a).map(|x| x.teams.clone()).unwrap_or(vec![]), Some(*a)),
+                PlayoffAllianceDescriptor::Alliance(a) => (alliances.get(a).map(|x| x.lineup()).unwrap_or(vec![]), Some(*a)),
                 _ => (vec![], None)
               };
 
               let id = Match::gen_id(m.ty, m.round, m.set, m.match_num);
-              if let Ok(mut existing) = Match::get(&id, kv) {
+              let existing = Match::get(&id, kv).ok();
+              if let Some(hold) = hold.filter(|_| !existing.as_ref().map(|e| e.played).unwrap_or(false)) {
+                offset = offset.max(hold);
+              }
+
+              if let Some(mut existing) = existing {
                 if !existing.played {
                   existing.start_time = offset.clone();
                   existing.red_alliance = red_alliance;
@@ -177,6 +195,10 @@ impl PlayoffMatchGenerator {
                   red_teams: vec![ red_teams.get(0).copied(), red_teams.get(1).copied(), red_teams.get(2).copied() ],
                   red_alliance,
                   dqs: vec![],
+                  surrogates: vec![],
+                  replay_reason: None,
+                  actual_start_time: None,
+                  original_start_time: None,
                   played: false,
                   ready: blue_alliance.is_some() && red_alliance.is_some()
                 };
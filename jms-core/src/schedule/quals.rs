@@ -1,8 +1,10 @@
 use jms_base::kv;
-use jms_core_lib::{models::{self, MatchType}, db::Table, schedule::generators::QualsMatchGeneratorParams};
+use jms_core_lib::{models::{self, MatchType}, db::Table, schedule::generators::{QualsMatchGeneratorParams, QualsScheduleReport, QualsTeamReport}};
 use log::info;
+use nalgebra as na;
+use rand::Rng;
 
-use super::quals_randomiser::{Annealer, ScheduleGenerator};
+use super::quals_randomiser::{Annealer, GenerationResult, ScheduleGenerator};
 
 #[derive(Debug, Clone)]
 pub struct QualsMatchGenerator;
@@ -22,31 +24,13 @@ impl QualsMatchGenerator {
     let station_balance_anneal = Annealer::new(1.0, 0.0, params.station_anneal_steps);
     let team_balance_anneal = Annealer::new(1.0, 0.0, params.team_anneal_steps);
 
-    let teams: Vec<usize> = models::Team::all(kv)?.iter().filter(|&t| t.schedule).map(|t| t.number).collect();
+    let mut teams: Vec<usize> = models::Team::all(kv)?.iter().filter(|&t| t.schedule).map(|t| t.number).collect();
+    // The DB gives us teams in no particular order, so sort them to keep the schedule reproducible from its seed
+    teams.sort();
     let existing_matches = models::Match::sorted(kv)?;
 
     // Determine timeslots for generation
-    let mut slots = vec![];
-    for block in models::ScheduleBlock::sorted(kv)? {
-      match block.block_type {
-        models::ScheduleBlockType::Qualification { cycle_time } => {
-          let mut offset = block.start_time;
-          while (offset + cycle_time.0) <= block.end_time {
-            if let Some(m) = existing_matches.iter().find(|m| m.start_time >= offset && m.start_time <= (offset + cycle_time.0)) {
-              // There's already a match in this slot - push back the offset by the cycle time
-              offset = m.start_time + cycle_time.0;
-            } else {
-              // No matches in this slot - happy days.
-              if offset >= chrono::Local::now() {
-                slots.push( ( offset, offset + cycle_time.0 ) );
-              }
-              offset = offset + cycle_time.0;
-            }
-          }
-        },
-        _ => ()
-      }
-    }
+    let slots = Self::slots(&existing_matches, kv)?;
 
     if slots.is_empty() {
       // Nothing to generate
@@ -54,19 +38,43 @@ impl QualsMatchGenerator {
     }
     
     // Generate
-    let generator = ScheduleGenerator::new(teams.len(), slots.len(), 6);
+    // Seeds are kept within u32 so they survive the round trip through JSON / Javascript numbers
+    let seed = params.seed.unwrap_or_else(|| rand::thread_rng().gen::<u32>() as u64);
+    // Turnaround carries over from the quals that are already scheduled, e.g. from before lunch
+    let prior_quals: Vec<&models::Match> = existing_matches.iter()
+      .filter(|m| m.match_type == MatchType::Qualification && m.start_time < slots[0].0)
+      .collect();
+    let prior_gaps = teams.iter().map(|&t| {
+      prior_quals.iter().rposition(|m| m.blue_teams.contains(&Some(t)) || m.red_teams.contains(&Some(t))).map(|i| prior_quals.len() - i)
+    }).collect();
+
+    let generator = ScheduleGenerator::new(teams.len(), slots.len(), 6, seed)
+      .with_turnaround(params.min_turnaround, params.hard_turnaround, prior_gaps);
 
-    let generation_result = generator.generate(team_balance_anneal, station_balance_anneal);
+    let generation_result = generator.generate(team_balance_anneal, station_balance_anneal)?;
     let team_sched = generation_result
       .schedule
       .contextualise(&teams);
+    let surrogates = generation_result.schedule.surrogates(teams.len());
 
     let gen_end_t = chrono::Local::now();
-    info!("Match Generation Completed in {}", gen_end_t - gen_start_t);
+    info!("Match Generation Completed in {} (seed {})", gen_end_t - gen_start_t, seed);
+
+    let report = Self::report(&generator, &generation_result, &teams, seed, params);
+    info!(
+      "Partners {}-{}, Opponents {}-{}, Station Imbalance {}, Min Turnaround {:?}",
+      report.min_partner, report.max_partner, report.min_opponent, report.max_opponent, report.max_station_imbalance, report.min_turnaround
+    );
+    report.insert(kv)?;
+
+    if params.dry_run {
+      return Ok(())
+    }
       
     // Commit
     let match_n_offset = existing_matches.iter().filter(|x| x.match_type == MatchType::Qualification).map(|x| x.set_number).max().unwrap_or(0);
     for (i, col) in team_sched.0.column_iter().enumerate() {
+      let surrogate_teams = col.iter().zip(surrogates.column(i).iter()).filter(|(_, &s)| s).map(|(&t, _)| t).collect();
       let teams = col.as_slice();
       let blue = teams[0..3].to_vec();
       let red = teams[3..6].to_vec();
@@ -86,6 +94,10 @@ impl QualsMatchGenerator {
         red_teams: red.iter().map(|&t| Some(t)).collect(),
         red_alliance: None,
         dqs: vec![],
+        surrogates: surrogate_teams,
+        replay_reason: None,
+        actual_start_time: None,
+        original_start_time: None,
         played: false,
         ready: true
       };
@@ -93,6 +105,75 @@ impl QualsMatchGenerator {
       m.insert(kv)?;
     }
 
+    QualsScheduleReport { committed: true, ..report }.insert(kv)?;
+
     Ok(())
   }
+
+  // Free timeslots in the Qualification schedule blocks, skipping over any slots already taken by existing matches.
+  pub fn slots(existing_matches: &[models::Match], kv: &kv::KVConnection) -> anyhow::Result<Vec<(chrono::DateTime<chrono::Local>, chrono::DateTime<chrono::Local>)>> {
+    let mut slots = vec![];
+    for block in models::ScheduleBlock::sorted(kv)? {
+      match block.block_type {
+        models::ScheduleBlockType::Qualification { cycle_time } => {
+          let mut offset = block.start_time;
+          while (offset + cycle_time.0) <= block.end_time {
+            if let Some(m) = existing_matches.iter().find(|m| m.start_time >= offset && m.start_time <= (offset + cycle_time.0)) {
+              // There's already a match in this slot - push back the offset by the cycle time
+              offset = m.start_time + cycle_time.0;
+            } else {
+              // No matches in this slot - happy days.
+              if offset >= chrono::Local::now() {
+                slots.push( ( offset, offset + cycle_time.0 ) );
+              }
+              offset = offset + cycle_time.0;
+            }
+          }
+        },
+        _ => ()
+      }
+    }
+    Ok(slots)
+  }
+
+  fn report(generator: &ScheduleGenerator, result: &GenerationResult, teams: &[usize], seed: u64, params: QualsMatchGeneratorParams) -> QualsScheduleReport {
+    let (partners, opponents) = generator.alliance_matrices(&result.schedule);
+    let turnarounds = generator.turnarounds(&result.schedule);
+
+    let pairs = |mat: &na::DMatrix<usize>| {
+      let values: Vec<usize> = (0..teams.len()).flat_map(|i| ((i + 1)..teams.len()).map(move |j| (i, j))).map(|ij| mat[ij]).collect();
+      ( values.iter().copied().min().unwrap_or(0), values.iter().copied().max().unwrap_or(0) )
+    };
+    let (min_partner, max_partner) = pairs(&partners);
+    let (min_opponent, max_opponent) = pairs(&opponents);
+
+    let team_reports: Vec<QualsTeamReport> = teams.iter().enumerate().map(|(i, &team)| {
+      let station_counts: Vec<usize> = result.station_dist.column(i).iter().copied().collect();
+      QualsTeamReport {
+        team,
+        matches: station_counts.iter().sum(),
+        station_counts,
+        min_turnaround: turnarounds[i],
+      }
+    }).collect();
+
+    QualsScheduleReport {
+      seed,
+      committed: false,
+      generated_at: chrono::Local::now(),
+      params,
+      num_teams: teams.len(),
+      num_matches: result.schedule.0.ncols(),
+      team_balance_score: result.team_balance_score,
+      station_balance_score: result.station_balance_score,
+      min_partner, max_partner,
+      min_opponent, max_opponent,
+      max_station_imbalance: team_reports.iter().map(|t| {
+        t.station_counts.iter().max().unwrap_or(&0) - t.station_counts.iter().min().unwrap_or(&0)
+      }).max().unwrap_or(0),
+      min_turnaround: team_reports.iter().filter_map(|t| t.min_turnaround).min(),
+      turnaround_violations: generator.turnaround_violations(&result.schedule),
+      teams: team_reports,
+    }
+  }
 }
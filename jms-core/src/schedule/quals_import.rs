@@ -0,0 +1,226 @@
+use jms_base::kv;
+use jms_core_lib::{models::{self, MatchType}, db::Table, schedule::generators::{QualsImportResult, ScheduleDiffEntry, ScheduleDiffKind}};
+
+use super::quals::QualsMatchGenerator;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedMatch {
+  pub number: usize,
+  pub red: Vec<usize>,
+  pub blue: Vec<usize>,
+  pub surrogates: Vec<usize>,
+}
+
+/**
+ * Parse a Qualification Schedule. Two formats are accepted:
+ * - MatchMaker output, where each line is whitespace separated as
+ *   `match red1 red1surrogate red2 red2surrogate red3 red3surrogate blue1 blue1surrogate ... blue3surrogate`
+ * - CSV, where each row is `match,red1,red2,red3,blue1,blue2,blue3`, with surrogates marked by a trailing `*`.
+ * Blank lines, and lines that don't start with a match number (e.g. headers) are ignored.
+ */
+pub fn parse_schedule(data: &str) -> anyhow::Result<Vec<ImportedMatch>> {
+  let mut matches: Vec<ImportedMatch> = vec![];
+
+  for (i, line) in data.lines().enumerate() {
+    let line = line.trim();
+    let fields: Vec<&str> = match line.contains(',') {
+      true => line.split(',').map(|f| f.trim()).collect(),
+      false => line.split_whitespace().collect(),
+    };
+
+    let number = match fields.first().and_then(|f| f.parse::<usize>().ok()) {
+      Some(n) => n,
+      None => continue
+    };
+
+    let mut teams = vec![];
+    let mut surrogates = vec![];
+    match fields.len() {
+      13 => {
+        for pair in fields[1..].chunks(2) {
+          let team = parse_team(pair[0], i)?;
+          if pair[1] == "1" {
+            surrogates.push(team);
+          }
+          teams.push(team);
+        }
+      },
+      7 => {
+        for field in &fields[1..] {
+          let team = parse_team(field.trim_end_matches('*'), i)?;
+          if field.ends_with('*') {
+            surrogates.push(team);
+          }
+          teams.push(team);
+        }
+      },
+      n => anyhow::bail!("Line {}: expected 7 (CSV) or 13 (MatchMaker) fields, got {}", i + 1, n)
+    }
+
+    if teams.iter().enumerate().any(|(j, t)| teams[..j].contains(t)) {
+      anyhow::bail!("Line {}: a team appears more than once in Match {}", i + 1, number);
+    }
+    if matches.iter().any(|m| m.number == number) {
+      anyhow::bail!("Line {}: Match {} appears more than once", i + 1, number);
+    }
+
+    matches.push(ImportedMatch { number, red: teams[0..3].to_vec(), blue: teams[3..6].to_vec(), surrogates });
+  }
+
+  matches.sort_by_key(|m| m.number);
+  Ok(matches)
+}
+
+fn parse_team(field: &str, line: usize) -> anyhow::Result<usize> {
+  field.parse().map_err(|_| anyhow::anyhow!("Line {}: '{}' is not a team number", line + 1, field))
+}
+
+// Every team in the schedule has to exist. Teams that aren't meant to be scheduled, or that are missing from it, are only
+// warned about.
+fn validate_teams(imported: &[ImportedMatch], teams: &[models::Team]) -> anyhow::Result<Vec<String>> {
+  let mut warnings = vec![];
+  let mut unknown = vec![];
+  let mut unscheduled = vec![];
+  for m in imported {
+    for &team in m.red.iter().chain(m.blue.iter()) {
+      match teams.iter().find(|t| t.number == team) {
+        None if !unknown.contains(&team) => unknown.push(team),
+        Some(t) if !t.schedule && !unscheduled.contains(&team) => unscheduled.push(team),
+        _ => ()
+      }
+    }
+  }
+  if !unknown.is_empty() {
+    anyhow::bail!("Unknown teams in the imported schedule: {}", unknown.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", "));
+  }
+  for team in unscheduled {
+    warnings.push(format!("Team {} is not marked to be scheduled", team));
+  }
+  for team in teams.iter().filter(|t| t.schedule) {
+    if !imported.iter().any(|m| m.red.contains(&team.number) || m.blue.contains(&team.number)) {
+      warnings.push(format!("Team {} does not appear in the imported schedule", team.number));
+    }
+  }
+  Ok(warnings)
+}
+
+pub struct QualsScheduleImporter;
+
+impl QualsScheduleImporter {
+  // Replace the (unplayed) Qualification Schedule with an imported one, slotting matches into the Qualification
+  // schedule blocks in the same way as the generator.
+  pub fn import(data: &str, dry_run: bool, kv: &kv::KVConnection) -> anyhow::Result<QualsImportResult> {
+    let imported = parse_schedule(data)?;
+    if imported.is_empty() {
+      anyhow::bail!("No matches found in the imported schedule");
+    }
+
+    let all_matches = models::Match::sorted(kv)?;
+    let (existing_quals, others): (Vec<models::Match>, Vec<models::Match>) = all_matches.into_iter().partition(|m| m.match_type == MatchType::Qualification);
+    if existing_quals.iter().any(|m| m.played) {
+      anyhow::bail!("Can't import a Qualification Schedule once Qualifications have started!");
+    }
+
+    let warnings = validate_teams(&imported, &models::Team::all(kv)?)?;
+
+    // Existing quals are replaced, so their slots are available to the import
+    let slots = QualsMatchGenerator::slots(&others, kv)?;
+    if slots.len() < imported.len() {
+      anyhow::bail!("The imported schedule has {} matches, but there are only {} Qualification slots available", imported.len(), slots.len());
+    }
+
+    let new_matches: Vec<models::Match> = imported.iter().zip(slots.iter()).map(|(m, slot)| models::Match {
+      id: models::Match::gen_id(MatchType::Qualification, 1, m.number, 1),
+      name: models::Match::gen_name(MatchType::Qualification, 1, m.number, 1),
+      start_time: slot.0,
+      match_type: MatchType::Qualification,
+      round: 1,
+      set_number: m.number,
+      match_number: 1,
+      blue_teams: m.blue.iter().map(|&t| Some(t)).collect(),
+      blue_alliance: None,
+      red_teams: m.red.iter().map(|&t| Some(t)).collect(),
+      red_alliance: None,
+      dqs: vec![],
+      surrogates: m.surrogates.clone(),
+      replay_reason: None,
+      actual_start_time: None,
+      original_start_time: None,
+      played: false,
+      ready: true
+    }).collect();
+
+    let mut diff = vec![];
+    for m in &new_matches {
+      let before = existing_quals.iter().find(|e| e.id == m.id).cloned();
+      let kind = match &before {
+        None => ScheduleDiffKind::Added,
+        Some(b) if b.red_teams == m.red_teams && b.blue_teams == m.blue_teams && b.start_time == m.start_time => ScheduleDiffKind::Unchanged,
+        Some(_) => ScheduleDiffKind::Changed,
+      };
+      diff.push(ScheduleDiffEntry { kind, name: m.name.clone(), before, after: Some(m.clone()) });
+    }
+    for e in existing_quals.iter().filter(|e| !new_matches.iter().any(|m| m.id == e.id)) {
+      diff.push(ScheduleDiffEntry { kind: ScheduleDiffKind::Removed, name: e.name.clone(), before: Some(e.clone()), after: None });
+    }
+
+    if !dry_run {
+      for e in &existing_quals {
+        e.delete(kv)?;
+      }
+      for m in &new_matches {
+        m.insert(kv)?;
+      }
+    }
+
+    Ok(QualsImportResult { committed: !dry_run, warnings, diff })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use jms_core_lib::models::Team;
+
+  use super::{parse_schedule, validate_teams, ImportedMatch};
+
+  #[test]
+  fn matchmaker() {
+    let data = "
+      1  4788 0 1234 0 5678 1  111 0 222 0 333 0
+      2   111 0 4788 0  222 0 1234 1 5678 0 333 0
+    ";
+    assert_eq!(parse_schedule(data).unwrap(), vec![
+      ImportedMatch { number: 1, red: vec![4788, 1234, 5678], blue: vec![111, 222, 333], surrogates: vec![5678] },
+      ImportedMatch { number: 2, red: vec![111, 4788, 222], blue: vec![1234, 5678, 333], surrogates: vec![1234] },
+    ]);
+  }
+
+  #[test]
+  fn csv_with_header() {
+    let data = "Match,Red 1,Red 2,Red 3,Blue 1,Blue 2,Blue 3\n2,111,222,333,4788,1234,5678*\n\n1, 4788, 1234, 5678, 111, 222, 333\n";
+    let matches = parse_schedule(data).unwrap();
+    assert_eq!(matches.iter().map(|m| m.number).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(matches[0].red, vec![4788, 1234, 5678]);
+    assert_eq!(matches[1].blue, vec![4788, 1234, 5678]);
+    assert_eq!(matches[1].surrogates, vec![5678]);
+  }
+
+  #[test]
+  fn malformed() {
+    assert!(parse_schedule("1,4788,1234,5678,111,222").is_err());
+    assert!(parse_schedule("1,4788,1234,abc,111,222,333").is_err());
+    assert!(parse_schedule("1,4788,4788,5678,111,222,333").is_err());
+    assert!(parse_schedule("1,4788,1234,5678,111,222,333\n1,4788,1234,5678,111,222,333").is_err());
+  }
+
+  #[test]
+  fn unknown_team() {
+    let teams: Vec<Team> = [4788, 1234, 5678, 111, 222, 333].into_iter().map(|n| Team::new(n, n.to_string(), None, None, None)).collect();
+
+    let known = parse_schedule("1,4788,1234,5678,111,222,333").unwrap();
+    assert_eq!(validate_teams(&known, &teams).unwrap(), Vec::<String>::new());
+
+    let unknown = parse_schedule("1,4788,1234,5678,111,222,999").unwrap();
+    assert!(validate_teams(&unknown, &teams).is_err());
+  }
+}
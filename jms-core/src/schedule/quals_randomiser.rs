@@ -1,8 +1,8 @@
-use std::{cmp, time};
+use std::{cell::RefCell, cmp, time};
 
 use log::{debug, info};
 use nalgebra as na;
-use rand::{prelude::SliceRandom, Rng};
+use rand::{prelude::SliceRandom, rngs::StdRng, Rng, SeedableRng};
 
 // Cols = Rounds
 #[derive(Debug)]
@@ -16,6 +16,27 @@ impl Schedule {
   pub fn contextualise(&self, teams: &[usize]) -> TeamSchedule {
     TeamSchedule(self.0.map(|x| teams[x]))
   }
+
+  // If the number of appearances doesn't divide evenly, some teams will play an extra match. That extra match is
+  // marked as a surrogate appearance (their third match, as per the game manual, or their last if they have fewer),
+  // and doesn't count towards rankings.
+  pub fn surrogates(&self, num_teams: usize) -> na::DMatrix<bool> {
+    let mut appearances = vec![0usize; num_teams];
+    for &team in self.0.iter() {
+      appearances[team] += 1;
+    }
+    let min_appearances = appearances.iter().copied().min().unwrap_or(0);
+
+    let mut seen = vec![0usize; num_teams];
+    // Matrix iteration is column-major, i.e. in match order
+    self.0.map(|team| {
+      let extra = appearances[team] - min_appearances;
+      let first_surrogate = cmp::min(2, appearances[team] - extra);
+      let n = seen[team];
+      seen[team] += 1;
+      extra > 0 && n >= first_surrogate && n < first_surrogate + extra
+    })
+  }
 }
 
 #[derive(Debug)]
@@ -27,8 +48,20 @@ pub struct ScheduleGenerator {
   num_matches: usize,
 
   teams: na::DVector<usize>,
+  rng: RefCell<StdRng>,
+
+  min_turnaround: usize,
+  hard_turnaround: bool,
+  // Number of matches since each team last played before the first match being generated, e.g. from the matches
+  // before a lunch break when the afternoon is being generated. 1 means they played in the match just before.
+  prior_gaps: Vec<Option<usize>>,
 }
 
+const MAX_SEED_ATTEMPTS: usize = 1000;
+
+// Penalty added to the team balance score for each appearance that breaks a soft turnaround constraint.
+const TURNAROUND_PENALTY: f64 = 1.0;
+
 pub struct GenerationResult {
   pub schedule: Schedule,
   pub team_balance_score: f64,
@@ -43,6 +76,7 @@ impl ScheduleGenerator {
     // num_matches_per_team: usize,
     num_matches: usize,
     num_stations: usize,
+    seed: u64,
   ) -> Self {
     let teams = na::DVector::from_iterator(num_teams, (0..num_teams).into_iter());
 
@@ -51,29 +85,51 @@ impl ScheduleGenerator {
       num_stations,
       num_matches,
       teams,
+      rng: RefCell::new(StdRng::seed_from_u64(seed)),
+      min_turnaround: 0,
+      hard_turnaround: false,
+      prior_gaps: vec![None; num_teams],
     }
   }
 
+  pub fn with_turnaround(mut self, min_turnaround: usize, hard: bool, prior_gaps: Vec<Option<usize>>) -> Self {
+    self.min_turnaround = min_turnaround;
+    self.hard_turnaround = hard;
+    self.prior_gaps = prior_gaps;
+    self
+  }
+
+  // The last match each team appeared in, with teams that played before this schedule given negative indices.
+  fn initial_last_seen(&self) -> Vec<Option<isize>> {
+    self.prior_gaps.iter().map(|g| g.map(|g| -(g as isize))).collect()
+  }
+
   fn generate_simple_schedule(&self) -> Schedule {
     let mut schedule = na::DMatrix::zeros(self.num_stations, self.num_matches);
 
-    let mut teams: Vec<usize> = shuffle(&self.teams).iter().map(|&x| x).collect();
+    let mut rng = self.rng.borrow_mut();
+    let mut teams: Vec<usize> = shuffle(&self.teams, &mut *rng).iter().map(|&x| x).collect();
+    let mut last_seen = self.initial_last_seen();
 
     for m in 0..self.num_matches {
       let mut match_picked = vec![];
       for s in 0..self.num_stations {
         if teams.len() == 0 {
-          teams = shuffle(&self.teams).iter().map(|&x| x).collect();
+          teams = shuffle(&self.teams, &mut *rng).iter().map(|&x| x).collect();
         }
 
         // Get the first team in the shuffled teams that isn't already in this match
-        // (for when rounds split in the middle of a match)
+        // (for when rounds split in the middle of a match), preferring those that have had enough turnaround.
+        let available = |x: &usize| !match_picked.iter().any(|y| x == y);
+        let rested = |x: &usize| last_seen[*x].map(|l| m as isize - l >= self.min_turnaround as isize).unwrap_or(true);
         let pos = teams
           .iter()
-          .position(|&x| !match_picked.iter().any(|&y| x == y))
+          .position(|x| available(x) && rested(x))
+          .or_else(|| teams.iter().position(available))
           .unwrap();
         let picked = teams.remove(pos);
         match_picked.push(picked);
+        last_seen[picked] = Some(m as isize);
 
         schedule[(s, m)] = picked;
       }
@@ -91,7 +147,32 @@ impl ScheduleGenerator {
     let cooccurrence = self.cooccurrence_matrix(&schedule);
 
     // Calculate stddev of upper triangle (first part of the cooccurrence)
-    cooccurrence.map(|c| stddev(&upper_triangle(&c, 1)))
+    let score = cooccurrence.map(|c| stddev(&upper_triangle(&c, 1)))?;
+
+    match self.turnaround_violations(schedule) {
+      0 => Some(score),
+      _ if self.hard_turnaround => None,
+      n => Some(score + (n as f64) * TURNAROUND_PENALTY)
+    }
+  }
+
+  // Number of appearances that come too soon after the team's previous appearance.
+  pub fn turnaround_violations(&self, schedule: &Schedule) -> usize {
+    let mut last_seen = self.initial_last_seen();
+    let mut violations = 0;
+
+    for (m, col) in schedule.0.column_iter().enumerate() {
+      for &team in col.iter() {
+        if let Some(last) = last_seen[team] {
+          if (m as isize - last) < self.min_turnaround as isize {
+            violations += 1;
+          }
+        }
+        last_seen[team] = Some(m as isize);
+      }
+    }
+
+    violations
   }
 
   pub fn cooccurrence_matrix(&self, schedule: &Schedule) -> Option<na::DMatrix<usize>> {
@@ -143,19 +224,79 @@ impl ScheduleGenerator {
     stations
   }
 
+  // Number of times each pair of teams are on the same alliance (partners) and opposing alliances (opponents).
+  // The first half of the stations are one alliance, the second half the other.
+  pub fn alliance_matrices(&self, schedule: &Schedule) -> (na::DMatrix<usize>, na::DMatrix<usize>) {
+    let mut partners: na::DMatrix<usize> = na::DMatrix::zeros(self.num_teams, self.num_teams);
+    let mut opponents: na::DMatrix<usize> = na::DMatrix::zeros(self.num_teams, self.num_teams);
+    let per_alliance = self.num_stations / 2;
+
+    for m in schedule.0.column_iter() {
+      for (i, &t1) in m.iter().enumerate() {
+        for (j, &t2) in m.iter().enumerate() {
+          if i == j {
+            continue;
+          } else if (i < per_alliance) == (j < per_alliance) {
+            partners[(t1, t2)] += 1;
+          } else {
+            opponents[(t1, t2)] += 1;
+          }
+        }
+      }
+    }
+
+    (partners, opponents)
+  }
+
+  // Smallest gap (in matches) between two consecutive appearances of each team, where 1 is back-to-back.
+  pub fn turnarounds(&self, schedule: &Schedule) -> Vec<Option<usize>> {
+    let mut last_seen = self.initial_last_seen();
+    let mut turnarounds: Vec<Option<usize>> = vec![None; self.num_teams];
+
+    for (m, col) in schedule.0.column_iter().enumerate() {
+      for &team in col.iter() {
+        if let Some(last) = last_seen[team] {
+          let gap = (m as isize - last) as usize;
+          turnarounds[team] = Some(turnarounds[team].map_or(gap, |t| cmp::min(t, gap)));
+        }
+        last_seen[team] = Some(m as isize);
+      }
+    }
+
+    turnarounds
+  }
+
   pub fn generate_incremental_station_balance_schedule(&self, schedule: &Schedule) -> Schedule {
-    let mut rng = rand::thread_rng();
+    let mut rng = self.rng.borrow_mut();
 
     let mut sched = schedule.0.clone();
     let col = rng.gen_range(0..sched.ncols());
-    sched.set_column(col, &shuffle(&sched.column(col)));
+    sched.set_column(col, &shuffle(&sched.column(col), &mut *rng));
     Schedule(sched)
   }
 
-  pub fn generate(&self, anneal_team_balance: Annealer, anneal_station_balance: Annealer) -> GenerationResult {
+  pub fn generate(&self, anneal_team_balance: Annealer, anneal_station_balance: Annealer) -> anyhow::Result<GenerationResult> {
     let t0 = time::Instant::now();
 
-    let seed = self.generate_simple_schedule();
+    if self.min_turnaround * self.num_stations > self.num_teams {
+      anyhow::bail!("A minimum turnaround of {} needs at least {} teams", self.min_turnaround, self.min_turnaround * self.num_stations);
+    }
+
+    // The annealer gets its own RNG, since the schedule generators borrow ours while it runs
+    let mut anneal_rng = StdRng::seed_from_u64(self.rng.borrow_mut().gen());
+
+    let mut seed = self.generate_simple_schedule();
+    if self.hard_turnaround {
+      // The annealer needs a valid schedule to start from
+      let mut attempts = 0;
+      while self.schedule_team_balance_score(&seed).is_none() {
+        attempts += 1;
+        if attempts >= MAX_SEED_ATTEMPTS {
+          anyhow::bail!("Could not generate a schedule meeting the minimum turnaround of {} matches", self.min_turnaround);
+        }
+        seed = self.generate_simple_schedule();
+      }
+    }
     let t1 = time::Instant::now();
 
     info!("Seed schedule generated (in {:.2}s)", (t1 - t0).as_secs_f32());
@@ -164,6 +305,7 @@ impl ScheduleGenerator {
     let t2 = time::Instant::now();
     let (annealed_1, tb_initial_score, tb_score) = anneal_team_balance.anneal(
       seed,
+      &mut anneal_rng,
       // |s| self.generate_incremental_team_balance_schedule(s),
       |_| self.generate_simple_schedule(),
       |s| self.schedule_team_balance_score(s),
@@ -183,6 +325,7 @@ impl ScheduleGenerator {
     let t4 = time::Instant::now();
     let (annealed_2, sb_initial_score, sb_score) = anneal_station_balance.anneal(
       annealed_1,
+      &mut anneal_rng,
       |s| self.generate_incremental_station_balance_schedule(s),
       |s| self.schedule_station_balance_scores(s),
     );
@@ -208,13 +351,13 @@ impl ScheduleGenerator {
     debug!("Station matrix:");
     debug!("{}", sm);
 
-    GenerationResult {
+    Ok(GenerationResult {
       schedule: annealed_2,
       team_balance_score: tb_score,
       station_balance_score: sb_score,
       cooccurrence: cooc,
       station_dist: sm,
-    }
+    })
   }
 }
 
@@ -234,13 +377,12 @@ impl Annealer {
     }
   }
 
-  pub fn anneal<T, G, E>(&self, initial: T, generator: G, evaluator: E) -> (T, f64, f64)
+  pub fn anneal<T, R, G, E>(&self, initial: T, rng: &mut R, generator: G, evaluator: E) -> (T, f64, f64)
   where
+    R: Rng,
     G: Fn(&T) -> T,
     E: Fn(&T) -> Option<f64>,
   {
-    let mut rng = rand::thread_rng();
-
     let mut temperature = self.temp_start;
     let mut current = initial;
     let mut current_score = evaluator(&current).unwrap();
@@ -267,13 +409,13 @@ impl Annealer {
 }
 
 // Allow any storage - slice or vec
-fn shuffle<S>(mat: &na::Matrix<usize, na::Dyn, na::U1, S>) -> na::DVector<usize>
+fn shuffle<S, R>(mat: &na::Matrix<usize, na::Dyn, na::U1, S>, rng: &mut R) -> na::DVector<usize>
 where
   S: na::storage::Storage<usize, na::Dyn, na::U1>,
+  R: Rng,
 {
-  let mut rng = rand::thread_rng();
   let mut x: Vec<usize> = mat.into_iter().map(|x| *x).collect();
-  x.shuffle(&mut rng);
+  x.shuffle(rng);
   na::DVector::from_vec(x)
 }
 
@@ -297,3 +439,60 @@ fn upper_triangle(mat: &na::DMatrix<usize>, shift: usize) -> na::DVector<usize>
   }
   upper
 }
+
+#[cfg(test)]
+mod tests {
+  use nalgebra as na;
+
+  use super::{Annealer, Schedule, ScheduleGenerator};
+
+  // Rows are stations, columns are matches
+  fn schedule(nrows: usize, matches: &[usize]) -> Schedule {
+    Schedule(na::DMatrix::from_column_slice(nrows, matches.len() / nrows, matches))
+  }
+
+  #[test]
+  fn no_surrogates_when_even() {
+    let sched = schedule(2, &[ 0, 1, 2, 3, 0, 2, 1, 3 ]);
+    assert!(sched.surrogates(4).iter().all(|&s| !s));
+  }
+
+  #[test]
+  fn surrogate_is_third_match() {
+    // Team 0 plays 4 times, everyone else 3
+    let sched = schedule(2, &[ 0, 1, 0, 2, 1, 2, 0, 1, 0, 2 ]);
+    let surrogates = sched.surrogates(3);
+    let flagged: Vec<(usize, usize)> = surrogates.iter().enumerate().filter(|(_, &s)| s).map(|(i, _)| (i % 2, i / 2)).collect();
+    assert_eq!(flagged, vec![ (0, 3) ]);
+  }
+
+  #[test]
+  fn surrogate_is_last_match_with_fewer() {
+    // Team 0 plays twice, everyone else once
+    let sched = schedule(2, &[ 0, 1, 2, 3, 0, 4 ]);
+    let surrogates = sched.surrogates(5);
+    assert_eq!(surrogates.iter().filter(|&&s| s).count(), 1);
+    assert!(surrogates[(0, 2)]);
+  }
+
+  #[test]
+  fn generated_surrogates() {
+    // 8 teams over 7 matches is 42 appearances, so 2 teams play an extra match
+    let generator = ScheduleGenerator::new(8, 7, 6, 4788);
+    let result = generator.generate(Annealer::new(1.0, 0.0, 100), Annealer::new(1.0, 0.0, 100)).unwrap();
+    let surrogates = result.schedule.surrogates(8);
+
+    let mut counted = vec![0usize; 8];
+    let mut extra = vec![0usize; 8];
+    for (&team, &surrogate) in result.schedule.0.iter().zip(surrogates.iter()) {
+      match surrogate {
+        true => extra[team] += 1,
+        false => counted[team] += 1,
+      }
+    }
+
+    // Every team has the same number of matches that count towards rankings
+    assert!(counted.iter().all(|&c| c == 5));
+    assert_eq!(extra.iter().sum::<usize>(), 2);
+  }
+}
@@ -0,0 +1,67 @@
+use chrono::Duration;
+use jms_base::kv;
+use jms_core_lib::{models::{Match, MatchType, ScheduleBlock, ScheduleBlockType, ScheduleSlip}};
+use log::{info, warn};
+
+pub struct ScheduleRetimer;
+
+impl ScheduleRetimer {
+  /**
+   * Shift the remaining Qualification matches from their original start time by the current schedule slip.
+   * Matches keep at least one cycle time between them, and a match that would run past the end of its schedule block is
+   * pushed into the next Qualification block (e.g. after lunch), cascading into the matches already there. Matches are never
+   * moved earlier than they were originally scheduled, so teams aren't caught out if the field gets ahead.
+   * Playoff matches aren't touched here since the playoff generator already times them from the playoff blocks.
+   */
+  pub fn retime(kv: &kv::KVConnection) -> anyhow::Result<usize> {
+    let all_matches = Match::sorted(kv)?;
+    let delta = ScheduleSlip::compute(&all_matches).delta.0;
+
+    let blocks: Vec<(ScheduleBlock, Duration)> = ScheduleBlock::sorted(kv)?.into_iter().filter_map(|b| match &b.block_type {
+      ScheduleBlockType::Qualification { cycle_time } => { let ct = cycle_time.0; Some((b, ct)) },
+      _ => None
+    }).collect();
+
+    let to_retime = all_matches.into_iter()
+      .filter(|m| m.match_type == MatchType::Qualification && !m.played && m.actual_start_time.is_none());
+
+    let mut prev: Option<(chrono::DateTime<chrono::Local>, usize)> = None;
+    let mut n_moved = 0;
+
+    for mut m in to_retime {
+      let original = m.scheduled_start_time();
+      let mut block_i = match blocks.iter().position(|(b, _)| original >= b.start_time && original < b.end_time) {
+        Some(i) => i,
+        // Not in a qualification block - we don't know how to fit it in, so leave it alone.
+        None => continue
+      };
+
+      let mut new_start = (original + delta).max(original);
+      if let Some((prev_start, prev_block)) = prev {
+        block_i = block_i.max(prev_block);
+        new_start = new_start.max(prev_start + blocks[block_i].1);
+      }
+      new_start = new_start.max(blocks[block_i].0.start_time);
+
+      while new_start + blocks[block_i].1 > blocks[block_i].0.end_time {
+        if block_i + 1 >= blocks.len() {
+          warn!("Schedule Overrun! {} is past the end of the last Qualification block", m.name);
+          break;
+        }
+        block_i += 1;
+        new_start = new_start.max(blocks[block_i].0.start_time);
+      }
+
+      // Only the times are written, so we don't race the arena marking the match as started
+      if new_start != m.start_time {
+        m.set_original_start_time(Some(original), kv)?;
+        m.set_start_time(new_start, kv)?;
+        n_moved += 1;
+      }
+      prev = Some((new_start, block_i));
+    }
+
+    info!("Re-timed {} matches (slip {}s)", n_moved, delta.num_seconds());
+    Ok(n_moved)
+  }
+}
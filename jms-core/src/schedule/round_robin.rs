@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use jms_core_lib::{models::{CommittedMatchScores, Match, MatchType, PlayoffMode, RoundRobinStanding}, scoring::scores::ScoringConfig};
+
+use super::{bracket::{best_of_sets, template_update}, playoffs::{GenerationUpdate, IncompleteMatch, PlayoffAllianceDescriptor, PlayoffScheduleItem, PlayoffTemplate}};
+
+// Each alliance plays every other alliance once, using the circle method so that each round has every alliance
+// playing at most once. With an odd number of alliances, one alliance sits out each round.
+pub fn round_robin_sets(n_alliances: usize) -> Vec<Vec<(usize, usize)>> {
+  let mut alliances: Vec<Option<usize>> = (1..=n_alliances).map(Some).collect();
+  if alliances.len() % 2 == 1 {
+    alliances.push(None);
+  }
+
+  let n = alliances.len();
+  let mut rounds = vec![];
+  for round in 0..n.saturating_sub(1) {
+    let mut sets = vec![];
+    for i in 0..(n / 2) {
+      if let (Some(a), Some(b)) = (alliances[i], alliances[n - 1 - i]) {
+        // Alternate sides so alliances aren't always on the same side of the field
+        sets.push(if (round + i) % 2 == 0 { (a, b) } else { (b, a) });
+      }
+    }
+    rounds.push(sets);
+
+    // Rotate everyone except the first alliance
+    let last = alliances.remove(n - 1);
+    alliances.insert(1, last);
+  }
+  rounds
+}
+
+pub fn round_robin_template(playoff_mode: &PlayoffMode, matches: &Vec<Match>, scores: &HashMap<String, CommittedMatchScores>, config: &ScoringConfig) -> anyhow::Result<PlayoffTemplate> {
+  let mut items = vec![];
+
+  for (round, sets) in round_robin_sets(playoff_mode.n_alliances).into_iter().enumerate() {
+    for (set, (red, blue)) in sets.into_iter().enumerate() {
+      items.push(PlayoffScheduleItem::Match(IncompleteMatch {
+        ty: MatchType::Playoff, round: round + 1, set: set + 1, match_num: 1,
+        red: PlayoffAllianceDescriptor::Alliance(red), blue: PlayoffAllianceDescriptor::Alliance(blue)
+      }));
+    }
+  }
+  items.push(PlayoffScheduleItem::AwardsBreak);
+
+  // The top two alliances go through to the finals once every round robin match has been played
+  let round_robin_matches = items.iter().filter(|x| matches!(x, PlayoffScheduleItem::Match(..))).count();
+  let played = matches.iter().filter(|m| m.match_type == MatchType::Playoff && m.played).count();
+
+  let (red, blue) = if round_robin_matches > 0 && played >= round_robin_matches {
+    let standings = RoundRobinStanding::calculate(playoff_mode.n_alliances, matches, scores, config)?;
+    (
+      standings.get(0).map(|s| PlayoffAllianceDescriptor::Alliance(s.alliance)).unwrap_or(PlayoffAllianceDescriptor::RoundRobinRank(1)),
+      standings.get(1).map(|s| PlayoffAllianceDescriptor::Alliance(s.alliance)).unwrap_or(PlayoffAllianceDescriptor::RoundRobinRank(2)),
+    )
+  } else {
+    (PlayoffAllianceDescriptor::RoundRobinRank(1), PlayoffAllianceDescriptor::RoundRobinRank(2))
+  };
+
+  items.extend(best_of_sets(&[IncompleteMatch { ty: MatchType::Final, round: 1, set: 1, match_num: 1, red, blue }], playoff_mode.final_best_of));
+
+  Ok(PlayoffTemplate {
+    items,
+    playoff_wins: None,
+    final_wins: PlayoffMode::wins_required(playoff_mode.final_best_of)
+  })
+}
+
+pub fn round_robin_update(playoff_mode: &PlayoffMode, matches: &Vec<Match>, scores: &HashMap<String, CommittedMatchScores>, config: &ScoringConfig) -> anyhow::Result<GenerationUpdate> {
+  if playoff_mode.n_alliances < 2 {
+    anyhow::bail!("A Round Robin needs at least 2 alliances!");
+  }
+
+  let template = round_robin_template(playoff_mode, matches, scores, config)?;
+  template_update(playoff_mode, &template, matches, scores, config)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use jms_core_lib::{models::{CommittedMatchScores, Match, MatchType, PlayoffMode, PlayoffModeType}, scoring::{crescendo::CrescendoLiveScore, game::Game, scores::{LiveScore, MatchScore, ScoringConfig}}};
+
+  use crate::schedule::playoffs::{IncompleteMatch, PlayoffAllianceDescriptor, PlayoffScheduleItem};
+
+  use super::{round_robin_sets, round_robin_template};
+
+  #[test]
+  fn sets() {
+    for n in 2..=8 {
+      let rounds = round_robin_sets(n);
+      assert_eq!(rounds.len(), if n % 2 == 0 { n - 1 } else { n });
+
+      // Nobody plays twice in a round
+      for sets in &rounds {
+        let mut alliances: Vec<usize> = sets.iter().flat_map(|&(a, b)| [a, b]).collect();
+        alliances.sort();
+        alliances.dedup();
+        assert_eq!(alliances.len(), sets.len() * 2);
+      }
+
+      // Everyone plays everyone else exactly once
+      let mut pairs: Vec<(usize, usize)> = rounds.iter().flatten().map(|&(a, b)| (a.min(b), a.max(b))).collect();
+      pairs.sort();
+      let expected: Vec<(usize, usize)> = (1..=n).flat_map(|a| ((a + 1)..=n).map(move |b| (a, b))).collect();
+      assert_eq!(pairs, expected);
+    }
+  }
+
+  fn played(round: usize, set: usize, red: usize, blue: usize) -> Match {
+    Match {
+      id: Match::gen_id(MatchType::Playoff, round, set, 1),
+      name: Match::gen_name(MatchType::Playoff, round, set, 1),
+      start_time: chrono::Local::now() + chrono::Duration::minutes((round * 10 + set) as i64),
+      match_type: MatchType::Playoff,
+      round, set_number: set, match_number: 1,
+      blue_teams: vec![], blue_alliance: Some(blue), red_teams: vec![], red_alliance: Some(red),
+      dqs: vec![], surrogates: vec![], replay_reason: None,
+      actual_start_time: None, original_start_time: None,
+      played: true, ready: true
+    }
+  }
+
+  fn finals(mode: &PlayoffMode, matches: &Vec<Match>, scores: &HashMap<String, CommittedMatchScores>) -> Vec<IncompleteMatch> {
+    round_robin_template(mode, matches, scores, &ScoringConfig::new(Game::Crescendo2024)).unwrap().items.into_iter().filter_map(|i| match i {
+      PlayoffScheduleItem::Match(m) if m.ty == MatchType::Final => Some(m),
+      _ => None
+    }).collect()
+  }
+
+  #[test]
+  fn finals_from_standings() {
+    let mode = PlayoffMode { mode: PlayoffModeType::RoundRobin, n_alliances: 3, final_best_of: 3, ..PlayoffMode::default() };
+    let matches: Vec<Match> = round_robin_sets(3).into_iter().enumerate()
+      .flat_map(|(round, sets)| sets.into_iter().enumerate().map(move |(set, (red, blue))| played(round + 1, set + 1, red, blue)))
+      .collect();
+
+    // The highest seeded alliance wins every match
+    let live = |adjustment| LiveScore::Crescendo2024(CrescendoLiveScore { adjustment, ..CrescendoLiveScore::new(3) });
+    let scores: HashMap<String, CommittedMatchScores> = matches.iter().map(|m| {
+      let (red, blue) = match m.red_alliance < m.blue_alliance { true => (10, 0), false => (0, 10) };
+      (m.id.clone(), CommittedMatchScores { match_id: m.id.clone(), scores: vec![ MatchScore { red: live(red), blue: live(blue) } ], last_update: chrono::Local::now() })
+    }).collect();
+
+    // Until the round robin is over, the finalists aren't known
+    let pending = finals(&mode, &matches[..2].to_vec(), &scores);
+    assert_eq!(pending.len(), 2);
+    assert!(pending.iter().all(|m| m.red == PlayoffAllianceDescriptor::RoundRobinRank(1) && m.blue == PlayoffAllianceDescriptor::RoundRobinRank(2)));
+
+    let resolved = finals(&mode, &matches, &scores);
+    assert_eq!(resolved.len(), 2);
+    assert!(resolved.iter().all(|m| m.red == PlayoffAllianceDescriptor::Alliance(1) && m.blue == PlayoffAllianceDescriptor::Alliance(2)));
+  }
+}
@@ -1,10 +1,10 @@
 use std::time::Duration;
 
 use jms_base::{mq, kv};
-use jms_core_lib::{models::{self, TeamRanking}, db::{Table, Singleton}, scoring::scores::MatchScore};
+use jms_core_lib::{models::{self, RetimeSettings, TeamRanking}, db::{Table, Singleton}, scoring::scores::MatchScore};
 use log::error;
 
-use crate::schedule::playoffs::PlayoffMatchGenerator;
+use crate::schedule::{playoffs::PlayoffMatchGenerator, retime::ScheduleRetimer};
 
 pub struct ScoringService {
   pub kv: kv::KVConnection,
@@ -32,6 +32,10 @@ impl ScoringService {
 
             // Update the playoffs bracket
             PlayoffMatchGenerator::update(&self.kv)?;
+
+            if RetimeSettings::get(&self.kv)?.auto_retime {
+              ScheduleRetimer::retime(&self.kv)?;
+            }
           },
           Some(Err(e)) => error!("Error: {}", e),
           None => ()
@@ -0,0 +1,239 @@
+use std::{collections::{HashMap, VecDeque}, time::{Duration, Instant}};
+
+use jms_core_lib::db::Singleton;
+
+use crate::DriverStationReport;
+
+// Packet loss is taken over at least this long, since the DS only reports totals since it connected
+const PACKET_LOSS_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum DiagnosisCode {
+  Estop,
+  WrongStation,
+  Radio,
+  Rio,
+  Code,
+  HighLatency,
+  PacketLoss,
+  LowBattery,
+  Brownout,
+}
+
+impl DiagnosisCode {
+  // The short code shown on the FTA view and the alliance station panels
+  pub fn code(&self) -> &'static str {
+    match self {
+      DiagnosisCode::Estop => "R-EST",
+      DiagnosisCode::WrongStation => "MOVE",
+      DiagnosisCode::Radio => "RADIO",
+      DiagnosisCode::Rio => "RIO",
+      DiagnosisCode::Code => "CODE",
+      DiagnosisCode::HighLatency => "L8NC",
+      DiagnosisCode::PacketLoss => "LOSS",
+      DiagnosisCode::LowBattery => "LBATT",
+      DiagnosisCode::Brownout => "BRWN",
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DiagnosisRule {
+  pub code: DiagnosisCode,
+  pub enabled: bool,
+  // Latency in ms, packet loss in %, and battery in volts. Ignored by the other codes.
+  pub threshold: f64,
+  // How long the condition has to hold before it's raised, and be gone before it's cleared
+  pub raise_after_ms: usize,
+  pub clear_after_ms: usize,
+}
+
+impl DiagnosisRule {
+  pub fn new(code: DiagnosisCode, threshold: f64, raise_after_ms: usize, clear_after_ms: usize) -> Self {
+    Self { code, enabled: true, threshold, raise_after_ms, clear_after_ms }
+  }
+
+  pub fn triggered(&self, report: &DriverStationReport, wrong_station: bool, packet_loss: f64) -> bool {
+    match self.code {
+      DiagnosisCode::Estop => report.estop,
+      DiagnosisCode::WrongStation => wrong_station,
+      DiagnosisCode::Radio => !report.radio_ping,
+      DiagnosisCode::Rio => !report.rio_ping,
+      DiagnosisCode::Code => !report.robot_ping,
+      DiagnosisCode::HighLatency => report.rtt as f64 > self.threshold,
+      DiagnosisCode::PacketLoss => packet_loss > self.threshold,
+      DiagnosisCode::LowBattery => report.battery_voltage < self.threshold,
+      DiagnosisCode::Brownout => report.brownout,
+    }
+  }
+}
+
+// The rules used to diagnose each Driver Station, in priority order - only the first one that's raised is shown.
+#[derive(jms_macros::Updateable)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DiagnosisSettings {
+  pub rules: Vec<DiagnosisRule>,
+}
+
+impl Default for DiagnosisSettings {
+  fn default() -> Self {
+    Self {
+      rules: vec![
+        DiagnosisRule::new(DiagnosisCode::Estop, 0.0, 0, 0),
+        DiagnosisRule::new(DiagnosisCode::WrongStation, 0.0, 0, 0),
+        DiagnosisRule::new(DiagnosisCode::Radio, 0.0, 0, 1000),
+        DiagnosisRule::new(DiagnosisCode::Rio, 0.0, 0, 1000),
+        DiagnosisRule::new(DiagnosisCode::Code, 0.0, 0, 1000),
+        DiagnosisRule::new(DiagnosisCode::HighLatency, 100.0, 1000, 2000),
+        DiagnosisRule::new(DiagnosisCode::PacketLoss, 10.0, 2000, 2000),
+        DiagnosisRule::new(DiagnosisCode::LowBattery, 9.0, 1000, 5000),
+        DiagnosisRule::new(DiagnosisCode::Brownout, 0.0, 0, 5000),
+      ]
+    }
+  }
+}
+
+impl Singleton for DiagnosisSettings {
+  const KEY: &'static str = "db:ds_diagnosis";
+}
+
+// Tracks how long each rule has been (or stopped being) triggered for a single Driver Station, so that a condition that
+// flickers in and out doesn't flicker the diagnosis with it.
+#[derive(Debug, Default)]
+pub struct DiagnosisTracker {
+  // Whether the rule is raised, and when its condition last changed to disagree with that
+  state: HashMap<DiagnosisCode, (bool, Option<Instant>)>,
+  // Recent (time, lost, sent) packet counters from the DS
+  comms: VecDeque<(Instant, u16, u16)>,
+}
+
+impl DiagnosisTracker {
+  pub fn update(&mut self, settings: &DiagnosisSettings, report: &DriverStationReport, wrong_station: bool) -> Option<DiagnosisCode> {
+    self.update_at(settings, report, wrong_station, Instant::now())
+  }
+
+  fn update_at(&mut self, settings: &DiagnosisSettings, report: &DriverStationReport, wrong_station: bool, now: Instant) -> Option<DiagnosisCode> {
+    let mut diagnosis = None;
+    let packet_loss = self.packet_loss(report, now);
+
+    for rule in settings.rules.iter().filter(|r| r.enabled) {
+      let (raised, changed) = self.state.entry(rule.code).or_insert((false, None));
+      if rule.triggered(report, wrong_station, packet_loss) == *raised {
+        *changed = None;
+      } else {
+        let since = *changed.get_or_insert(now);
+        let hold = if *raised { rule.clear_after_ms } else { rule.raise_after_ms };
+        if now - since >= Duration::from_millis(hold as u64) {
+          *raised = !*raised;
+          *changed = None;
+        }
+      }
+
+      if *raised && diagnosis.is_none() {
+        diagnosis = Some(rule.code);
+      }
+    }
+
+    diagnosis
+  }
+
+  // Packet loss in % over the last PACKET_LOSS_WINDOW
+  fn packet_loss(&mut self, report: &DriverStationReport, now: Instant) -> f64 {
+    // The counters going backwards means the DS has started counting again
+    if self.comms.back().is_some_and(|&(_, lost, sent)| report.pkts_lost < lost || report.pkts_sent < sent) {
+      self.comms.clear();
+    }
+    self.comms.push_back((now, report.pkts_lost, report.pkts_sent));
+    while self.comms.len() > 1 && now - self.comms[1].0 >= PACKET_LOSS_WINDOW {
+      self.comms.pop_front();
+    }
+
+    let (_, first_lost, first_sent) = self.comms[0];
+    let (lost, sent) = ((report.pkts_lost - first_lost) as f64, (report.pkts_sent - first_sent) as f64);
+    lost / (lost + sent).max(1.0) * 100.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use crate::{DriverStationReport, RobotState};
+
+  use super::{DiagnosisCode, DiagnosisRule, DiagnosisSettings, DiagnosisTracker};
+
+  fn report(battery_voltage: f64) -> DriverStationReport {
+    DriverStationReport {
+      team: 4788, robot_ping: true, rio_ping: true, radio_ping: true, battery_voltage,
+      estop: false, mode: RobotState::Teleop, pkts_sent: 100, pkts_lost: 0, rtt: 5,
+      actual_station: None, brownout: false, diagnosis: None
+    }
+  }
+
+  #[test]
+  fn hysteresis() {
+    let settings = DiagnosisSettings { rules: vec![ DiagnosisRule::new(DiagnosisCode::LowBattery, 9.0, 1000, 5000) ] };
+    let mut tracker = DiagnosisTracker::default();
+    let t0 = Instant::now();
+    let at = |ms: u64| t0 + Duration::from_millis(ms);
+
+    // A dip shorter than raise_after_ms isn't raised
+    assert_eq!(tracker.update_at(&settings, &report(8.0), false, at(0)), None);
+    assert_eq!(tracker.update_at(&settings, &report(8.0), false, at(500)), None);
+    assert_eq!(tracker.update_at(&settings, &report(12.0), false, at(900)), None);
+
+    // The timer starts again from the next dip, and raises once it's held long enough
+    assert_eq!(tracker.update_at(&settings, &report(8.0), false, at(1000)), None);
+    assert_eq!(tracker.update_at(&settings, &report(8.0), false, at(1999)), None);
+    assert_eq!(tracker.update_at(&settings, &report(8.0), false, at(2000)), Some(DiagnosisCode::LowBattery));
+
+    // Recovering briefly doesn't clear it
+    assert_eq!(tracker.update_at(&settings, &report(12.0), false, at(3000)), Some(DiagnosisCode::LowBattery));
+    assert_eq!(tracker.update_at(&settings, &report(8.0), false, at(4000)), Some(DiagnosisCode::LowBattery));
+
+    // ... but staying recovered for clear_after_ms does
+    assert_eq!(tracker.update_at(&settings, &report(12.0), false, at(5000)), Some(DiagnosisCode::LowBattery));
+    assert_eq!(tracker.update_at(&settings, &report(12.0), false, at(9999)), Some(DiagnosisCode::LowBattery));
+    assert_eq!(tracker.update_at(&settings, &report(12.0), false, at(10000)), None);
+  }
+
+  #[test]
+  fn priority() {
+    let mut settings = DiagnosisSettings::default();
+    let mut tracker = DiagnosisTracker::default();
+    let now = Instant::now();
+
+    let mut r = report(12.0);
+    r.robot_ping = false;
+    // Both are raised straight away, but only the first in the list is shown
+    assert_eq!(tracker.update_at(&settings, &r, true, now), Some(DiagnosisCode::WrongStation));
+    assert_eq!(tracker.update_at(&settings, &r, false, now), Some(DiagnosisCode::Code));
+
+    // Disabled rules are skipped
+    settings.rules.iter_mut().filter(|r| r.code == DiagnosisCode::Code).for_each(|r| r.enabled = false);
+    let mut tracker = DiagnosisTracker::default();
+    assert_eq!(tracker.update_at(&settings, &r, false, now), None);
+  }
+
+  #[test]
+  fn packet_loss() {
+    let settings = DiagnosisSettings { rules: vec![ DiagnosisRule::new(DiagnosisCode::PacketLoss, 10.0, 0, 0) ] };
+    let mut tracker = DiagnosisTracker::default();
+    let t0 = Instant::now();
+    let mut update = |ms: u64, pkts_lost: u16, pkts_sent: u16| {
+      tracker.update_at(&settings, &DriverStationReport { pkts_lost, pkts_sent, ..report(12.0) }, false, t0 + Duration::from_millis(ms))
+    };
+
+    // A burst of loss after a long clean run is still caught, even though it's tiny compared to the totals
+    assert_eq!(update(0, 0, 10000), None);
+    assert_eq!(update(500, 0, 10025), None);
+    assert_eq!(update(1000, 50, 10025), Some(DiagnosisCode::PacketLoss));
+
+    // ... and clears once the window has moved past it
+    assert_eq!(update(1500, 50, 10050), Some(DiagnosisCode::PacketLoss));
+    assert_eq!(update(2000, 50, 10075), None);
+
+    // The DS starting to count again isn't loss
+    assert_eq!(update(2500, 0, 25), None);
+  }
+}
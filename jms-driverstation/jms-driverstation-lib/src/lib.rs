@@ -1,6 +1,12 @@
 use std::num::ParseIntError;
 
-use jms_core_lib::{models::AllianceStationId, db::Table};
+pub mod diagnosis;
+pub mod tcp_codec;
+pub mod udp_codec;
+
+pub const DS_LOG_TOPIC: &'static str = "ds.log";
+
+use jms_core_lib::{models::{AllianceParseError, AllianceStationId, Match, MatchType}, db::{Singleton, Table}};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub enum RobotState {
@@ -30,23 +36,78 @@ pub struct DriverStationReport {
   pub pkts_lost: u16,
   pub rtt: u8,
 
-  pub actual_station: Option<AllianceStationId>
+  pub actual_station: Option<AllianceStationId>,
+
+  // From the DS log data, which only comes through once a second
+  #[serde(default)]
+  pub brownout: bool,
+  // Worked out by the DS service from the DiagnosisSettings rules
+  #[serde(default)]
+  pub diagnosis: Option<diagnosis::DiagnosisCode>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum DSDisconnectionReason {
+  TCPClosed,
+  TCPFault,
+  Timeout,
+  WrongMatch
+}
+
+// The Driver Station connection for each Alliance Station, so a DS that's connected but waiting or in the wrong station
+// can be told apart from one that isn't there at all. A DS is recorded against the station its team is in, or failing
+// that, the station it's plugged into.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DriverStationConnection {
+  pub station: AllianceStationId,
+  pub team: u16,
+  // The team according to the DS IP. None if the DS is on the admin / flat network.
+  pub ip_team: Option<u16>,
+  pub address: String,
+  pub connected: bool,
+  pub status: tcp_codec::Fms2DsStationStatus,
+  pub occupied_station: Option<AllianceStationId>,
+  pub connected_at: chrono::DateTime<chrono::Local>,
+  pub disconnected_at: Option<chrono::DateTime<chrono::Local>>,
+  pub disconnect_reason: Option<DSDisconnectionReason>,
 }
 
-impl DriverStationReport {
-  pub fn diagnosis(&self) -> Option<&'static str> {
-    if self.estop { return Some("ESTOP") }
-    if !self.radio_ping  { return Some("RADIO") }
-    if !self.rio_ping { return Some("RIO") }
-    if !self.robot_ping { return Some("CODE") }
+impl Table for DriverStationConnection {
+  const PREFIX: &'static str = "ds_conn";
+  type Id = AllianceStationId;
+  type Err = AllianceParseError;
 
-    if self.rtt > 100 { return Some("L8NC") }
-    if self.battery_voltage < 9.0 { return Some("LBATT") }
+  fn id(&self) -> Self::Id {
+    self.station
+  }
+}
 
-    return None
+impl DriverStationConnection {
+  pub fn describe(&self) -> String {
+    match (self.connected, self.status) {
+      (true, tcp_codec::Fms2DsStationStatus::Good) => "is connected, but not sending control packets".to_owned(),
+      (true, tcp_codec::Fms2DsStationStatus::Bad) => match self.occupied_station {
+        Some(occupied) => format!("is plugged into {}", occupied.to_string()),
+        None => "is in the wrong station".to_owned()
+      },
+      (true, tcp_codec::Fms2DsStationStatus::Waiting) => "is connected, but waiting for a station".to_owned(),
+      (false, _) => format!(
+        "disconnected{}{}",
+        self.disconnect_reason.map(|r| format!(" ({:?})", r)).unwrap_or_default(),
+        self.disconnected_at.map(|t| format!(" at {}", t.format("%H:%M:%S"))).unwrap_or_default()
+      ),
+    }
   }
 }
 
+// Log data and robot messages from the DS TCP connection, published on DS_LOG_TOPIC for the match logs.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(tag = "type")]
+pub enum DriverStationLogEvent {
+  Sample { team: u16, data: tcp_codec::DSLogData },
+  Messages { team: u16, messages: Vec<tcp_codec::TimestampedMessage> },
+}
+
 impl Table for DriverStationReport {
   const PREFIX: &'static str = "ds";
   type Id = u16;
@@ -57,6 +118,70 @@ impl Table for DriverStationReport {
   }
 }
 
+// The software versions reported by each team's Driver Station, kept across matches.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DriverStationVersions {
+  pub team: u16,
+  pub ds: Option<String>,
+  pub rio: Option<String>,
+  pub wpilib: Option<String>,
+}
+
+impl Table for DriverStationVersions {
+  const PREFIX: &'static str = "db:ds_versions";
+  type Id = u16;
+  type Err = ParseIntError;
+
+  fn id(&self) -> Self::Id {
+    self.team
+  }
+}
+
+impl DriverStationVersions {
+  // Everything that's older than the minimum versions. Versions we haven't heard yet (or can't make sense of) aren't flagged.
+  pub fn outdated(&self, settings: &DriverStationSettings) -> Vec<String> {
+    [
+      ("DS", &self.ds, &settings.min_ds_version),
+      ("RIO", &self.rio, &settings.min_rio_version),
+      ("WPILib", &self.wpilib, &settings.min_wpilib_version),
+    ].into_iter().filter_map(|(name, version, minimum)| match (version, minimum) {
+      (Some(v), Some(min)) if !version_parts(v).is_empty() && version_parts(v) < version_parts(min) => Some(format!("{} {} (minimum {})", name, v, min)),
+      _ => None
+    }).collect()
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DriverStationVersionStatus {
+  pub versions: DriverStationVersions,
+  pub outdated: Vec<String>,
+}
+
+// Versions come in all sorts of formats (24.0, 2024_v2.1, 2024.3.2), so we just compare the numbers in order.
+fn version_parts(version: &str) -> Vec<u64> {
+  version.split(|c: char| !c.is_ascii_digit()).filter_map(|p| p.parse().ok()).collect()
+}
+
+#[derive(jms_macros::Updateable)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct DriverStationSettings {
+  pub min_ds_version: Option<String>,
+  pub min_rio_version: Option<String>,
+  pub min_wpilib_version: Option<String>,
+  // Stations with out of date software can't be prestarted unless they're bypassed
+  pub block_outdated: bool,
+}
+
+impl Default for DriverStationSettings {
+  fn default() -> Self {
+    Self { min_ds_version: None, min_rio_version: None, min_wpilib_version: None, block_outdated: false }
+  }
+}
+
+impl Singleton for DriverStationSettings {
+  const KEY: &'static str = "db:ds_settings";
+}
+
 #[derive(Debug)]
 pub enum TournamentLevel {
   Test = 0,
@@ -64,4 +189,53 @@ pub enum TournamentLevel {
   Practice = 1,
   Qualification = 2,
   Playoff = 3,
+}
+
+impl From<MatchType> for TournamentLevel {
+  fn from(ty: MatchType) -> Self {
+    match ty {
+      MatchType::Test => TournamentLevel::Test,
+      MatchType::Qualification => TournamentLevel::Qualification,
+      MatchType::Playoff | MatchType::Final => TournamentLevel::Playoff,
+    }
+  }
+}
+
+// The match and play number shown on the Driver Station (and in its logs).
+// Qualifications use the match number, with the replay as the play number. Playoffs use the same encoding as cheesy-arena,
+// where the match number is XYZ - X is the bracket (Finals = 1, then elimination rounds from 2), Y is the set, and Z the match.
+pub fn ds_match_numbers(m: &Match) -> (u16, u8) {
+  match m.match_type {
+    MatchType::Test | MatchType::Qualification => (m.set_number as u16, m.match_number as u8),
+    MatchType::Playoff => (((m.round + 1) * 100 + m.set_number * 10 + m.match_number) as u16, 1),
+    MatchType::Final => ((100 + 10 + m.match_number) as u16, 1),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use jms_core_lib::models::{Match, MatchType};
+
+  use super::ds_match_numbers;
+
+  #[test]
+  fn match_numbers() {
+    let original = Match {
+      id: Match::gen_id(MatchType::Qualification, 1, 12, 1),
+      name: Match::gen_name(MatchType::Qualification, 1, 12, 1),
+      start_time: chrono::Local::now(),
+      match_type: MatchType::Qualification,
+      round: 1, set_number: 12, match_number: 1,
+      blue_teams: vec![], blue_alliance: None, red_teams: vec![], red_alliance: None,
+      dqs: vec![], surrogates: vec![], replay_reason: None,
+      actual_start_time: None, original_start_time: None,
+      played: true, ready: true
+    };
+    assert_eq!(ds_match_numbers(&original), (12, 1));
+
+    // A replay keeps the match number, and goes up a play
+    let replay = original.replay("Field Fault".to_owned(), chrono::Local::now()).unwrap();
+    assert_eq!(ds_match_numbers(&replay), (12, 2));
+    assert_eq!(ds_match_numbers(&replay.replay("Field Fault".to_owned(), chrono::Local::now()).unwrap()), (12, 3));
+  }
 }
\ No newline at end of file
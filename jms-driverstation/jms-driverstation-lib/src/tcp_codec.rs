@@ -1,6 +1,6 @@
 use bitvec::prelude::*;
 use bytes::{Buf, BufMut};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use jms_core_lib::models::AllianceStationId;
 use tokio_util::codec::{Decoder, Encoder};
 
@@ -17,22 +17,19 @@ pub enum Ds2FmsTCPTags {
   DSVersion(String),
   LogData(DSLogData),
   Ping,
-  #[allow(dead_code)]
   ErrorData(Vec<TimestampedMessage>),
   Unknown(u8, usize),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct TimestampedMessage {
-  #[allow(dead_code)]
-  timestamp: DateTime<Utc>,
-  #[allow(dead_code)]
-  message: String,
+  pub timestamp: DateTime<Utc>,
+  pub message: String,
 }
 
 // This is based off dslog
 // https://github.com/ligerbots/dslogparser/blob/master/dslogparser/dslogparser.py
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct DSLogData {
   pub rtt: f64,
   pub lost_percent: f64,
@@ -61,14 +58,12 @@ pub struct Fms2DsTCP {
 
 #[derive(Debug)]
 pub enum Fms2DsTCPTags {
-  #[allow(dead_code)]
   EventCode(String),
   StationInfo(AllianceStationId, Fms2DsStationStatus),
-  #[allow(dead_code)]
   GameData(String),
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub enum Fms2DsStationStatus {
   Good = 0,
   Bad = 1,
@@ -122,18 +117,15 @@ impl Decoder for DSTCPCodec {
       let tag: Option<Ds2FmsTCPTags> = match id {
         0x00 => {
           // WPILib Version
-          // Some(Ds2FmsTCPTags::WPILibVersion(utf8_str_with_len(&mut buf, size)?))
-          None
+          Some(Ds2FmsTCPTags::WPILibVersion(utf8_str(&mut buf)))
         }
         0x01 => {
           // RIO Version
-          // Some(Ds2FmsTCPTags::RIOVersion(utf8_str_with_len(&mut buf, size)?))
-          None
+          Some(Ds2FmsTCPTags::RIOVersion(utf8_str(&mut buf)))
         }
         0x02 => {
           // DS Version
-          // Some(Ds2FmsTCPTags::DSVersion(utf8_str_with_len(&mut buf, size)?))
-          None
+          Some(Ds2FmsTCPTags::DSVersion(utf8_str(&mut buf)))
         }
         0x16 => {
           // Log Data
@@ -177,27 +169,31 @@ impl Decoder for DSTCPCodec {
           Some(Ds2FmsTCPTags::LogData(dat))
         }
         0x17 => {
-          // Error / events data
-          None
-          // TODO: Having some out of bounds issues here
-          // let count = buf.get_u32();
-          // let msgs: Result<Vec<TimestampedMessage>, Self::Error> = (0..count)
-          //   .map(|_| {
-          //     let secs_since_1904 = buf.get_u64() as i64;
-          //     /* Offset to the unix epoch */
-          //     let datetime = NaiveDateTime::from_timestamp(secs_since_1904 - 2_082_844_800, 0);
-
-          //     buf.get_u64(); // Unknown bytes
-          //     let msg_len = buf.get_u32();
-
-          //     Ok(TimestampedMessage {
-          //       timestamp: DateTime::<Utc>::from_utc(datetime, Utc),
-          //       message: utf8_str_with_len(&mut buf, msg_len as usize)?,
-          //     })
-          //   })
-          //   .collect();
-
-          // Some(Ds2FmsTCPTags::ErrorData(msgs?))
+          // Error / events data. Each message has a LabVIEW timestamp (seconds since 1904 + a 2^-64 fraction), followed
+          // by a length-prefixed string. The count isn't always right, so we stop as soon as we run out of data.
+          let mut msgs = vec![];
+          if buf.remaining() >= 4 {
+            let count = buf.get_u32();
+            for _ in 0..count {
+              if buf.remaining() < 20 {
+                break;
+              }
+              let secs_since_1904 = buf.get_i64();
+              let fraction = buf.get_u64();
+              let msg_len = buf.get_u32() as usize;
+              if buf.remaining() < msg_len {
+                break;
+              }
+
+              let nanos = (((fraction as u128) * 1_000_000_000) >> 64) as u32;
+              msgs.push(TimestampedMessage {
+                /* Offset to the unix epoch */
+                timestamp: Utc.timestamp_opt(secs_since_1904 - 2_082_844_800, nanos).single().unwrap_or_else(Utc::now),
+                message: String::from_utf8_lossy(&buf.split_to(msg_len)).to_string(),
+              });
+            }
+          }
+          Some(Ds2FmsTCPTags::ErrorData(msgs))
         }
         0x18 => {
           // Team Number
@@ -223,6 +219,11 @@ impl Decoder for DSTCPCodec {
   }
 }
 
+// Version strings take up the rest of the tag, and are sometimes null-padded
+fn utf8_str(buf: &mut bytes::BytesMut) -> String {
+  String::from_utf8_lossy(&buf.split_to(buf.remaining())).trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_string()
+}
+
 impl Encoder<Fms2DsTCP> for DSTCPCodec {
   type Error = std::io::Error;
 
@@ -1,7 +1,7 @@
 use bytes::{Buf, BufMut};
 use chrono::{DateTime, Local, Timelike, Datelike};
 use jms_core_lib::models::AllianceStationId;
-use jms_driverstation_lib::{TournamentLevel, RobotState};
+use crate::{TournamentLevel, RobotState};
 use tokio_util::codec::{Decoder, Encoder};
 
 #[derive(Default, Debug, Clone)]
@@ -1,26 +1,15 @@
-use std::{net::SocketAddr, time::Duration, sync::{atomic::AtomicBool, Arc}};
+use std::{net::SocketAddr, time::Duration, sync::{atomic::AtomicBool, Arc, RwLock}};
 
-use chrono::Local;
+use chrono::{DateTime, Local};
 use futures::{StreamExt, SinkExt};
 use jms_arena_lib::{AllianceStation, ARENA_STATE_KEY, ArenaState, SerialisedLoadedMatch, ARENA_MATCH_KEY, MatchPlayState};
-use jms_base::kv::KVConnection;
-use jms_core_lib::{models::{AllianceStationId, Alliance}, db::Table};
-use jms_driverstation_lib::{RobotState, TournamentLevel, DriverStationReport};
-use log::error;
+use jms_base::{kv::KVConnection, mq::MessageQueueChannel};
+use jms_core_lib::{models::{AllianceStationId, Alliance, EventDetails, Match}, db::{Singleton, Table}};
+use jms_driverstation_lib::{RobotState, TournamentLevel, DriverStationReport, DriverStationLogEvent, diagnosis::{DiagnosisSettings, DiagnosisTracker}, DriverStationVersions, DriverStationConnection, DSDisconnectionReason, DS_LOG_TOPIC, ds_match_numbers, tcp_codec::{DSTCPCodec, Fms2DsStationStatus, Fms2DsTCP, Fms2DsTCPTags, Ds2FmsTCPTags}, udp_codec::{DSUDPCodec, Ds2FmsUDP, Fms2DsUDP, Ds2FmsUDPTags}};
+use log::{error, warn};
 use tokio::{net::{TcpStream, UdpSocket}, sync::broadcast, time::{Instant, self}};
 use tokio_util::{codec::Framed, udp::UdpFramed};
 
-use crate::{tcp_codec::{DSTCPCodec, Fms2DsStationStatus, Fms2DsTCP, Fms2DsTCPTags, Ds2FmsTCPTags}, udp_codec::{DSUDPCodec, Ds2FmsUDP, Fms2DsUDP, Ds2FmsUDPTags}};
-
-#[derive(Debug, PartialEq, Eq)]
-pub enum DSDisconnectionReason {
-  // TCPTimeout,
-  TCPClosed,
-  TCPFault,
-  Timeout,
-  WrongMatch
-}
-
 #[derive(Debug, PartialEq, Eq)]
 pub enum DSConnectionState {
   Connected,
@@ -36,18 +25,31 @@ pub struct DSConnection {
   framed_udp: UdpFramed<DSUDPCodec>, // UDP Outgoing
   udp_rx: broadcast::Receiver<Ds2FmsUDP>,
   last_packet_time: Instant,
+  connected_at: DateTime<Local>,
   wrong_station_n: usize,
+  // The Event Code and Game Data are only sent when they change
+  sent_event_code: Option<String>,
+  sent_game_data: Option<String>,
+  // Versions can arrive before the team number, so they're held here until we know who to store them against
+  versions: DriverStationVersions,
+  diagnosis: DiagnosisTracker,
+  // The last brownout state from the DS log data
+  brownout: bool,
   kv: KVConnection,
-  arena_ok: Arc<AtomicBool>
+  mq: MessageQueueChannel,
+  arena_ok: Arc<AtomicBool>,
+  diagnosis_settings: Arc<RwLock<DiagnosisSettings>>
 }
 
 impl DSConnection {
   pub async fn new(
     kv: KVConnection,
+    mq: MessageQueueChannel,
     addr: SocketAddr,
     stream: TcpStream,
     udp_rx: broadcast::Receiver<Ds2FmsUDP>,
-    arena_ok: Arc<AtomicBool>
+    arena_ok: Arc<AtomicBool>,
+    diagnosis_settings: Arc<RwLock<DiagnosisSettings>>
   ) -> DSConnection {
     let mut addr_udp = addr;
     addr_udp.set_port(1121);
@@ -63,9 +65,17 @@ impl DSConnection {
       udp_rx,
       state: DSConnectionState::Connected,
       last_packet_time: Instant::now(),
+      connected_at: Local::now(),
       wrong_station_n: 0,
+      sent_event_code: None,
+      sent_game_data: None,
+      versions: DriverStationVersions::default(),
+      diagnosis: DiagnosisTracker::default(),
+      brownout: false,
       kv,
-      arena_ok
+      mq,
+      arena_ok,
+      diagnosis_settings
     }
   }
 
@@ -147,6 +157,8 @@ impl DSConnection {
           if self.wrong_station_n >= 20 {
             self.state = DSConnectionState::Disconnected(DSDisconnectionReason::WrongMatch);
           }
+
+          self._update_connection().await;
         }
 
         // UDP Data
@@ -166,17 +178,15 @@ impl DSConnection {
           match frame {
             Some(req) => match req {
               Ok(pkt) => {
-                for tag in pkt.tags.iter() {
-                  self._process_tcp_tag(tag);
+                for tag in pkt.tags {
+                  self._process_tcp_tag(tag).await;
                 }
 
                 // TCP Update
                 let status = self._get_station_status().await;
 
                 if let Some(_team) = self.team {
-                  let mut tags = vec![];
-                  // TODO: Event Code (once implemented)
-                  // TODO: Game Data (once implemented)
+                  let mut tags = self._construct_event_tags().await;
                   tags.push(self._construct_station_tag(status).await);
 
                   self.framed_tcp.send(Fms2DsTCP{ tags }).await.unwrap(); // TODO: Handle error
@@ -197,7 +207,54 @@ impl DSConnection {
       }
     }
 
-    // Connection closed, it'll age off.
+    // Connection closed. The report will age off, but we keep a record of why we disconnected.
+    self._update_connection().await;
+  }
+
+  // Publish the state of this connection against its station. Connected records are refreshed every second so they age off
+  // if we go away, whereas disconnections stick around for a while so the FTA can see why.
+  async fn _update_connection(&self) {
+    let team = match self.team {
+      Some(team) => team,
+      None => return
+    };
+
+    let occupied = self._get_occupied_alliance_station().await.map(|s| s.id);
+    let station = match self._get_desired_alliance_station().await.map(|s| s.id).or(occupied) {
+      Some(station) => station,
+      None => return
+    };
+
+    let (connected, disconnect_reason) = match self.state {
+      DSConnectionState::Connected => (true, None),
+      DSConnectionState::Disconnected(reason) => (false, Some(reason)),
+    };
+    let address = self.addr_tcp.to_string();
+
+    if !connected {
+      // Don't clobber a DS that's since reconnected to this station
+      if let Ok(existing) = DriverStationConnection::get(&station, &self.kv) {
+        if existing.connected && existing.address != address {
+          return;
+        }
+      }
+    }
+
+    let record = DriverStationConnection {
+      station,
+      team: team as u16,
+      ip_team: self.team_by_ip().map(|t| t as u16),
+      address,
+      connected,
+      status: self._get_station_status().await,
+      occupied_station: occupied,
+      connected_at: self.connected_at,
+      disconnected_at: if connected { None } else { Some(Local::now()) },
+      disconnect_reason,
+    };
+
+    record.insert(&self.kv).ok();
+    record.expire(if connected { 5 } else { 300 }, &self.kv).ok();
   }
 
   async fn _encode_udp_update(&self, _team: usize) -> Option<Fms2DsUDP> {
@@ -223,33 +280,26 @@ impl DSConnection {
 
         let remaining_seconds = remaining.to_std().unwrap_or(Duration::from_millis(0)).as_secs_f32();
 
+        let (tournament_level, match_number, play_number) = match self._get_current_match() {
+          Some(m) => {
+            let (match_number, play_number) = ds_match_numbers(&m);
+            (TournamentLevel::from(m.match_type), match_number, play_number)
+          },
+          None => (TournamentLevel::Test, 1, 1)
+        };
+
         let pkt = Fms2DsUDP {
           estop: estop,
           enabled: (!station.bypass) && !(estop || astop) && command_enable,
           mode: command_state,
           station: station.id,
-          tournament_level: TournamentLevel::Test,      // TODO: Change this
-          match_number: 1,
-          play_number: 1,
+          tournament_level,
+          match_number,
+          play_number,
           time: Local::now(),
           remaining_seconds: f32::max(remaining_seconds, 0f32) as u16,
         };
 
-        // TODO: Later
-        // if let Some(m) = match_meta {
-        //   pkt.tournament_level = ds::TournamentLevel::from(m.match_type);
-        //   // We use the same encoding as cheesy-arena. For matches with set numbers, the match num is encoded as
-        //   // XYZ, where X = final bracket (Q=4, S=2, F=1), Y = set number, Z = match number
-        //   pkt.match_number = match m.match_type {
-        //     models::MatchType::Playoff => match m.match_subtype.unwrap() {
-        //       models::MatchSubtype::Quarterfinal => (400 + 10 * m.set_number + m.match_number) as u16,
-        //       models::MatchSubtype::Semifinal => (200 + 10 * m.set_number + m.match_number) as u16,
-        //       models::MatchSubtype::Final => (100 + 10 * m.set_number + m.match_number) as u16,
-        //     },
-        //     _ => m.match_number as u16,
-        //   };
-        // }
-
         Some(pkt)
       } else {
         None
@@ -259,7 +309,7 @@ impl DSConnection {
     }
   }
 
-  async fn _decode_udp_update(&self, pkt: Ds2FmsUDP) {
+  async fn _decode_udp_update(&mut self, pkt: Ds2FmsUDP) {
     // let mut arena = self.arena.lock().await;
     // let station_mut = arena.station_for_team_mut(self.team);
     // let mut stns = self.stations.lock().await;
@@ -276,6 +326,8 @@ impl DSConnection {
       pkts_lost: 0,
       rtt: 0,
       actual_station: self._get_occupied_alliance_station().await.map(|x| x.id),
+      brownout: self.brownout,
+      diagnosis: None,
     };
 
     for tag in pkt.tags {
@@ -289,19 +341,99 @@ impl DSConnection {
       }
     }
 
+    let wrong_station = self._get_station_status().await == Fms2DsStationStatus::Bad;
+    report.diagnosis = self.diagnosis.update(&self.diagnosis_settings.read().unwrap(), &report, wrong_station);
+
     report.insert(&self.kv).ok();
     report.expire(2, &self.kv).ok();
   }
 
-  fn _process_tcp_tag(&mut self, tag: &Ds2FmsTCPTags) {
-    match tag {
-      Ds2FmsTCPTags::TeamNumber(team) => {
-        self.team = Some(*team as usize);
+  async fn _process_tcp_tag(&mut self, tag: Ds2FmsTCPTags) {
+    let event = match (tag, self.team) {
+      (Ds2FmsTCPTags::TeamNumber(team), _) => {
+        self.team = Some(team as usize);
+        self._update_versions();
+        None
+      },
+      (Ds2FmsTCPTags::DSVersion(version), _) => {
+        self.versions.ds = Some(version);
+        self._update_versions();
+        None
+      },
+      (Ds2FmsTCPTags::RIOVersion(version), _) => {
+        self.versions.rio = Some(version);
+        self._update_versions();
+        None
+      },
+      (Ds2FmsTCPTags::WPILibVersion(version), _) => {
+        self.versions.wpilib = Some(version);
+        self._update_versions();
+        None
+      },
+      (Ds2FmsTCPTags::LogData(data), Some(team)) => {
+        self.brownout = data.brownout;
+        Some(DriverStationLogEvent::Sample { team: team as u16, data })
+      },
+      (Ds2FmsTCPTags::ErrorData(messages), Some(team)) if !messages.is_empty() => Some(DriverStationLogEvent::Messages { team: team as u16, messages }),
+      _ => None, // Other, don't worry about it for now
+    };
+
+    if let Some(event) = event {
+      if let Err(e) = self.mq.publish(DS_LOG_TOPIC, event).await {
+        warn!("Could not publish DS Log Data ({:?}): {}", self.team, e);
+      }
+    }
+  }
+
+  // Merge what we've heard on this connection into the team's record, keeping anything this DS hasn't told us yet.
+  fn _update_versions(&self) {
+    if let Some(team) = self.team {
+      let team = team as u16;
+      let existing = DriverStationVersions::get(&team, &self.kv).ok();
+      let record = DriverStationVersions {
+        team,
+        ds: self.versions.ds.clone().or(existing.as_ref().and_then(|e| e.ds.clone())),
+        rio: self.versions.rio.clone().or(existing.as_ref().and_then(|e| e.rio.clone())),
+        wpilib: self.versions.wpilib.clone().or(existing.as_ref().and_then(|e| e.wpilib.clone())),
+      };
+
+      if existing.as_ref() != Some(&record) {
+        if let Err(e) = record.insert(&self.kv) {
+          warn!("Could not store DS Versions ({}): {}", team, e);
+        }
       }
-      _ => (), // Other, don't worry about it for now
     }
   }
 
+  fn _get_current_match(&self) -> Option<Match> {
+    let loaded = self.kv.json_get::<SerialisedLoadedMatch>(ARENA_MATCH_KEY, "$").ok()?;
+    Match::get(&loaded.match_id, &self.kv).ok()
+  }
+
+  async fn _construct_event_tags(&mut self) -> Vec<Fms2DsTCPTags> {
+    let mut tags = vec![];
+    let details = EventDetails::get(&self.kv).ok();
+
+    let event_code = details.as_ref().and_then(|d| d.code.clone());
+    if event_code != self.sent_event_code {
+      if let Some(code) = &event_code {
+        tags.push(Fms2DsTCPTags::EventCode(code.clone()));
+      }
+      self.sent_event_code = event_code;
+    }
+
+    let game_data = match (details, self._get_current_match(), self._get_desired_alliance_station().await) {
+      (Some(details), Some(m), Some(stn)) => details.game.game_data(&m.id, stn.id.alliance),
+      _ => None
+    };
+    if game_data != self.sent_game_data {
+      tags.push(Fms2DsTCPTags::GameData(game_data.clone().unwrap_or_default()));
+      self.sent_game_data = game_data;
+    }
+
+    tags
+  }
+
   async fn _construct_station_tag(&self, status: Fms2DsStationStatus) -> Fms2DsTCPTags {
     let correct_station = self._get_desired_alliance_station().await.map(|x| x.id);
 
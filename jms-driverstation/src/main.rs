@@ -1,27 +1,25 @@
-use std::{time::Duration, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+use std::{time::Duration, sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}}};
 
 use futures::StreamExt;
-use jms_base::{kv::KVConnection, logging::JMSLogger};
-use jms_core_lib::{models::JmsComponent, db::Table};
+use jms_base::{kv::KVConnection, logging::JMSLogger, mq::MessageQueue};
+use jms_core_lib::{models::JmsComponent, db::{Singleton, Table}};
 use log::{info, error};
 use tokio::{sync::broadcast, net::{TcpListener, UdpSocket}, try_join};
 use tokio_util::udp::UdpFramed;
-use udp_codec::{Ds2FmsUDP, DSUDPCodec};
+use jms_driverstation_lib::{diagnosis::DiagnosisSettings, udp_codec::{Ds2FmsUDP, DSUDPCodec}};
 
 use crate::connector::DSConnection;
 
 pub mod connector;
-pub mod tcp_codec;
-pub mod udp_codec;
 
-async fn tcp(kv: KVConnection, udp_tx: &broadcast::Sender<Ds2FmsUDP>, arena_ok: Arc<AtomicBool>) -> anyhow::Result<()> {
+async fn tcp(kv: KVConnection, mq: MessageQueue, udp_tx: &broadcast::Sender<Ds2FmsUDP>, arena_ok: Arc<AtomicBool>, diagnosis_settings: Arc<RwLock<DiagnosisSettings>>) -> anyhow::Result<()> {
   let server = TcpListener::bind("0.0.0.0:1750").await?;
   loop {
     info!("Listening for connections...");
     let (stream, addr) = server.accept().await?;
     info!("Connected: {}", addr);
 
-    let mut conn = DSConnection::new(kv.clone()?, addr, stream, udp_tx.subscribe(), arena_ok.clone()).await;
+    let mut conn = DSConnection::new(kv.clone()?, mq.channel().await?, addr, stream, udp_tx.subscribe(), arena_ok.clone(), diagnosis_settings.clone()).await;
     tokio::spawn(async move {
       conn.process().await;
       info!(
@@ -78,23 +76,35 @@ async fn arena_ok_worker(kv: KVConnection, ok: Arc<AtomicBool>) -> anyhow::Resul
   }
 }
 
+// The diagnosis is worked out for every DS packet, so the settings are cached here rather than read each time
+async fn diagnosis_settings_worker(kv: KVConnection, settings: Arc<RwLock<DiagnosisSettings>>) -> anyhow::Result<()> {
+  let mut interval = tokio::time::interval(Duration::from_millis(1000));
+  loop {
+    interval.tick().await;
+    *settings.write().unwrap() = DiagnosisSettings::get(&kv)?;
+  }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
   let _ = JMSLogger::init().await?;
   let kv = KVConnection::new()?;
+  let mq = MessageQueue::new("jms.driverstation-reply").await?;
 
   let component = JmsComponent::new("jms.driverstation", "JMS-DriverStation", "D", 500);
   component.insert(&kv)?;
 
   let arena_ok = Arc::new(AtomicBool::new(false));
+  let diagnosis_settings = Arc::new(RwLock::new(DiagnosisSettings::default()));
 
   let (udp_tx, _) = broadcast::channel(16);
 
-  let fut_tcp = tcp(kv.clone()?, &udp_tx, arena_ok.clone());
+  let fut_tcp = tcp(kv.clone()?, mq, &udp_tx, arena_ok.clone(), diagnosis_settings.clone());
   let fut_udp = udp_recv(&udp_tx);
   let component_fut = run_component(kv.clone()?, component);
-  let arena_ok_fut = arena_ok_worker(kv, arena_ok.clone());
-  try_join!(fut_tcp, fut_udp, component_fut, arena_ok_fut)?;
+  let arena_ok_fut = arena_ok_worker(kv.clone()?, arena_ok.clone());
+  let diagnosis_fut = diagnosis_settings_worker(kv, diagnosis_settings.clone());
+  try_join!(fut_tcp, fut_udp, component_fut, arena_ok_fut, diagnosis_fut)?;
 
   Ok(())
 }
@@ -45,12 +45,15 @@ pub enum EstopMode {
 #[derive(jms_macros::Updateable)]
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct FieldElectronicsSettings {
-  pub estop_mode: EstopMode
+  pub estop_mode: EstopMode,
+  // Stations have a dedicated A-Stop button on IO 1. Without one, the E-Stop button acts as an A-Stop during Auto.
+  #[serde(default)]
+  pub astop_button: bool,
 }
 
 impl Default for FieldElectronicsSettings {
   fn default() -> Self {
-    Self { estop_mode: EstopMode::NormallyClosed }
+    Self { estop_mode: EstopMode::NormallyClosed, astop_button: false }
   }
 }
 
@@ -1,13 +1,13 @@
-use std::{borrow::Cow, collections::HashMap, net::{IpAddr, Ipv4Addr}, time::Duration};
+use std::{borrow::Cow, collections::{HashMap, HashSet}, net::{IpAddr, Ipv4Addr}, time::{Duration, Instant}};
 
 use binmarshal::AsymmetricCow;
 use grapple_frc_msgs::grapple::{jms::{Colour, JMSCardUpdate, JMSElectronicsUpdate, JMSMessage, JMSRole, Pattern}, misc::MiscMessage, GrappleDeviceMessage, TaggedGrappleMessage};
-use jms_arena_lib::{AllianceStation, ArenaEntryCondition, ArenaRPCClient, ArenaState, SerialisedLoadedMatch, ARENA_MATCH_KEY, ARENA_STATE_KEY};
+use jms_arena_lib::{AllianceStation, ArenaAuditEntry, ArenaAuditEvent, ArenaEntryCondition, ArenaRPCClient, ArenaState, MatchPlayState, SerialisedLoadedMatch, StopButtonFault, ARENA_MATCH_KEY, ARENA_STATE_KEY};
 use jms_base::{kv, mq::{self, MessageQueueChannel}};
-use jms_core_lib::{db::{Singleton, Table}, models::Alliance, scoring::scores::{MatchScore, ScoringConfig}};
+use jms_core_lib::{db::{Singleton, Table}, models::{Alliance, AllianceStationId}, scoring::scores::{MatchScore, ScoringConfig}};
 use jms_driverstation_lib::DriverStationReport;
 use jms_electronics_lib::{EstopMode, FieldElectronicsEndpoint, FieldElectronicsServiceRPC, FieldElectronicsSettings, FieldElectronicsUpdate};
-use log::warn;
+use log::{info, warn};
 use pnet::datalink;
 
 use crate::network::JMSElectronicsL2Framed;
@@ -19,6 +19,42 @@ pub fn get_jms_admin_interface() -> datalink::NetworkInterface {
           .unwrap()
 }
 
+const ESTOP_IO: usize = 0;
+const ASTOP_IO: usize = 1;
+// Number of consecutive status frames a stop input must be pressed for before it counts
+const STOP_DEBOUNCE: usize = 20;
+// How long a stop input can read as open from power-up before we flag it - a cut wire in Normally Closed mode never closes
+const STOP_FAULT_TIME: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct StopInput {
+  counter: usize,
+  since: Option<Instant>,
+  // Whether the circuit has ever read as released since the electronics came online
+  released: bool,
+}
+
+impl StopInput {
+  // Returns true for as long as a press is held past the debounce
+  fn update(&mut self, pressed: bool) -> bool {
+    if pressed {
+      self.counter = self.counter.saturating_add(1);
+      self.since.get_or_insert_with(Instant::now);
+      self.counter >= STOP_DEBOUNCE
+    } else {
+      self.counter = 0;
+      self.since = None;
+      self.released = true;
+      false
+    }
+  }
+
+  // A button that's pressed and held has been seen released first. One that's read as pressed since power-up is wiring.
+  fn circuit_open(&self) -> bool {
+    !self.released && self.since.map(|s| s.elapsed() > STOP_FAULT_TIME).unwrap_or(false)
+  }
+}
+
 pub struct JMSElectronics {
   kv: kv::KVConnection,
   mq: mq::MessageQueueChannel,
@@ -41,7 +77,8 @@ impl JMSElectronics {
     let mut entry_condition = ArenaEntryCondition::get(&self.kv)?;
     let mut current_match: Option<SerialisedLoadedMatch> = self.kv.json_get(ARENA_MATCH_KEY, "$").ok();
 
-    let mut estop_counter = HashMap::new();
+    let mut estop_counter: HashMap<(String, usize), StopInput> = HashMap::new();
+    let mut seen_stations = HashSet::new();
     
     // let mut arena_is_estopped = false;
     let mut arena_state = self.kv.json_get::<ArenaState>(ARENA_STATE_KEY, "$")?;
@@ -61,6 +98,20 @@ impl JMSElectronics {
           arena_state = self.kv.json_get::<ArenaState>(ARENA_STATE_KEY, "$")?;
           entry_condition = ArenaEntryCondition::get(&self.kv)?;
           current_match = self.kv.json_get(ARENA_MATCH_KEY, "$").ok();
+
+          // Forget inputs on electronics that have dropped off, so their wiring is checked again when they come back
+          estop_counter.retain(|(mac, _), _| endpoints.iter().any(|ep| &ep.mac == mac));
+
+          // Stations whose electronics have dropped off can't be stopped from the field, so flag them
+          for stn in stations.iter_mut().filter(|s| seen_stations.contains(&s.id)) {
+            let role = match stn.id.alliance {
+              Alliance::Blue => JMSRole::Blue(stn.id.station as u8),
+              Alliance::Red => JMSRole::Red(stn.id.station as u8),
+            };
+            if !endpoints.iter().any(|ep| ep.status.role == role) && stn.stop_fault != Some(StopButtonFault::Offline) {
+              self.set_stop_fault(stn, Some(StopButtonFault::Offline))?;
+            }
+          }
         },
         _ = lighting_update.tick() => {
           tick_n = tick_n.wrapping_add(1);
@@ -72,9 +123,10 @@ impl JMSElectronics {
               Alliance::Red => ( &score.red, &score.blue, Colour::new(255, 0, 0), Colour::new(5, 0, 0), JMSRole::Red(station.id.station as u8) ),
             };
 
-            let score_derived = team_score.derive(&other_score, config);
+            let indicators = team_score.derive(&other_score, &config)?.indicators();
+            let other_indicators = other_score.derive(&team_score, &config)?.indicators();
 
-            let top_bar = match (team_score.coop, other_score.coop, arena_state) {
+            let top_bar = match (indicators.coopertition, other_indicators.coopertition, arena_state) {
               (_, _, ArenaState::Estop) => Pattern::Blank,
               (true, true, _) => Pattern::Solid(Colour::new(255, 120, 0)),
               (true, false, _) => Pattern::FillLeft(Colour::new(255, 255, 0), Colour::new(5, 5, 0), 128),
@@ -93,30 +145,32 @@ impl JMSElectronics {
               },
               (_, true, _, _) => (Pattern::DiagonalStripes(Colour::new(255, 0, 0), Colour::new(5, 0, 0)), Pattern::Blank, "ESTOP".to_owned(), Colour::new(255, 0, 0)),
               (true, _, _, _) => (Pattern::DiagonalStripes(Colour::new(255, 80, 0), Colour::new(5, 5, 0)), Pattern::Blank, "ASTOP".to_owned(), Colour::new(255, 80, 0)),
-              (_, _, Some(report), _) if report.diagnosis() != None => {
-                (Pattern::Solid(Colour::new(255, 80, 0)), Pattern::Blank, report.diagnosis().unwrap().to_owned(), Colour::new(255, 0, 0))
+              (_, _, Some(DriverStationReport { diagnosis: Some(diagnosis), .. }), _) => {
+                (Pattern::Solid(Colour::new(255, 80, 0)), Pattern::Blank, diagnosis.code().to_owned(), Colour::new(255, 0, 0))
               },
               (_, _, _, ArenaState::MatchPlay) => {
-                if let Some(secs) = score_derived.notes.amplified_remaining.as_ref().map(|x| x.0.num_seconds()) {
+                if let Some(secs) = indicators.bonus_remaining.as_ref().map(|x| x.0.num_seconds()) {
                   (
                     Pattern::Blank,
                     Pattern::FillLeft(primary_colour.clone(), secondary_colour.clone(), (255 / 10) * secs as u8),
                     "".to_owned(),
                     Colour::new(0, 0, 0)
                   )
-                } else {
+                } else if let Some(progress) = indicators.progress.as_ref() {
                   (
                     Pattern::Blank,
                     Pattern::Blank,
-                    format!("{}/{}", score_derived.notes.total_count, score_derived.melody_threshold),
-                    if score_derived.melody_rp { Colour::new(0, 255, 0) } else { Colour::new(255, 40, 0) }
+                    format!("{}/{}", progress.current, progress.target),
+                    if progress.achieved { Colour::new(0, 255, 0) } else { Colour::new(255, 40, 0) }
                   )
+                } else {
+                  (Pattern::Blank, Pattern::Blank, "".to_owned(), Colour::new(0, 0, 0))
                 }
               },
               _ => (Pattern::Blank, Pattern::Blank, station.team.map(|team| format!("{}", team)).unwrap_or("----".to_owned()), Colour::new(0, 255, 0))
             };
 
-            let (background, text_colour) = match (arena_state, score_derived.notes.amplified_remaining, entry_condition.clone()) {
+            let (background, text_colour) = match (arena_state, indicators.bonus_remaining, entry_condition.clone()) {
               (ArenaState::Estop, _, _) => {
                 if tick_n % 4 < 2 {
                   (
@@ -233,60 +287,50 @@ impl JMSElectronics {
                   grapple_frc_msgs::grapple::jms::JMSCardStatus::IO(io) => {
                     match ep.status.role {
                       JMSRole::ScoringTable => {
-                        let estop_state = invert ^ io[0];
-                        if let Some(counter) = estop_counter.get_mut(&endpoint.to_string()) {
-                          if estop_state {
-                            *counter += 1;
-                            if *counter > 20 && arena_state != ArenaState::Estop {
-                              match ArenaRPCClient::signal(&self.mq, jms_arena_lib::ArenaSignal::Estop, "Field Electronics (Scoring Table)".to_string()).await? {
-                                Ok(()) => (),
-                                Err(e) => warn!("Field Electronics - Signal Error: {}", e)
-                              }
-                            }
-                          } else {
-                            *counter = 0;
+                        let estop = estop_counter.entry((ep.mac.clone(), ESTOP_IO)).or_default();
+                        if estop.update(invert ^ io[ESTOP_IO]) && arena_state != ArenaState::Estop {
+                          match ArenaRPCClient::signal(&self.mq, jms_arena_lib::ArenaSignal::Estop, "Field Electronics (Scoring Table)".to_string()).await? {
+                            Ok(()) => (),
+                            Err(e) => warn!("Field Electronics - Signal Error: {}", e)
                           }
-                        } else {
-                          estop_counter.insert(endpoint.to_string(), 0);
                         }
                       },
-                      JMSRole::Red(stn) => {
-                        if let Some(stn) = stations.iter_mut().find(|x| x.id.alliance == Alliance::Red && x.id.station == stn as usize) {
-                          let estop_state = invert ^ io[0];
-                          if let Some(counter) = estop_counter.get_mut(&endpoint.to_string()) {
-                            if estop_state {
-                              *counter += 1;
-                              if *counter > 20 && !stn.physical_estop {
-                                stn.set_physical_estop(true, &self.kv)?;
-                              }
-                            } else {
-                              *counter = 0;
-                              if stn.physical_estop {
-                                stn.set_physical_estop(false, &self.kv)?;
-                              }
+                      JMSRole::Red(stn) | JMSRole::Blue(stn) => {
+                        let id = AllianceStationId {
+                          alliance: if matches!(ep.status.role, JMSRole::Red(_)) { Alliance::Red } else { Alliance::Blue },
+                          station: stn as usize
+                        };
+                        if let Some(stn) = stations.iter_mut().find(|x| x.id == id) {
+                          seen_stations.insert(id);
+                          let in_auto = current_match.as_ref().map(|m| m.state == MatchPlayState::Auto).unwrap_or(false);
+
+                          let estop = estop_counter.entry((ep.mac.clone(), ESTOP_IO)).or_default();
+                          let estop_pressed = estop.update(invert ^ io[ESTOP_IO]);
+                          let estop_open = estop.circuit_open();
+
+                          let astop = estop_counter.entry((ep.mac.clone(), ASTOP_IO)).or_default();
+                          let astop_pressed = settings.astop_button && astop.update(invert ^ io[ASTOP_IO]);
+                          let astop_open = settings.astop_button && astop.circuit_open();
+
+                          // Re-assert held stops every frame, so one that's still held when the FTA resets latches again
+                          if arena_state != ArenaState::Estop {
+                            if estop_pressed && !settings.astop_button && in_auto {
+                              self.physical_stop(stn, true)?;
+                            } else if estop_pressed {
+                              self.physical_stop(stn, false)?;
                             }
-                          } else {
-                            estop_counter.insert(endpoint.to_string(), 0);
-                          }
-                        }
-                      },
-                      JMSRole::Blue(stn) => {
-                        if let Some(stn) = stations.iter_mut().find(|x| x.id.alliance == Alliance::Blue && x.id.station == stn as usize) {
-                          let estop_state = invert ^ io[0];
-                          if let Some(counter) = estop_counter.get_mut(&endpoint.to_string()) {
-                            if estop_state {
-                              *counter += 1;
-                              if *counter > 20 && !stn.physical_estop {
-                                stn.set_physical_estop(true, &self.kv)?;
-                              }
-                            } else {
-                              *counter = 0;
-                              if stn.physical_estop {
-                                stn.set_physical_estop(false, &self.kv)?;
-                              }
+                            if astop_pressed && in_auto {
+                              self.physical_stop(stn, true)?;
                             }
-                          } else {
-                            estop_counter.insert(endpoint.to_string(), 0);
+                          }
+
+                          let fault = match (estop_open, astop_open) {
+                            (true, _) => Some(StopButtonFault::EstopCircuitOpen),
+                            (_, true) => Some(StopButtonFault::AstopCircuitOpen),
+                            _ => None
+                          };
+                          if fault != stn.stop_fault {
+                            self.set_stop_fault(stn, fault)?;
                           }
                         }
                       },
@@ -308,6 +352,29 @@ impl JMSElectronics {
   }
 }
 
+impl JMSElectronics {
+  // Latch a physical stop button press. The arena turns this into an E-Stop or A-Stop for the station.
+  fn physical_stop(&self, stn: &mut AllianceStation, astop: bool) -> anyhow::Result<()> {
+    match astop {
+      true if !stn.physical_astop => stn.set_physical_astop(true, &self.kv)?,
+      false if !stn.physical_estop => stn.set_physical_estop(true, &self.kv)?,
+      _ => return Ok(())
+    }
+    ArenaAuditEntry::record_current("Field Electronics".to_owned(), ArenaAuditEvent::PhysicalStop { station: stn.id, astop }, &self.kv)?;
+    Ok(())
+  }
+
+  fn set_stop_fault(&self, stn: &mut AllianceStation, fault: Option<StopButtonFault>) -> anyhow::Result<()> {
+    match fault {
+      Some(f) => warn!("{}: {}", stn.id.to_string(), f),
+      None => info!("{}: Stop fault cleared", stn.id.to_string()),
+    }
+    stn.set_stop_fault(fault, &self.kv)?;
+    ArenaAuditEntry::record_current("Field Electronics".to_owned(), ArenaAuditEvent::StopFault { station: stn.id, fault }, &self.kv)?;
+    Ok(())
+  }
+}
+
 /* TODO: We can move this into JMSElectronics and use tokio::select! on the rpc.next() option to keep
   mutability requirements.  */
 pub struct JMSElectronicsService {
@@ -365,7 +432,9 @@ impl FieldElectronicsServiceRPC for JMSElectronicsService {
   async fn reset_estops(&mut self) -> Result<(), String> {
     for mut stn in AllianceStation::all(&self.kv).map_err(|e| e.to_string())? {
       stn.set_physical_estop(false, &self.kv).map_err(|e| e.to_string())?;
+      stn.set_physical_astop(false, &self.kv).map_err(|e| e.to_string())?;
     }
+    ArenaAuditEntry::record_current("Field Electronics".to_owned(), ArenaAuditEvent::PhysicalStopReset, &self.kv).map_err(|e| e.to_string())?;
     Ok(())
   }
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use jms_driverstation_lib::diagnosis::{DiagnosisCode, DiagnosisSettings};
+
+use crate::{in_match, MatchLog, TimeseriesDsReportEntry};
+
+// Why a robot wasn't running. Each report sample is put down to one cause, in the same order as the FTA diagnosis,
+// so time without a radio isn't counted again as time without a RIO or code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum LossCause {
+  NoDriverStation,
+  Radio,
+  Rio,
+  Code,
+  HighLatency,
+  LowBattery,
+  Brownout,
+}
+
+impl LossCause {
+  pub const ALL: [LossCause; 7] = [
+    LossCause::NoDriverStation, LossCause::Radio, LossCause::Rio, LossCause::Code,
+    LossCause::HighLatency, LossCause::LowBattery, LossCause::Brownout
+  ];
+
+  // Total time (in ms) before a cause is worth a ticket. Brownouts are always worth a look.
+  pub fn threshold(&self) -> usize {
+    match self {
+      LossCause::NoDriverStation | LossCause::Radio | LossCause::Rio | LossCause::Code => 2000,
+      LossCause::HighLatency | LossCause::LowBattery => 5000,
+      LossCause::Brownout => 1,
+    }
+  }
+
+  // The CSA ticket issue type this cause is filed under
+  pub fn issue_type(&self) -> &'static str {
+    match self {
+      LossCause::NoDriverStation => "LAPTOP",
+      LossCause::Radio | LossCause::HighLatency => "RADIO",
+      LossCause::Rio => "ROBORIO",
+      LossCause::Code => "CODE",
+      LossCause::LowBattery | LossCause::Brownout => "POWER",
+    }
+  }
+
+  // Latency and battery use the same thresholds as the FTA diagnosis, and aren't counted if their rule is turned off
+  fn of(entry: &TimeseriesDsReportEntry, settings: &DiagnosisSettings) -> Option<LossCause> {
+    let triggered = |code: DiagnosisCode| {
+      entry.report.as_ref().is_some_and(|r| settings.rules.iter().any(|rule| rule.enabled && rule.code == code && rule.triggered(r, false, 0.0)))
+    };
+
+    match &entry.report {
+      None => Some(LossCause::NoDriverStation),
+      Some(r) if !r.radio_ping => Some(LossCause::Radio),
+      Some(r) if !r.rio_ping => Some(LossCause::Rio),
+      Some(r) if !r.robot_ping => Some(LossCause::Code),
+      Some(_) if triggered(DiagnosisCode::HighLatency) => Some(LossCause::HighLatency),
+      Some(_) if triggered(DiagnosisCode::LowBattery) => Some(LossCause::LowBattery),
+      Some(_) => None
+    }
+  }
+}
+
+impl std::fmt::Display for LossCause {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LossCause::NoDriverStation => write!(f, "Driver Station disconnected"),
+      LossCause::Radio => write!(f, "Radio lost"),
+      LossCause::Rio => write!(f, "RoboRIO lost"),
+      LossCause::Code => write!(f, "Robot code lost"),
+      LossCause::HighLatency => write!(f, "High latency"),
+      LossCause::LowBattery => write!(f, "Low battery"),
+      LossCause::Brownout => write!(f, "Brownout"),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct LossWindow {
+  pub cause: LossCause,
+  pub start: i64,       // In ms
+  pub end: i64,         // In ms
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct LossSummary {
+  pub cause: LossCause,
+  pub total: usize,     // In ms
+  pub windows: usize,
+  pub exceeds_threshold: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct MatchLogAnalysis {
+  pub windows: Vec<LossWindow>,
+  pub summary: Vec<LossSummary>,
+}
+
+impl MatchLogAnalysis {
+  pub fn analyse(log: &MatchLog, settings: &DiagnosisSettings) -> Self {
+    let mut windows = vec![];
+
+    // Each sample lasts until the next one. The last sample gets the usual sample period.
+    // Robots aren't expected to be running before or after the match, so that's left out.
+    let reports = log.timeseries.iter().filter(|e| in_match(e.phase)).map(|e| (e.time, LossCause::of(e, settings)));
+    push_windows(&mut windows, reports, 250);
+
+    let brownouts = log.log_data.iter().filter(|e| in_match(e.phase)).map(|e| (e.time, e.data.brownout.then_some(LossCause::Brownout)));
+    push_windows(&mut windows, brownouts, 1000);
+
+    let summary = LossCause::ALL.iter().filter_map(|&cause| {
+      let matching = windows.iter().filter(|w| w.cause == cause);
+      let total: usize = matching.clone().map(|w| (w.end - w.start).max(0) as usize).sum();
+      match total {
+        0 => None,
+        total => Some(LossSummary { cause, total, windows: matching.count(), exceeds_threshold: total >= cause.threshold() })
+      }
+    }).collect();
+
+    Self { windows, summary }
+  }
+
+  pub fn exceeded(&self) -> Vec<&LossSummary> {
+    self.summary.iter().filter(|s| s.exceeds_threshold).collect()
+  }
+}
+
+fn push_windows<I: Iterator<Item = (i64, Option<LossCause>)>>(windows: &mut Vec<LossWindow>, samples: I, period: i64) {
+  let samples: Vec<(i64, Option<LossCause>)> = samples.collect();
+  let mut current: Option<LossWindow> = None;
+
+  for (i, (time, cause)) in samples.iter().enumerate() {
+    let end = samples.get(i + 1).map(|(t, _)| *t).unwrap_or(time + period).max(*time);
+
+    match (&mut current, cause) {
+      (Some(w), Some(c)) if w.cause == *c => w.end = end,
+      (_, cause) => {
+        windows.extend(current.take());
+        current = cause.map(|cause| LossWindow { cause, start: *time, end });
+      }
+    }
+  }
+
+  windows.extend(current);
+}
@@ -0,0 +1,114 @@
+use chrono::{DateTime, Duration, TimeZone};
+use jms_driverstation_lib::{DriverStationReport, RobotState, tcp_codec::DSLogData};
+
+use crate::MatchLog;
+
+// Seconds between the LabVIEW epoch (1904) and the unix epoch
+const LABVIEW_EPOCH_OFFSET: i64 = 2_082_844_800;
+const DSLOG_VERSION: i32 = 3;
+const DSLOG_PERIOD: usize = 20;
+
+impl MatchLog {
+  // Render the log as a .dslog, the same as the Driver Station writes, so teams can open it in the DS Log Viewer.
+  // The DS logs every 20ms, so each of our samples is held until the next. We use the DS log data if we have it,
+  // otherwise the control packet reports. `start` is the start of the match, but the file starts with the first entry.
+  pub fn to_dslog<Tz: TimeZone>(&self, start: DateTime<Tz>) -> Vec<u8> {
+    let samples: Vec<(i64, DSLogData)> = match self.log_data.is_empty() {
+      false => self.log_data.iter().map(|e| (e.time, e.data.clone())).collect(),
+      true => self.timeseries.iter().map(|e| (e.time, e.report.as_ref().map(report_log_data).unwrap_or_default())).collect(),
+    };
+
+    let times = samples.iter().map(|(t, _)| *t).chain(self.timeseries.iter().map(|e| e.time));
+    let (first, end) = (times.clone().min().unwrap_or(0), times.max().unwrap_or(0));
+    let start = start + Duration::milliseconds(first);
+
+    let mut out = vec![];
+    out.extend(DSLOG_VERSION.to_be_bytes());
+    out.extend((start.timestamp() + LABVIEW_EPOCH_OFFSET).to_be_bytes());
+    out.extend(((((start.timestamp_subsec_nanos() as u128) << 64) / 1_000_000_000) as u64).to_be_bytes());
+
+    let mut current = DSLogData::default();
+    let mut next = samples.iter().peekable();
+
+    for t in (first..=end).step_by(DSLOG_PERIOD) {
+      while let Some((_, data)) = next.next_if(|(time, _)| *time <= t) {
+        current = data.clone();
+      }
+      write_dslog_entry(&mut out, &current);
+    }
+
+    out
+  }
+
+  // One row per report sample, alongside the latest DS log data and any robot messages since the last row.
+  pub fn to_csv(&self) -> String {
+    let mut out = "time,phase,station_estop,station_astop,ds,radio,rio,code,estop,mode,battery,rtt,pkts_lost,pkts_sent,brownout,watchdog,rio_cpu,can_usage,wifi_db,bandwidth,messages\n".to_owned();
+
+    let mut log_data = self.log_data.iter().peekable();
+    let mut messages = self.messages.iter().peekable();
+    let mut current: Option<&DSLogData> = None;
+
+    for (i, entry) in self.timeseries.iter().enumerate() {
+      let last = i == self.timeseries.len() - 1;
+      while let Some(e) = log_data.next_if(|e| e.time <= entry.time) {
+        current = Some(&e.data);
+      }
+
+      let mut msgs = vec![];
+      while let Some(m) = messages.next_if(|m| last || m.time <= entry.time) {
+        msgs.push(m.message.message.clone());
+      }
+
+      let report = match &entry.report {
+        Some(r) => format!(
+          "true,{},{},{},{},{:?},{:.2},{},{},{}",
+          r.radio_ping, r.rio_ping, r.robot_ping, r.estop, r.mode, r.battery_voltage, r.rtt, r.pkts_lost, r.pkts_sent
+        ),
+        None => "false,,,,,,,,,".to_owned()
+      };
+
+      let data = match current {
+        Some(d) => format!("{},{},{:.1},{:.1},{:.1},{:.2}", d.brownout, d.watchdog, d.rio_cpu, d.can_usage, d.wifi_db, d.bandwidth),
+        None => ",,,,,".to_owned()
+      };
+
+      let phase = entry.phase.map(|p| format!("{:?}", p)).unwrap_or_default();
+      out += &format!("{:.3},{},{},{},{},{},\"{}\"\n", entry.time as f64 / 1000.0, phase, entry.estop, entry.astop, report, data, msgs.join(" | ").replace('"', "\"\""));
+    }
+
+    out
+  }
+}
+
+fn report_log_data(report: &DriverStationReport) -> DSLogData {
+  let total = (report.pkts_sent as f64) + (report.pkts_lost as f64);
+  DSLogData {
+    rtt: report.rtt as f64,
+    lost_percent: if total > 0.0 { (report.pkts_lost as f64) * 100.0 / total } else { 0.0 },
+    battery: report.battery_voltage,
+    ds_teleop: report.mode == RobotState::Teleop,
+    ds_auto: report.mode == RobotState::Auto,
+    robot_teleop: report.robot_ping && report.mode == RobotState::Teleop,
+    robot_auto: report.robot_ping && report.mode == RobotState::Auto,
+    robot_disable: !report.robot_ping,
+    ..Default::default()
+  }
+}
+
+fn write_dslog_entry(out: &mut Vec<u8>, d: &DSLogData) {
+  out.push((d.rtt * 2.0) as u8);
+  out.push((d.lost_percent / 4.0) as u8);
+  out.extend(((d.battery * 256.0) as u16).to_be_bytes());
+  out.push((d.rio_cpu * 2.0) as u8);
+
+  // Status flags are inverted, MSB first
+  let flags = [d.brownout, d.watchdog, d.ds_teleop, d.ds_auto, d.ds_disable, d.robot_teleop, d.robot_auto, d.robot_disable];
+  out.push(!flags.iter().fold(0u8, |acc, f| (acc << 1) | (*f as u8)));
+
+  out.push((d.can_usage * 2.0) as u8);
+  out.push((d.wifi_db * 2.0) as u8);
+  out.extend(((d.bandwidth * 256.0) as u16).to_be_bytes());
+
+  // PDP ID, currents and the trailing resistance / voltage / temperature bytes. We don't get these from the DS.
+  out.extend([0u8; 25]);
+}
@@ -1,19 +1,97 @@
+pub mod analysis;
+pub mod export;
+
 use std::convert::Infallible;
 
+use jms_arena_lib::MatchPlayState;
 use jms_core_lib::db::Table;
-use jms_driverstation_lib::DriverStationReport;
+use jms_driverstation_lib::{DriverStationReport, tcp_codec::{DSLogData, TimestampedMessage}};
 
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct MatchLog {
   pub team: usize,
   pub match_id: String,
-  pub timeseries: Vec<TimeseriesDsReportEntry>
+  pub timeseries: Vec<TimeseriesDsReportEntry>,
+  // Log data and robot messages sent by the DS over TCP
+  #[serde(default)]
+  pub log_data: Vec<TimeseriesDsLogEntry>,
+  #[serde(default)]
+  pub messages: Vec<TimeseriesDsMessageEntry>,
+  // Filled in once the match is committed
+  #[serde(default)]
+  pub analysis: Option<analysis::MatchLogAnalysis>,
+  // When capture started, while times are still relative to Prestart. Cleared once they're moved to the start of Auto.
+  #[serde(default)]
+  pub capture_start: Option<chrono::DateTime<chrono::Local>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub enum MatchLogFormat {
+  DSLog,
+  CSV
+}
+
+impl MatchLogFormat {
+  pub fn extension(&self) -> &'static str {
+    match self {
+      MatchLogFormat::DSLog => "dslog",
+      MatchLogFormat::CSV => "csv",
+    }
+  }
+}
+
+impl MatchLog {
+  pub fn new(match_id: String, team: usize) -> Self {
+    Self { team, match_id, timeseries: vec![], log_data: vec![], messages: vec![], analysis: None, capture_start: None }
+  }
+
+  pub fn shift(&mut self, offset: i64) {
+    self.timeseries.iter_mut().for_each(|e| e.time += offset);
+    self.log_data.iter_mut().for_each(|e| e.time += offset);
+    self.messages.iter_mut().for_each(|e| e.time += offset);
+  }
+
+  // Whether the match actually got underway while we were logging
+  pub fn played(&self) -> bool {
+    self.timeseries.iter().any(|e| e.phase.is_some_and(|p| p != MatchPlayState::Waiting && p != MatchPlayState::Warmup))
+  }
 }
 
+// Times are in ms from the start of Auto, so anything logged between Prestart and the start of the match is negative.
+// Older logs only covered Auto to Teleop, and don't have a phase.
+
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct TimeseriesDsReportEntry {
-  pub time: usize,      // In ms
-  pub report: Option<DriverStationReport>
+  pub time: i64,
+  #[serde(default)]
+  pub phase: Option<MatchPlayState>,
+  pub report: Option<DriverStationReport>,
+  // The station's stop state, as opposed to the robot's own (report.estop)
+  #[serde(default)]
+  pub estop: bool,
+  #[serde(default)]
+  pub astop: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct TimeseriesDsLogEntry {
+  pub time: i64,
+  #[serde(default)]
+  pub phase: Option<MatchPlayState>,
+  pub data: DSLogData
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct TimeseriesDsMessageEntry {
+  pub time: i64,
+  #[serde(default)]
+  pub phase: Option<MatchPlayState>,
+  pub message: TimestampedMessage
+}
+
+// The part of the match where the robot is meant to be running
+pub fn in_match(phase: Option<MatchPlayState>) -> bool {
+  matches!(phase, None | Some(MatchPlayState::Auto) | Some(MatchPlayState::Pause) | Some(MatchPlayState::Teleop))
 }
 
 impl Table for MatchLog {
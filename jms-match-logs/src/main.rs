@@ -1,15 +1,15 @@
-use std::collections::{HashMap, hash_map::Entry};
-
-use jms_arena_lib::{SerialisedLoadedMatch, ARENA_MATCH_KEY, MatchPlayState};
-use jms_base::{kv, logging::JMSLogger};
-use jms_core_lib::{models::JmsComponent, db::Table};
-use jms_driverstation_lib::DriverStationReport;
-use jms_match_logs_lib::{MatchLog, TimeseriesDsReportEntry};
-use log::info;
+use std::{collections::{HashMap, hash_map::Entry}, time::{Duration, Instant}};
+
+use jms_arena_lib::{AllianceStation, ArenaState, SerialisedLoadedMatch, ARENA_MATCH_KEY, ARENA_STATE_KEY};
+use jms_base::{kv, logging::JMSLogger, mq};
+use jms_core_lib::{models::{JmsComponent, SupportTicket, TicketComment}, db::{Singleton, Table}};
+use jms_driverstation_lib::{diagnosis::DiagnosisSettings, DriverStationLogEvent, DriverStationReport, DS_LOG_TOPIC};
+use jms_match_logs_lib::{analysis::MatchLogAnalysis, MatchLog, TimeseriesDsLogEntry, TimeseriesDsMessageEntry, TimeseriesDsReportEntry};
+use log::{info, warn};
 use tokio::try_join;
 
 async fn component_svc(kv: kv::KVConnection) -> anyhow::Result<()> {
-  let mut interval = tokio::time::interval(std::time::Duration::from_millis(500));
+  let mut interval = tokio::time::interval(Duration::from_millis(500));
   let mut component = JmsComponent::new("jms.match-logs", "JMS-Match-Logs", "L", 1000);
 
   component.insert(&kv)?;
@@ -20,58 +20,206 @@ async fn component_svc(kv: kv::KVConnection) -> anyhow::Result<()> {
   }
 }
 
-async fn logs_svc(kv: kv::KVConnection) -> anyhow::Result<()> {
-  let mut interval = tokio::time::interval(std::time::Duration::from_millis(250));
+// Open a CSA ticket for teams that lost too much of the match, or add to the ticket that's already open for it.
+fn raise_tickets(log: &MatchLog, kv: &kv::KVConnection) -> anyhow::Result<()> {
+  let exceeded = match &log.analysis {
+    Some(analysis) => analysis.exceeded(),
+    None => return Ok(())
+  };
+  let worst = match exceeded.iter().max_by_key(|s| s.total) {
+    Some(worst) => worst,
+    None => return Ok(())
+  };
+
+  let comment = format!(
+    "Match Log Analysis: {}",
+    exceeded.iter().map(|s| format!("{} for {:.1}s ({}x)", s.cause, s.total as f64 / 1000.0, s.windows)).collect::<Vec<_>>().join(", ")
+  );
+
+  let existing = SupportTicket::all(kv)?.into_iter().find(|t| t.team == log.team && t.match_id.as_ref() == Some(&log.match_id) && !t.resolved);
+  let mut ticket = existing.unwrap_or_else(|| SupportTicket::new(log.team, Some(log.match_id.clone()), worst.cause.issue_type().to_owned(), "JMS".to_owned()));
+  ticket.notes.push(TicketComment { author: "JMS".to_owned(), time: chrono::Local::now(), comment });
+  ticket.insert(kv)?;
+
+  info!("Raised ticket for {}: {}", log.team, worst.cause);
+  Ok(())
+}
 
-  let mut logs = HashMap::new();
-  let mut last_state = None;
+// Logs are captured from Prestart until the match is committed, so Warmup, Cooldown and the disabled periods either side
+// of the match are kept as well. Entries are appended to the DB as they come in, so a crash mid-match doesn't lose the log.
+struct Capture {
+  match_id: String,
+  started: Instant,
+  // Wall-clock time of the start of capture, persisted on each log until it's aligned so a restart can pick it back up
+  started_at: chrono::DateTime<chrono::Local>,
+  // Whether times have been shifted to be relative to the start of Auto
+  aligned: bool,
+  logs: HashMap<usize, MatchLog>,
+}
 
-  loop {
-    interval.tick().await;
+impl Capture {
+  fn new(match_id: String) -> Self {
+    Self { match_id, started: Instant::now(), started_at: chrono::Local::now(), aligned: false, logs: HashMap::new() }
+  }
 
-    let reports = DriverStationReport::all(&kv)?;
-    let current_match: Option<SerialisedLoadedMatch> = kv.json_get(ARENA_MATCH_KEY, "$").ok();
-    if let Some(current_match) = current_match {
-      let match_time = current_match.match_time.map(|mt| mt.0.num_milliseconds()).unwrap_or(0);
+  // Pick up a capture that was in progress before a restart. If it hadn't been aligned yet, it's left for align() to shift.
+  fn resume(match_id: String, current_match: &SerialisedLoadedMatch, kv: &kv::KVConnection) -> anyhow::Result<Self> {
+    let mut capture = Self::new(match_id);
+    for log in MatchLog::all(kv)?.into_iter().filter(|l| l.match_id == capture.match_id && l.analysis.is_none()) {
+      capture.logs.insert(log.team, log);
+    }
 
-      if current_match.state == MatchPlayState::Auto || current_match.state == MatchPlayState::Pause || current_match.state == MatchPlayState::Teleop {
-        let mut waiting: Vec<usize> = logs.keys().cloned().collect::<Vec<_>>();
-        for report in reports {
-          waiting.retain(|x| x != &(report.team as usize));
-          // waiting.remove(report.team as usize);
+    match capture.logs.values().filter_map(|l| l.capture_start).min() {
+      Some(started_at) => {
+        let elapsed = (chrono::Local::now() - started_at).to_std().unwrap_or_default();
+        capture.started = capture.started.checked_sub(elapsed).unwrap_or(capture.started);
+        capture.started_at = started_at;
+      },
+      None => capture.aligned = !capture.logs.is_empty() || current_match.match_time.is_some(),
+    }
 
-          let entry = match logs.entry(report.team as usize) {
-            Entry::Occupied(o) => o.into_mut(),
-            Entry::Vacant(v) => v.insert(MatchLog { match_id: current_match.match_id.clone(), team: report.team as usize, timeseries: vec![] })
-          };
+    if !capture.logs.is_empty() {
+      info!("Resuming Match Log capture for {} ({} teams)", capture.match_id, capture.logs.len());
+    }
+    Ok(capture)
+  }
+
+  fn time(&self, current_match: &SerialisedLoadedMatch) -> i64 {
+    match (self.aligned, &current_match.match_time) {
+      (true, Some(mt)) => mt.0.num_milliseconds(),
+      _ => self.started.elapsed().as_millis() as i64
+    }
+  }
+
+  // Once Auto starts, move everything captured so far to be relative to the start of the match
+  fn align(&mut self, current_match: &SerialisedLoadedMatch, kv: &kv::KVConnection) -> anyhow::Result<()> {
+    if let (false, Some(mt)) = (self.aligned, &current_match.match_time) {
+      let offset = mt.0.num_milliseconds() - self.started.elapsed().as_millis() as i64;
+      for log in self.logs.values_mut() {
+        log.shift(offset);
+        log.capture_start = None;
+        log.insert(kv)?;
+      }
+      self.aligned = true;
+    }
+    Ok(())
+  }
 
-          entry.timeseries.push(TimeseriesDsReportEntry {
-            time: match_time as usize,
-            report: Some(report)
-          });
+  fn push<T: serde::Serialize>(&mut self, team: usize, path: &str, entry: T, field: fn(&mut MatchLog) -> &mut Vec<T>, kv: &kv::KVConnection) -> anyhow::Result<()> {
+    let log = match self.logs.entry(team) {
+      Entry::Occupied(o) => o.into_mut(),
+      Entry::Vacant(v) => {
+        let mut log = MatchLog::new(self.match_id.clone(), team);
+        log.capture_start = (!self.aligned).then_some(self.started_at);
+        log.insert(kv)?;
+        v.insert(log)
+      }
+    };
+    kv.json_arr_append(&log.key(), path, &entry)?;
+    field(log).push(entry);
+    Ok(())
+  }
+
+  // Commit the logs if the match was played, otherwise throw them away (e.g. if Prestart was undone)
+  fn finish(self, kv: &kv::KVConnection) -> anyhow::Result<()> {
+    info!("Committing Match Logs...");
+    let settings = DiagnosisSettings::get(kv)?;
+    for (_, mut log) in self.logs {
+      if !log.played() {
+        log.delete(kv)?;
+        continue;
+      }
+
+      log.analysis = Some(MatchLogAnalysis::analyse(&log, &settings));
+      log.insert(kv)?;
+      if let Err(e) = raise_tickets(&log, kv) {
+        warn!("Could not raise tickets for {}: {}", log.team, e);
+      }
+    }
+    kv.bgsave()?;
+    info!("Match Logs Committed!");
+    Ok(())
+  }
+}
+
+fn capturing(state: &ArenaState, was_capturing: bool) -> bool {
+  match state {
+    ArenaState::Prestart { .. } | ArenaState::MatchArmed | ArenaState::MatchPlay | ArenaState::MatchComplete => true,
+    // Keep going through an E-Stop so we can see what led up to it
+    ArenaState::Estop => was_capturing,
+    _ => false
+  }
+}
+
+async fn logs_svc(kv: kv::KVConnection, mq: mq::MessageQueueChannel) -> anyhow::Result<()> {
+  let mut interval = tokio::time::interval(Duration::from_millis(250));
+  let mut ds_logs: mq::MessageQueueSubscriber<DriverStationLogEvent> = mq.subscribe(DS_LOG_TOPIC, "match-logs-ds", "MatchLogs", false).await?;
+
+  let mut capture: Option<Capture> = None;
+  let mut current_match: Option<SerialisedLoadedMatch> = None;
+  let mut first = true;
+
+  loop {
+    tokio::select! {
+      _ = interval.tick() => {
+        current_match = kv.json_get(ARENA_MATCH_KEY, "$").ok();
+        let state = kv.json_get::<ArenaState>(ARENA_STATE_KEY, "$").ok();
+
+        let window = match (&state, &current_match) {
+          (Some(state), Some(m)) if capturing(state, capture.is_some() || first) => Some(m),
+          _ => None
+        };
+
+        // Finish up if we've left the capture window, or a different match has been loaded
+        if capture.as_ref().is_some_and(|c| window.map(|m| &m.match_id) != Some(&c.match_id)) {
+          capture.take().unwrap().finish(&kv)?;
         }
 
-        for wait in waiting {
-          // These teams haven't received an update - make their report as nil
-          if let Some(entry) = logs.get_mut(&wait) {
-            entry.timeseries.push(TimeseriesDsReportEntry { time: match_time as usize, report: None });
+        if let Some(m) = window {
+          let c = match capture.as_mut() {
+            Some(c) => c,
+            None => capture.insert(match first {
+              true => Capture::resume(m.match_id.clone(), m, &kv)?,
+              false => Capture::new(m.match_id.clone())
+            })
+          };
+          c.align(m, &kv)?;
+
+          let time = c.time(m);
+          for stn in AllianceStation::all(&kv)? {
+            if let Some(team) = stn.team {
+              let entry = TimeseriesDsReportEntry {
+                time,
+                phase: Some(m.state),
+                report: DriverStationReport::get(&(team as u16), &kv).ok(),
+                estop: stn.estop,
+                astop: stn.astop
+              };
+              c.push(team, "$.timeseries", entry, |l| &mut l.timeseries, &kv)?;
+            }
           }
         }
-      }
 
-      if current_match.state == MatchPlayState::Complete && last_state != Some(MatchPlayState::Complete) {
-        // Commit records
-        info!("Committing Match Logs...");
-        for (_, log) in logs.drain() {
-          log.insert(&kv)?;
-        }
-        kv.bgsave()?;
-        info!("Match Logs Committed!");
+        first = false;
+      },
+      msg = ds_logs.next() => match msg {
+        Some(Ok(msg)) => if let (Some(c), Some(m)) = (capture.as_mut(), current_match.as_ref()) {
+          let (time, phase) = (c.time(m), Some(m.state));
+          match msg.data {
+            DriverStationLogEvent::Sample { team, data } if c.logs.contains_key(&(team as usize)) => {
+              c.push(team as usize, "$.log_data", TimeseriesDsLogEntry { time, phase, data }, |l| &mut l.log_data, &kv)?;
+            },
+            DriverStationLogEvent::Messages { team, messages } if c.logs.contains_key(&(team as usize)) => {
+              for message in messages {
+                c.push(team as usize, "$.messages", TimeseriesDsMessageEntry { time, phase, message }, |l| &mut l.messages, &kv)?;
+              }
+            },
+            _ => ()
+          }
+        },
+        Some(Err(e)) => warn!("DS Log Error: {}", e),
+        None => anyhow::bail!("DS Log Channel Closed"),
       }
-
-      last_state = Some(current_match.state);
-    } else {
-      last_state = None;
     }
   }
 }
@@ -81,9 +229,10 @@ async fn main() -> anyhow::Result<()> {
   let _ = JMSLogger::init().await?;
   
   let kv = kv::KVConnection::new()?;
+  let mq = mq::MessageQueue::new("jms.match-logs-reply").await?;
 
   let component_fut = component_svc(kv.clone()?);
-  let logs_fut = logs_svc(kv);
+  let logs_fut = logs_svc(kv, mq.channel().await?);
   try_join!(component_fut, logs_fut)?;
 
   Ok(())
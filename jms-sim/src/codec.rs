@@ -0,0 +1,228 @@
+use bitvec::prelude::*;
+use bytes::{Buf, BufMut};
+use chrono::{Local, TimeZone};
+use jms_core_lib::models::{Alliance, AllianceStationId};
+use jms_driverstation_lib::{RobotState, TournamentLevel, tcp_codec::{Ds2FmsTCP, Ds2FmsTCPTags, Fms2DsStationStatus, Fms2DsTCP, Fms2DsTCPTags}, udp_codec::{Ds2FmsUDP, Ds2FmsUDPTags, Fms2DsUDP}};
+use tokio_util::codec::{Decoder, Encoder};
+
+// The Driver Station side of DSTCPCodec and DSUDPCodec. Encoding here is the inverse of the FMS decoder, and vice versa,
+// so the simulated driver stations are parsed by exactly the same code as the real ones.
+
+pub struct SimTCPCodec {
+  decode_frame_len: u16,
+}
+
+impl SimTCPCodec {
+  pub fn new() -> SimTCPCodec {
+    SimTCPCodec { decode_frame_len: 0 }
+  }
+}
+
+impl Decoder for SimTCPCodec {
+  type Item = Fms2DsTCP;
+  type Error = std::io::Error;
+
+  fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+    if self.decode_frame_len == 0 {
+      if src.remaining() < 2 {
+        return Ok(None);
+      }
+      self.decode_frame_len = src.get_u16();
+    }
+
+    if src.remaining() < self.decode_frame_len as usize {
+      return Ok(None);
+    }
+
+    let mut buf = src.split_to(self.decode_frame_len as usize);
+    self.decode_frame_len = 0;
+
+    let mut pkt = Fms2DsTCP { tags: vec![] };
+    match buf.get_u8() {
+      0x14 => pkt.tags.push(Fms2DsTCPTags::EventCode(str_with_len(&mut buf))),
+      0x19 => {
+        let stn = AllianceStationId::from_ds_number(buf.get_u8()).unwrap_or(AllianceStationId::new(Alliance::Blue, 1));
+        let status = match buf.get_u8() {
+          0 => Fms2DsStationStatus::Good,
+          1 => Fms2DsStationStatus::Bad,
+          _ => Fms2DsStationStatus::Waiting,
+        };
+        pkt.tags.push(Fms2DsTCPTags::StationInfo(stn, status));
+      },
+      0x1c => pkt.tags.push(Fms2DsTCPTags::GameData(str_with_len(&mut buf))),
+      _ => ()
+    }
+
+    Ok(Some(pkt))
+  }
+}
+
+fn str_with_len(buf: &mut bytes::BytesMut) -> String {
+  let len = (buf.get_u8() as usize).min(buf.remaining());
+  String::from_utf8_lossy(&buf.split_to(len)).to_string()
+}
+
+impl Encoder<Ds2FmsTCP> for SimTCPCodec {
+  type Error = std::io::Error;
+
+  fn encode(&mut self, pkt: Ds2FmsTCP, dst: &mut bytes::BytesMut) -> Result<(), Self::Error> {
+    for tag in pkt.tags {
+      let mut writer: Vec<u8> = vec![];
+
+      match tag {
+        Ds2FmsTCPTags::TeamNumber(team) => {
+          writer.put_u8(0x18);
+          writer.put_u16(team);
+        },
+        Ds2FmsTCPTags::DSVersion(version) => {
+          writer.put_u8(0x02);
+          writer.extend_from_slice(version.as_bytes());
+        },
+        Ds2FmsTCPTags::LogData(dat) => {
+          writer.put_u8(0x16);
+          writer.put_u8((dat.rtt * 2.0) as u8);
+          writer.put_u8((dat.lost_percent / 4.0) as u8);
+          writer.put_u8(dat.battery.trunc() as u8);
+          writer.put_u8((dat.battery.fract() * 256.0) as u8);
+          writer.put_u8((dat.rio_cpu * 2.0) as u8);
+
+          let mut status = 0u8;
+          {
+            let bv = status.view_bits_mut::<Msb0>();
+            bv.set(0, dat.brownout);
+            bv.set(1, dat.watchdog);
+            bv.set(2, dat.ds_teleop);
+            bv.set(3, dat.ds_auto);
+            bv.set(4, dat.ds_disable);
+            bv.set(5, dat.robot_teleop);
+            bv.set(6, dat.robot_auto);
+            bv.set(7, dat.robot_disable);
+          }
+          writer.put_u8(!status);
+
+          writer.put_u8((dat.can_usage * 2.0) as u8);
+          writer.put_u8((dat.wifi_db * 2.0) as u8);
+          writer.put_u16((dat.bandwidth * 256.0) as u16);
+        },
+        Ds2FmsTCPTags::Ping => writer.put_u8(0x1c),
+        // The FMS doesn't care about the rest yet
+        _ => continue
+      }
+
+      dst.put_u16(writer.len() as u16);
+      dst.extend_from_slice(&writer);
+    }
+
+    Ok(())
+  }
+}
+
+pub struct SimUDPCodec {
+  pub seq_num_enc: u16,
+}
+
+impl SimUDPCodec {
+  pub fn new() -> SimUDPCodec {
+    SimUDPCodec { seq_num_enc: 0 }
+  }
+}
+
+impl Decoder for SimUDPCodec {
+  type Item = Fms2DsUDP;
+  type Error = std::io::Error;
+
+  fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+    if src.remaining() < 22 {
+      return Ok(None);
+    }
+
+    let mut buf = src.split_to(src.remaining());
+    buf.get_u16(); // Sequence
+    buf.get_u8();  // Comms version
+
+    let control = buf.get_u8();
+    buf.get_u8();  // Request
+
+    let station = AllianceStationId::from_ds_number(buf.get_u8()).unwrap_or(AllianceStationId::new(Alliance::Blue, 1));
+    let tournament_level = match buf.get_u8() {
+      1 => TournamentLevel::Practice,
+      2 => TournamentLevel::Qualification,
+      3 => TournamentLevel::Playoff,
+      _ => TournamentLevel::Test,
+    };
+    let match_number = buf.get_u16();
+    let play_number = buf.get_u8();
+
+    let _micros = buf.get_u32();
+    let (sec, min, hour, day, month, year) = (buf.get_u8(), buf.get_u8(), buf.get_u8(), buf.get_u8(), buf.get_u8(), buf.get_u8());
+    let time = Local.with_ymd_and_hms(year as i32 + 1900, month as u32, day as u32, hour as u32, min as u32, sec as u32).single().unwrap_or_else(Local::now);
+
+    let remaining_seconds = buf.get_u16();
+
+    Ok(Some(Fms2DsUDP {
+      estop: (control & 0b1000_0000) != 0,
+      enabled: (control & 0b0000_0100) != 0,
+      mode: match control & 0b11 {
+        2 => RobotState::Auto,
+        1 => RobotState::Test,
+        _ => RobotState::Teleop,
+      },
+      station,
+      tournament_level,
+      match_number,
+      play_number,
+      time,
+      remaining_seconds,
+    }))
+  }
+}
+
+impl Encoder<Ds2FmsUDP> for SimUDPCodec {
+  type Error = std::io::Error;
+
+  fn encode(&mut self, pkt: Ds2FmsUDP, dst: &mut bytes::BytesMut) -> Result<(), Self::Error> {
+    self.seq_num_enc = self.seq_num_enc.wrapping_add(1);
+    dst.put_u16(self.seq_num_enc);
+    dst.put_u8(0x00);
+
+    let mode_bits = match pkt.mode {
+      RobotState::Auto => 2,
+      RobotState::Test => 1,
+      RobotState::Teleop => 0,
+    };
+    dst.put_u8(
+      ((pkt.estop as u8) << 7) | ((pkt.robot as u8) << 5) | ((pkt.radio as u8) << 4)
+      | ((pkt.rio as u8) << 3) | ((pkt.enabled as u8) << 2) | mode_bits
+    );
+    dst.put_u16(pkt.team);
+    dst.put_u8(pkt.battery.trunc() as u8);
+    dst.put_u8((pkt.battery.fract() * 256.0) as u8);
+
+    for tag in pkt.tags {
+      match tag {
+        Ds2FmsUDPTags::FieldRadioMetrics(strength, bandwidth) | Ds2FmsUDPTags::RobotRadioMetrics(strength, bandwidth) => {
+          dst.put_u8(4);
+          dst.put_u8(if matches!(tag, Ds2FmsUDPTags::FieldRadioMetrics(..)) { 0x00 } else { 0x03 });
+          dst.put_u8(strength);
+          dst.put_u16(bandwidth);
+        },
+        Ds2FmsUDPTags::CommsMetrics(lost, sent, trip_time) => {
+          dst.put_u8(6);
+          dst.put_u8(0x01);
+          dst.put_u16(lost);
+          dst.put_u16(sent);
+          dst.put_u8(trip_time);
+        },
+        Ds2FmsUDPTags::LaptopMetrics(battery, cpu) => {
+          dst.put_u8(3);
+          dst.put_u8(0x02);
+          dst.put_u8(battery);
+          dst.put_u8(cpu);
+        },
+        Ds2FmsUDPTags::Unknown(..) => ()
+      }
+    }
+
+    Ok(())
+  }
+}
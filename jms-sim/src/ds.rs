@@ -0,0 +1,143 @@
+use std::{net::{Ipv4Addr, SocketAddr}, time::Duration};
+
+use futures::{SinkExt, StreamExt};
+use jms_core_lib::models::AllianceStationId;
+use jms_driverstation_lib::{RobotState, tcp_codec::{DSLogData, Ds2FmsTCP, Ds2FmsTCPTags, Fms2DsStationStatus, Fms2DsTCPTags}, udp_codec::{Ds2FmsUDP, Ds2FmsUDPTags}};
+use log::{info, warn};
+use tokio::net::{TcpSocket, UdpSocket};
+use tokio_util::{codec::Framed, udp::UdpFramed};
+
+use crate::codec::{SimTCPCodec, SimUDPCodec};
+
+// The real DS sends control packets every 20ms, and TCP pings / log data every second.
+const UDP_INTERVAL: Duration = Duration::from_millis(20);
+const TCP_INTERVAL: Duration = Duration::from_millis(1000);
+
+#[derive(Debug, Clone)]
+pub struct VirtualDSConfig {
+  pub fms: Ipv4Addr,
+  pub battery: f64,
+  // Probability (0-1) of dropping each control packet
+  pub loss: f64,
+  pub trip_time: u8,
+  pub robot: bool,
+}
+
+pub struct VirtualDriverStation {
+  pub team: u16,
+  // The team whose network the DS is plugged into. If this is another team, the DS is in the wrong station.
+  pub network_team: u16,
+  pub config: VirtualDSConfig,
+}
+
+// The FMS works out which station a DS is in from its 10.TE.AM.x address. Loopback covers all of 127.0.0.0/8 on Linux,
+// so we use 127.TE.AM.x to get the same behaviour on a single machine.
+pub fn team_address(team: u16, host: u8) -> Ipv4Addr {
+  Ipv4Addr::new(127, (team / 100) as u8, (team % 100) as u8, host)
+}
+
+impl VirtualDriverStation {
+  // Keep the DS connected, reconnecting whenever the FMS drops us (like a real DS does)
+  pub async fn run(self) {
+    loop {
+      match self.connect().await {
+        Ok(()) => info!("[{}] Disconnected", self.team),
+        Err(e) => warn!("[{}] Connection Error: {}", self.team, e),
+      }
+      tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+  }
+
+  async fn connect(&self) -> anyhow::Result<()> {
+    // A DS in the wrong station shares the network with that station's own DS, so it needs a different host address
+    let local = team_address(self.network_team, if self.network_team == self.team { 5 } else { 6 });
+
+    let socket = TcpSocket::new_v4()?;
+    socket.bind(SocketAddr::new(local.into(), 0))?;
+    let stream = socket.connect(SocketAddr::new(self.config.fms.into(), 1750)).await?;
+    let mut framed_tcp = Framed::new(stream, SimTCPCodec::new());
+
+    let udp_socket = UdpSocket::bind(SocketAddr::new(local.into(), 1121)).await?;
+    let mut framed_udp = UdpFramed::new(udp_socket, SimUDPCodec::new());
+    let fms_udp = SocketAddr::new(self.config.fms.into(), 1160);
+
+    info!("[{}] Connected from {}", self.team, local);
+
+    framed_tcp.send(Ds2FmsTCP { tags: vec![ Ds2FmsTCPTags::TeamNumber(self.team), Ds2FmsTCPTags::DSVersion("JMS-Sim".to_owned()) ] }).await?;
+
+    let mut udp_timer = tokio::time::interval(UDP_INTERVAL);
+    let mut tcp_timer = tokio::time::interval(TCP_INTERVAL);
+
+    let (mut enabled, mut estop, mut mode) = (false, false, RobotState::Teleop);
+    let mut station: Option<(AllianceStationId, Fms2DsStationStatus)> = None;
+    let (mut sent, mut lost) = (0u16, 0u16);
+
+    loop {
+      tokio::select! {
+        _ = udp_timer.tick() => {
+          // Lost packets still count toward the DS's sent total
+          sent = sent.wrapping_add(1);
+          if rand::random::<f64>() < self.config.loss {
+            lost = lost.wrapping_add(1);
+            continue;
+          }
+
+          let pkt = Ds2FmsUDP {
+            seq: 0,
+            team: self.team,
+            estop,
+            robot: self.config.robot,
+            radio: true,
+            rio: true,
+            enabled: enabled && self.config.robot,
+            mode,
+            battery: self.config.battery,
+            tags: vec![ Ds2FmsUDPTags::CommsMetrics(lost, sent, self.config.trip_time) ],
+          };
+          framed_udp.send((pkt, fms_udp)).await?;
+        },
+        _ = tcp_timer.tick() => {
+          let log = DSLogData {
+            rtt: self.config.trip_time as f64,
+            lost_percent: if sent > 0 { (lost as f64) * 100.0 / (sent as f64) } else { 0.0 },
+            battery: self.config.battery,
+            ds_teleop: mode == RobotState::Teleop,
+            ds_auto: mode == RobotState::Auto,
+            ds_disable: !enabled,
+            robot_teleop: self.config.robot && mode == RobotState::Teleop,
+            robot_auto: self.config.robot && mode == RobotState::Auto,
+            robot_disable: !(enabled && self.config.robot),
+            ..Default::default()
+          };
+          framed_tcp.send(Ds2FmsTCP { tags: vec![ Ds2FmsTCPTags::Ping, Ds2FmsTCPTags::LogData(log) ] }).await?;
+        },
+        frame = framed_tcp.next() => match frame {
+          Some(pkt) => for tag in pkt?.tags {
+            match tag {
+              Fms2DsTCPTags::StationInfo(stn, status) if station != Some((stn, status)) => {
+                info!("[{}] Station {} - {:?}", self.team, stn.to_string(), status);
+                station = Some((stn, status));
+              },
+              Fms2DsTCPTags::EventCode(code) => info!("[{}] Event Code: {}", self.team, code),
+              Fms2DsTCPTags::GameData(data) => info!("[{}] Game Data: {}", self.team, data),
+              _ => ()
+            }
+          },
+          None => return Ok(())
+        },
+        frame = framed_udp.next() => match frame {
+          Some(Ok((pkt, _))) => {
+            if (pkt.enabled, pkt.estop, pkt.mode) != (enabled, estop, mode) {
+              info!("[{}] {:?} - {}{}", self.team, pkt.mode, if pkt.enabled { "Enabled" } else { "Disabled" }, if pkt.estop { " (E-Stopped)" } else { "" });
+            }
+            enabled = pkt.enabled;
+            estop = pkt.estop;
+            mode = pkt.mode;
+          },
+          Some(Err(e)) => warn!("[{}] UDP Error: {}", self.team, e),
+          None => return Ok(())
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, net::Ipv4Addr, time::Duration};
+
+use clap::Parser;
+use ds::{VirtualDSConfig, VirtualDriverStation};
+use jms_arena_lib::AllianceStation;
+use jms_base::{kv::KVConnection, logging::JMSLogger};
+use log::info;
+use tokio::task::JoinHandle;
+
+pub mod codec;
+pub mod ds;
+
+/// Simulated Driver Stations, for rehearsing the arena flow without any robots.
+/// The simulated DSs talk to JMS-DriverStation over loopback (127.TE.AM.x), so this needs to run on the same (Linux) machine.
+#[derive(Parser, Debug)]
+struct Args {
+  /// The teams to simulate. If not provided, follows the teams in the Alliance Stations, so matches can be loaded as normal.
+  #[clap(value_parser)]
+  teams: Vec<u16>,
+  /// The address JMS-DriverStation is listening on
+  #[clap(long, value_parser, default_value = "127.0.0.1")]
+  fms: Ipv4Addr,
+  /// The battery voltage reported by each robot
+  #[clap(long, value_parser, default_value_t = 12.5)]
+  battery: f64,
+  /// The battery voltage for a specific team, as TEAM=VOLTS
+  #[clap(long, value_parser = parse_team_value)]
+  battery_for: Vec<(u16, f64)>,
+  /// The fraction (0-1) of control packets each DS drops
+  #[clap(long, value_parser, default_value_t = 0.0)]
+  loss: f64,
+  /// The fraction of control packets dropped for a specific team, as TEAM=FRACTION
+  #[clap(long, value_parser = parse_team_value)]
+  loss_for: Vec<(u16, f64)>,
+  /// The trip time reported by each DS, in milliseconds
+  #[clap(long, value_parser, default_value_t = 5)]
+  trip_time: u8,
+  /// Teams whose robot code isn't running
+  #[clap(long, value_parser)]
+  no_robot: Vec<u16>,
+  /// Teams that plug into the wrong station, taking the network of the next team along
+  #[clap(long, value_parser)]
+  wrong_station: Vec<u16>,
+}
+
+fn parse_team_value(s: &str) -> Result<(u16, f64), String> {
+  let (team, value) = s.split_once('=').ok_or(format!("Expected TEAM=VALUE, got {}", s))?;
+  Ok((
+    team.parse().map_err(|_| format!("Invalid team: {}", team))?,
+    value.parse().map_err(|_| format!("Invalid value: {}", value))?
+  ))
+}
+
+impl Args {
+  fn lookup(values: &[(u16, f64)], team: u16, default: f64) -> f64 {
+    values.iter().find(|(t, _)| *t == team).map(|(_, v)| *v).unwrap_or(default)
+  }
+
+  // The DSs that should be running, as (team, team whose network it's plugged into)
+  fn desired(&self, teams: &[u16]) -> Vec<(u16, u16)> {
+    teams.iter().enumerate().map(|(i, &team)| {
+      match self.wrong_station.contains(&team) && teams.len() > 1 {
+        true => (team, teams[(i + 1) % teams.len()]),
+        false => (team, team)
+      }
+    }).collect()
+  }
+
+  fn spawn(&self, team: u16, network_team: u16) -> JoinHandle<()> {
+    let ds = VirtualDriverStation {
+      team,
+      network_team,
+      config: VirtualDSConfig {
+        fms: self.fms,
+        battery: Self::lookup(&self.battery_for, team, self.battery),
+        loss: Self::lookup(&self.loss_for, team, self.loss),
+        trip_time: self.trip_time,
+        robot: !self.no_robot.contains(&team),
+      }
+    };
+    info!("Starting Virtual DS for {} (network of {})", team, network_team);
+    tokio::spawn(ds.run())
+  }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+  let _ = JMSLogger::init().await?;
+  let args = Args::parse();
+
+  if !args.teams.is_empty() {
+    let handles: Vec<JoinHandle<()>> = args.desired(&args.teams).into_iter().map(|(team, network)| args.spawn(team, network)).collect();
+    futures::future::join_all(handles).await;
+    return Ok(());
+  }
+
+  // Follow the teams in the Alliance Stations, starting and stopping DSs as matches are loaded
+  let kv = KVConnection::new()?;
+  let mut running: HashMap<u16, (u16, JoinHandle<()>)> = HashMap::new();
+  let mut interval = tokio::time::interval(Duration::from_millis(1000));
+
+  loop {
+    interval.tick().await;
+    let teams: Vec<u16> = AllianceStation::sorted(&kv)?.into_iter().filter_map(|s| s.team.map(|t| t as u16)).collect();
+    let desired = args.desired(&teams);
+
+    running.retain(|team, (network, handle)| {
+      let keep = desired.contains(&(*team, *network));
+      if !keep {
+        info!("Stopping Virtual DS for {}", team);
+        handle.abort();
+      }
+      keep
+    });
+
+    for (team, network) in desired {
+      if !running.contains_key(&team) {
+        running.insert(team, (network, args.spawn(team, network)));
+      }
+    }
+  }
+}
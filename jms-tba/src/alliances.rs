@@ -3,6 +3,8 @@ use jms_core_lib::models;
 
 use crate::{teams::TBATeam, client::TBAClient};
 
+// alliance_selections/update only takes the picks, as the trusted API has no field for backups. Backups reach TBA through the
+// match upload instead, since playoff matches are scheduled with the alliance's lineup (see PlayoffAlliance::lineup).
 #[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(transparent)]
 pub struct TBAAlliance(Vec<TBATeam>);
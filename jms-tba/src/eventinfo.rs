@@ -16,6 +16,7 @@ pub enum TBAPlayoffType {
   BestOf5FinalOnly = 6,
   BestOf3FinalOnly = 7,
   DoubleElim8 = 10,
+  DoubleElim4 = 11,
   Custom = 8
 }
 
@@ -49,9 +50,13 @@ impl TBAEventInfoUpdate {
   fn playoff_type(kv: &kv::KVConnection) -> anyhow::Result<TBAPlayoffType> {
     let pm = models::PlayoffMode::get(kv)?;
     
+    // Smaller brackets are the 8 alliance template with byes, and the match sets are numbered to suit, so they're
+    // always reported as 8 alliance brackets.
     Ok(match (pm.mode, pm.n_alliances) {
       (PlayoffModeType::Bracket, 1..=8) => TBAPlayoffType::Bracket8,
       (PlayoffModeType::DoubleBracket, 1..=8) => TBAPlayoffType::DoubleElim8,
+      (PlayoffModeType::RoundRobin, 6) => TBAPlayoffType::RoundRobin6,
+      // TBA only has a layout for the 6 alliance round robin
       _ => TBAPlayoffType::Custom
     })
   }
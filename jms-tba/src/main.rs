@@ -1,5 +1,5 @@
 use jms_base::{kv, logging::JMSLogger, mq};
-use jms_core_lib::{models::{JmsComponent, self}, db::Table};
+use jms_core_lib::{models::{JmsComponent, self}, db::{Singleton, Table}};
 use jms_tba_lib::TBARPC;
 use log::{info, warn};
 use matches::TBAMatchUpdate;
@@ -40,7 +40,7 @@ async fn do_update(kv: &kv::KVConnection) -> anyhow::Result<()> {
   if let Err(e) = alliances.issue(&kv).await { warn!("Could not issue alliance update: {}", e) }
 
   // Rankings
-  let rankings: TBARankings = models::TeamRanking::all(&kv)?.into();
+  let rankings = TBARankings::new(models::TeamRanking::all(&kv)?, models::EventDetails::get(&kv)?.game);
   if let Err(e) = rankings.issue(&kv).await { warn!("Could not issue ranking update: {}", e) }
 
   // Matches
@@ -1,5 +1,5 @@
 use jms_base::kv;
-use jms_core_lib::{db::{Singleton, Table}, models::{self, MatchType, PlayoffModeType}, scoring::scores::{EndgameType, MatchScoreSnapshot, ScoringConfig, SnapshotScore}};
+use jms_core_lib::{db::{Singleton, Table}, models::{self, MatchType, PlayoffModeType}, scoring::scores::{MatchScoreSnapshot, ScoringConfig}};
 use log::{error, info, warn};
 
 use crate::{teams::TBATeam, client::TBAClient};
@@ -7,8 +7,9 @@ use crate::{teams::TBATeam, client::TBAClient};
  #[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct TBAMatchAlliance {
   teams: Vec<TBATeam>,
+  surrogate_team_keys: Vec<TBATeam>,
   score: Option<isize>,
-  // We don't do surrogates and DQs
+  // We don't do DQs
 }
 
 #[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
@@ -27,7 +28,7 @@ pub struct TBAMatch {
   pub set_number: usize,
   pub match_number: usize,
   pub alliances: TBAMatchAlliances,
-  pub score_breakdown: Option<TBA2024ScoreBreakdownFull>,
+  pub score_breakdown: Option<TBAScoreBreakdownFull>,
   pub time_str: Option<String>,
   pub time_utc: Option<String>
 }
@@ -51,12 +52,16 @@ impl TBAMatchUpdate {
 
     let playoff_type = models::PlayoffMode::get(kv)?;
     let matches = models::Match::all(kv)?;
+    let all_matches = matches.clone();
     let scores = models::CommittedMatchScores::all_map(kv)?;
     let score_config = ScoringConfig::get(kv)?;
 
     for m in matches {
-      if m.match_type != MatchType::Test {
-        let latest_score = scores.get(&m.id).and_then(|cms| cms.scores.last()).map(|x| x.clone().derive(score_config));
+      // TBA only knows of one match per Qualification set, so only send the latest played replay
+      let is_stale_qual = m.superseded_by_replay(&all_matches) || (m.match_type == MatchType::Qualification && m.match_number > 1 && !m.played);
+
+      if m.match_type != MatchType::Test && !is_stale_qual {
+        let latest_score = scores.get(&m.id).and_then(|cms| cms.scores.last()).map(|x| x.clone().derive(&score_config)).transpose()?;
         
         // Try to convert our format into TBA's format
         let (comp_level, set, match_n) = match (&playoff_type.mode, m.match_type, m.round, m.set_number, m.match_number) {
@@ -81,6 +86,11 @@ impl TBAMatchUpdate {
             };
             ( Some(TBAMatchLevel("sf")), tba_set, m )
           },
+          (PlayoffModeType::RoundRobin, MatchType::Playoff, r, s, _) => {
+            // TBA treats the round robin as one big set, numbered in the order they're played
+            let sets_per_round = playoff_type.n_alliances / 2;
+            ( Some(TBAMatchLevel("sf")), 1, (r - 1) * sets_per_round + s )
+          },
           _ => (None, 0, 0)
         };
 
@@ -89,15 +99,18 @@ impl TBAMatchUpdate {
         // Fill TBA's match type
         match (comp_level, set, match_n) {
           ( Some(comp_level), set_number, match_number ) if set_number != 0 && match_number != 0 => {
+            // Playoff matches hold the alliance lineup, so a backup that's been called in is sent in place of the team it replaced
             let red_teams = m.red_teams.iter().filter_map(|x| x.map(|t| TBATeam::from(t))).collect();
             let blue_teams = m.blue_teams.iter().filter_map(|x| x.map(|t| TBATeam::from(t))).collect();
+            let red_surrogates = m.red_teams.iter().filter_map(|x| *x).filter(|&t| m.is_surrogate(t)).map(|t| TBATeam::from(t)).collect();
+            let blue_surrogates = m.blue_teams.iter().filter_map(|x| *x).filter(|&t| m.is_surrogate(t)).map(|t| TBATeam::from(t)).collect();
 
             let tba_match = TBAMatch {
               comp_level, set_number, match_number,
-              score_breakdown: latest_score.clone().map(Into::into),
+              score_breakdown: latest_score.as_ref().map(TBAScoreBreakdownFull::new).transpose()?,
               alliances: TBAMatchAlliances {
-                red: TBAMatchAlliance { teams: red_teams, score: latest_score.as_ref().map(|x| x.red.derived.total_score as isize) },
-                blue: TBAMatchAlliance { teams: blue_teams, score: latest_score.as_ref().map(|x| x.blue.derived.total_score as isize) }
+                red: TBAMatchAlliance { teams: red_teams, surrogate_team_keys: red_surrogates, score: latest_score.as_ref().map(|x| x.red.derived.total_score() as isize) },
+                blue: TBAMatchAlliance { teams: blue_teams, surrogate_team_keys: blue_surrogates, score: latest_score.as_ref().map(|x| x.blue.derived.total_score() as isize) }
               },
               time_str: Some(m.start_time.format("%l:%M %p").to_string()),
               time_utc: Some(chrono::DateTime::<chrono::Utc>::from(m.start_time).format("%+").to_string())
@@ -150,143 +163,16 @@ impl TBAMatchUpdate {
 }
 
 #[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
-pub struct TBA2024ScoreBreakdownFull {
-  blue: TBA2024ScoreBreakdown,
-  red: TBA2024ScoreBreakdown,
-}
-
-impl From<MatchScoreSnapshot> for TBA2024ScoreBreakdownFull {
-  fn from(score: MatchScoreSnapshot) -> Self {
-    Self {
-      red: score.red.into(),
-      blue: score.blue.into()
-    }
-  }
-}
-
-#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
-#[allow(non_snake_case)]
-pub struct TBA2024ScoreBreakdown {
-  // Thanks Cheesy-Arena :)
-  autoLineRobot1: &'static str,
-  autoLineRobot2: &'static str,
-  autoLineRobot3: &'static str,
-  autoLeavePoints: isize,
-
-  autoAmpNoteCount: isize,
-  autoAmpNotePoints: isize,
-  autoSpeakerNoteCount: isize,
-  autoSpeakerNotePoints: isize,
-  autoTotalNotePoints: isize,
-  autoPoints: isize,
-
-  teleopAmpNoteCount: isize,
-  teleopAmpNotePoints: isize,
-  teleopSpeakerNoteCount: isize,
-  teleopSpeakerNotePoints: isize,
-  teleopSpeakerNoteAmplifiedCount: isize,
-  teleopSpeakerNoteAmplifiedPoints: isize,
-  teleopTotalNotePoints: isize,
-
-  endGameRobot1: &'static str,
-  endGameRobot2: &'static str,
-  endGameRobot3: &'static str,
-
-  endGameParkPoints: isize,
-  endGameOnStagePoints: isize,
-  endGameHarmonyPoints: isize,
-  micStageLeft: bool,
-  micCenterStage: bool,
-  micStageRight: bool,
-
-  endGameSpotLightBonusPoints: isize,
-
-  trapStageLeft: bool,
-  trapCenterStage: bool,
-  trapStageRight: bool,
-
-  endGameNoteInTrapPoints: isize,
-  endGameTotalStagePoints: isize,
-  teleopPoints: isize,
-
-  coopertitionCriteriaMet: bool,
-  melodyBonusAchieved: bool,
-  ensembleBonusAchieved: bool,
-
-  foulCount: isize,
-  techFoulCount: isize,
-  g424Penalty: bool,
-  foulPoints: isize,
-  totalPoints: isize,
-  rp: isize,
+pub struct TBAScoreBreakdownFull {
+  blue: serde_json::Value,
+  red: serde_json::Value,
 }
 
-impl TBA2024ScoreBreakdown {
-  pub fn yes_no(b: Option<&bool>) -> &'static str {
-    if b == Some(&true) { "Yes" }
-    else { "No" }
-  }
-
-  pub fn endgame_map(egt: Option<&EndgameType>) -> &'static str {
-    match egt {
-      Some(EndgameType::None) => "None",
-      Some(EndgameType::Parked) => "Parked",
-      Some(EndgameType::Stage(0)) => "CenterStage",
-      Some(EndgameType::Stage(1)) => "StageLeft",
-      Some(EndgameType::Stage(2)) => "StageRight",
-      _ => "None"
-    }
-  }
-}
-
-impl From<SnapshotScore> for TBA2024ScoreBreakdown {
-  fn from(score: SnapshotScore) -> Self {
-    let live = score.live;
-    let derived = score.derived;
-
-    Self {
-      autoLineRobot1: Self::yes_no(live.leave.get(0)),
-      autoLineRobot2: Self::yes_no(live.leave.get(1)),
-      autoLineRobot3: Self::yes_no(live.leave.get(2)),
-      autoLeavePoints: derived.leave_points,
-      autoAmpNoteCount: live.notes.amp.auto as isize,
-      autoAmpNotePoints: derived.notes.amp_points.auto,
-      autoSpeakerNoteCount: live.notes.speaker_auto as isize,
-      autoSpeakerNotePoints: derived.notes.speaker_auto_points,
-      autoTotalNotePoints: derived.notes.amp_points.auto + derived.notes.speaker_auto_points,
-      autoPoints: derived.mode_score.auto,
-      teleopAmpNoteCount: live.notes.amp.teleop as isize,
-      teleopAmpNotePoints: derived.notes.amp_points.teleop,
-      teleopSpeakerNoteCount: live.notes.speaker_unamped as isize,
-      teleopSpeakerNotePoints: derived.notes.speaker_unamped_points,
-      teleopSpeakerNoteAmplifiedCount: live.notes.speaker_amped as isize,
-      teleopSpeakerNoteAmplifiedPoints: derived.notes.speaker_amped_points,
-      teleopTotalNotePoints: derived.notes.amp_points.teleop + derived.notes.speaker_unamped_points + derived.notes.speaker_amped_points,
-      endGameRobot1: Self::endgame_map(live.endgame.get(0)),
-      endGameRobot2: Self::endgame_map(live.endgame.get(1)),
-      endGameRobot3: Self::endgame_map(live.endgame.get(2)),
-      endGameParkPoints: derived.endgame_park_points,
-      endGameOnStagePoints: derived.endgame_onstage_points,
-      endGameHarmonyPoints: derived.endgame_harmony_points,
-      micStageLeft: live.microphones.get(1) == Some(&true),
-      micCenterStage: live.microphones.get(0) == Some(&true),
-      micStageRight: live.microphones.get(2) == Some(&true),
-      endGameSpotLightBonusPoints: derived.endgame_spotlit_bonus_points,
-      trapStageLeft: live.traps.get(1) == Some(&true),
-      trapCenterStage: live.traps.get(0) == Some(&true),
-      trapStageRight: live.traps.get(2) == Some(&true),
-      endGameNoteInTrapPoints: derived.endgame_trap_points,
-      endGameTotalStagePoints: derived.endgame_points,
-      teleopPoints: derived.mode_score.teleop,
-      coopertitionCriteriaMet: derived.coopertition_met,
-      melodyBonusAchieved: derived.melody_rp,
-      ensembleBonusAchieved: derived.ensemble_rp,
-      foulCount: live.penalties.fouls as isize,
-      techFoulCount: live.penalties.tech_fouls as isize,
-      g424Penalty: false,
-      foulPoints: derived.penalty_score as isize,
-      totalPoints: derived.total_score as isize,
-      rp: derived.total_rp as isize,
-    }
+impl TBAScoreBreakdownFull {
+  pub fn new(snapshot: &MatchScoreSnapshot) -> anyhow::Result<Self> {
+    Ok(Self {
+      blue: snapshot.blue.tba_breakdown()?,
+      red: snapshot.red.tba_breakdown()?,
+    })
   }
 }
\ No newline at end of file
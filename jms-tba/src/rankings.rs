@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+
 use jms_base::kv;
-use jms_core_lib::models;
+use jms_core_lib::{models, scoring::game::Game};
 
 use crate::{teams::TBATeam, client::TBAClient};
 
@@ -14,21 +16,21 @@ pub struct TBATeamRank {
   dqs: usize,
 
   rp: f64,
-  auto: isize,
-  teleop: isize,
-  endgame: isize,
+  #[serde(flatten)]
+  tiebreakers: HashMap<String, isize>,
 }
 
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct TBARankings {
-  breakdowns: Vec<&'static str>,
+  breakdowns: Vec<String>,
   rankings: Vec<TBATeamRank>
 }
 
-impl From<Vec<models::TeamRanking>> for TBARankings {
-  fn from(mut ranks: Vec<models::TeamRanking>) -> Self {
+impl TBARankings {
+  pub fn new(mut ranks: Vec<models::TeamRanking>, game: Game) -> Self {
     ranks.sort();
-    let breakdowns = vec!["wins", "losses", "ties", "rp", "auto", "endgame", "teleop"];
+    let tiebreaker_names = game.tiebreakers().iter().map(|x| x.name.to_lowercase()).collect::<Vec<_>>();
+    let breakdowns = ["wins", "losses", "ties", "rp"].into_iter().map(|x| x.to_owned()).chain(tiebreaker_names.iter().cloned()).collect();
     let rankings = ranks.iter().enumerate().map(|(i, r)| TBATeamRank {
       team_key: TBATeam::from(r.team as usize),
       rank: i + 1,
@@ -36,20 +38,16 @@ impl From<Vec<models::TeamRanking>> for TBARankings {
       losses: r.loss as usize,
       ties: r.tie as usize,
       played: r.played as usize,
-      dqs: 0,
+      dqs: r.dqs,
       rp: (r.rp as f64) / (r.played as f64),
-      auto: r.auto_points as isize,
-      teleop: r.teleop_points as isize,
-      endgame: r.endgame_points as isize
+      tiebreakers: tiebreaker_names.iter().enumerate().map(|(j, name)| (name.clone(), r.tiebreakers.get(j).copied().unwrap_or(0))).collect()
     }).collect();
 
     Self {
       breakdowns, rankings
     }
   }
-}
 
-impl TBARankings {
   pub async fn issue(&self, kv: &kv::KVConnection) -> anyhow::Result<()> {
     TBAClient::post("rankings", "update", self, kv).await
   }
@@ -100,6 +100,7 @@ export default withPermission(["ManageAudience"], function AudienceDisplayContro
       <h4 className="mt-3"> Playoffs </h4>
       <Button onClick={() => setScene({ scene: "AllianceSelection" })}> Alliance Selection </Button> &nbsp;
       <Button onClick={() => setScene({ scene: "PlayoffBracket" })} variant="orange"> Playoff Bracket </Button> &nbsp;
+      <Button onClick={() => setScene({ scene: "Timeout" })} variant="purple"> Timeout </Button> &nbsp;
 
       <h4 className="mt-3"> Awards </h4>
       {
@@ -3,7 +3,7 @@ import "./audience.scss";
 
 import { useEffect, useState } from "react";
 import { useWebsocket } from "../support/ws-component"
-import { AllianceStation, ArenaState, AudienceDisplay, AudienceDisplaySound, Award, EventDetails, Match, MatchScoreSnapshot, PlayoffAlliance, PlayoffMode, SerialisedLoadedMatch, Team, TeamRanking } from "../ws-schema";
+import { AllianceSelection, AllianceStation, ArenaState, AudienceDisplay, AudienceDisplaySound, Award, EventDetails, Match, MatchScoreSnapshot, PlayoffAlliance, PlayoffMode, SerialisedLoadedMatch, SerialisedTimeout, Team, TeamRanking } from "../ws-schema";
 import React from "react";
 import { CSSTransition, SwitchTransition, TransitionGroup } from "react-transition-group";
 import FieldScene from "./scenes/field";
@@ -15,6 +15,7 @@ import MatchResultsScene from "./scenes/match-results";
 import AwardScene from "./scenes/award";
 import { usePrevious } from "../support/util";
 import MatchPlayScene from "./scenes/match-play";
+import TimeoutScene from "./scenes/timeout";
 import { playSound } from "./utils";
 
 function withDefaultTransition(key: string, children: React.ReactNode) {
@@ -35,9 +36,11 @@ export default function AudienceDisplay() {
   const [ playoffMode, setPlayoffMode ] = useState<PlayoffMode>();
   const [ nextMatch, setNextMatch ] = useState<Match | null>(null);
   const [ alliances, setAlliances ] = useState<PlayoffAlliance[]>([]);
+  const [ selection, setSelection ] = useState<AllianceSelection>();
   const [ awards, setAwards ] = useState<Award[]>([]);
   const [ currentScore, setCurrentScore ] = useState<MatchScoreSnapshot>();
   const [ arenaState, setArenaState ] = useState<ArenaState>();
+  const [ activeTimeout, setActiveTimeout ] = useState<SerialisedTimeout | null>(null);
   const lastState = usePrevious(arenaState);
 
   const { call, subscribe, unsubscribe } = useWebsocket();
@@ -61,9 +64,11 @@ export default function AudienceDisplay() {
       subscribe<"scoring/rankings">("scoring/rankings", setRankings),
       subscribe<"arena/stations">("arena/stations", setStations),
       subscribe<"alliances/alliances">("alliances/alliances", setAlliances),
+      subscribe<"alliances/selection">("alliances/selection", setSelection),
       subscribe<"awards/awards">("awards/awards", setAwards),
       subscribe<"scoring/current">("scoring/current", setCurrentScore),
       subscribe<"arena/state">("arena/state", setArenaState),
+      subscribe<"arena/timeout">("arena/timeout", setActiveTimeout),
     ];
 
     refreshPlayoffMode();
@@ -100,7 +105,8 @@ export default function AudienceDisplay() {
         : scene.scene === "MatchPlay" ? withDefaultTransition("MatchPlay", <MatchPlayScene eventDetails={eventDetails} currentMatch={currentMatch} matches={matches} teams={teams} stations={stations} currentScore={currentScore} arenaState={arenaState} />)
         : scene.scene === "MatchResults" ? withDefaultTransition("MatchResults", <MatchResultsScene match_id={scene.params} eventDetails={eventDetails} teams={teams} matches={matches} />)
         : scene.scene === "PlayoffBracket" ? withDefaultTransition("PlayoffBracket", <PlayoffBracketScene eventDetails={eventDetails} matches={matches} teams={teams} playoff_mode={playoffMode?.mode} next_match={nextMatch || undefined} />)
-        : scene.scene === "AllianceSelection" ? withDefaultTransition("AllianceSelection", <AllianceSelectionScene eventDetails={eventDetails} alliances={alliances} teams={teams} rankings={rankings} />)
+        : scene.scene === "AllianceSelection" ? withDefaultTransition("AllianceSelection", <AllianceSelectionScene eventDetails={eventDetails} alliances={alliances} teams={teams} rankings={rankings} selection={selection} />)
+        : scene.scene === "Timeout" ? withDefaultTransition("Timeout", <TimeoutScene eventDetails={eventDetails} timeout={activeTimeout} />)
         : scene.scene === "Award" ? withDefaultTransition("Award", <AwardScene award_id={scene.params} eventDetails={eventDetails} teams={teams} awards={awards} />)
         : <React.Fragment />
       }
@@ -1,4 +1,4 @@
-import { EventDetails, PlayoffAlliance, Team, TeamRanking } from "@/app/ws-schema";
+import { AllianceSelection, EventDetails, PlayoffAlliance, Team, TeamRanking } from "@/app/ws-schema";
 import AudienceCard from "../card";
 import { Col, Row } from "react-bootstrap";
 
@@ -6,10 +6,15 @@ interface AllianceSelectionSceneProps {
   eventDetails: EventDetails,
   alliances: PlayoffAlliance[],
   rankings: TeamRanking[],
-  teams: Team[]
+  teams: Team[],
+  selection?: AllianceSelection
 }
 
-export default function AllianceSelectionScene({ eventDetails, alliances, rankings, teams }: AllianceSelectionSceneProps) {
+export default function AllianceSelectionScene({ eventDetails, alliances, rankings, teams, selection }: AllianceSelectionSceneProps) {
+  const last_action = selection?.history[selection.history.length - 1]?.action;
+  const last_pick = last_action?.action === "Pick" ? last_action.team : undefined;
+  const picking = selection?.active ? selection.picking : undefined;
+
   const chosen = alliances.flatMap(a => a.teams).filter(x => !!x);
   const remaining = rankings.filter(r => !chosen.includes(r.team)).map((r, i) => (
     { team: r.team, rank: i + 1 }
@@ -30,10 +35,11 @@ export default function AllianceSelectionScene({ eventDetails, alliances, rankin
           <Col> Pick 3 </Col>
         </Row>
         {
-          alliances.map((alliance, i) => <Row key={i}>
+          alliances.map((alliance, i) => <Row key={i} className={alliance.number === picking ? "picking" : undefined}>
             <Col md={1}> { alliance.number } </Col>
             {
-              [0, 1, 2, 3].map(i => <Col key={i}>
+              // Keyed on the history length so the animation replays for every pick
+              [0, 1, 2, 3].map(i => <Col key={last_pick && alliance.teams[i] === last_pick ? `${i}-${selection?.history.length}` : i} className={last_pick && alliance.teams[i] === last_pick ? "just-picked" : undefined}>
                 { teams.find(t => t.number === alliance.teams[i])?.display_number || alliance.teams[i] }
               </Col>)
             }
@@ -43,7 +49,7 @@ export default function AllianceSelectionScene({ eventDetails, alliances, rankin
       <Col className="alliance-remaining">
         <Row className="flex-wrap">
           {
-            remaining.map((r, i) => <Col md="auto" key={i}>
+            remaining.map((r, i) => <Col md="auto" key={i} className={selection?.declined.includes(r.team) ? "declined" : undefined}>
               <span className="rank">{ r.rank }</span>: { teams.find(t => t.number === r.team)?.display_number || r.team }
             </Col>)
           }
@@ -0,0 +1,19 @@
+import { EventDetails, SerialisedTimeout } from "@/app/ws-schema";
+import AudienceCard from "../card";
+import { Col, Row } from "react-bootstrap";
+import { formatCountdown, timeoutName } from "@/app/field-control/timeout_control";
+
+export default function TimeoutScene({ timeout, eventDetails }: { timeout: SerialisedTimeout | null, eventDetails: EventDetails }) {
+  return <AudienceCard event_name={eventDetails.event_name} logo={eventDetails.event_logo}>
+    <Row>
+      <Col className="audience-card-title" md="auto">
+        { timeout ? timeoutName(timeout.timeout) : "Timeout" }
+      </Col>
+    </Row>
+    <Row className="audience-timeout">
+      <Col>
+        { timeout ? formatCountdown(timeout.remaining) : "0:00" }
+      </Col>
+    </Row>
+  </AudienceCard>
+}
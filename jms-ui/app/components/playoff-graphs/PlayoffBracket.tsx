@@ -64,9 +64,23 @@ const POS: { [k in PlayoffModeType]: { [k in MatchType]: { [k: number]: { x: num
     "Final": {
       1: [ { x: 1250, y: 275 } ]
     }
+  },
+  // Round Robin positions depend on the number of alliances, see roundRobinPosition
+  "RoundRobin": {
+    "Test": {},
+    "Qualification": {},
+    "Playoff": {},
+    "Final": {}
   }
 };
 
+const roundRobinPosition = (matches: Match[], ty: MatchType, round: number, set: number) => {
+  const n_rounds = _.max(matches.filter(m => m.match_type === "Playoff").map(m => m.round)) || 0;
+  if (ty === "Final")
+    return { x: n_rounds * 250, y: 0 };
+  return { x: (round - 1) * 250, y: (set - 1) * 75 };
+};
+
 const EDGES: { [k in PlayoffModeType]: { src: [MatchType, number, number], dst: [ MatchType, number, number, Alliance] }[] } = {
   "Bracket": [
     { src: ["Playoff", 1, 1], dst: ["Playoff", 2, 1, "red"] },
@@ -100,7 +114,8 @@ const EDGES: { [k in PlayoffModeType]: { src: [MatchType, number, number], dst:
 
     // Round 5
     { src: ["Playoff", 5, 1], dst: [ "Final", 1, 1, "blue" ] },
-  ]
+  ],
+  "RoundRobin": []
 }
 
 export default function PlayoffBracketGraph({ matches, next_match, dark_mode, playoff_mode, teams }: { matches: Match[], next_match?: Match, dark_mode?: boolean, playoff_mode: PlayoffModeType, teams?: Team[] }) {
@@ -128,7 +143,7 @@ export default function PlayoffBracketGraph({ matches, next_match, dark_mode, pl
           played: played,
           ready: ready
         },
-        position: {
+        position: playoff_mode === "RoundRobin" ? roundRobinPosition(filt_matches, ty, round, set) : {
           x: POS[playoff_mode]?.[ty]?.[round]?.[set - 1]?.x,
           y: POS[playoff_mode]?.[ty]?.[round]?.[set - 1]?.y,
         }
@@ -6,8 +6,9 @@ import { useToasts } from "@/app/support/errors";
 import { withPermission } from "@/app/support/permissions"
 import { nullIfEmpty } from "@/app/support/strings";
 import { useWebsocket } from "@/app/support/ws-component";
-import { Match, MatchLog, SupportTicket, Team } from "@/app/ws-schema";
-import { faCheck, faChevronCircleLeft, faTimes, faUserCheck, faUserSlash } from "@fortawesome/free-solid-svg-icons";
+import { Match, MatchLog, MatchLogFormat, SupportTicket, Team } from "@/app/ws-schema";
+import { openReport } from "@/app/support/util";
+import { faCheck, faChevronCircleLeft, faDownload, faTimes, faUserCheck, faUserSlash } from "@fortawesome/free-solid-svg-icons";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
 import moment from "moment";
 import Link from "next/link";
@@ -126,6 +127,17 @@ export default withPermission(["Ticketing"], function CSATicket({ params }: { pa
         }
       </Col>
       <Col md={9}>
+        {
+          matchLog && <div className="text-end mb-2">
+            {
+              (["DSLog", "CSV"] as MatchLogFormat[]).map(format => <Button key={format} className="ms-2" size="sm" variant="secondary" onClick={() => {
+                call<"tickets/export_match_log">("tickets/export_match_log", { match_id: matchLog.match_id, team: matchLog.team, format }).then(openReport).catch(addError)
+              }}>
+                <FontAwesomeIcon icon={faDownload} /> &nbsp; { format === "DSLog" ? ".dslog" : "CSV" }
+              </Button>)
+            }
+          </div>
+        }
         {
           matchLog ? <MatchLogView matchLog={matchLog} /> : <h3>No Match Log Available</h3>
         }
@@ -4,18 +4,19 @@ import "./fta.scss";
 import { PermissionGate, withPermission } from "@/app/support/permissions"
 import JmsWebsocket from "@/app/support/ws";
 import { useWebsocket } from "@/app/support/ws-component";
-import { AllianceStation, AllianceStationId, AllianceStationUpdate, ArenaState, DriverStationReport, Match, SerialisedLoadedMatch, SupportTicket, Team } from "@/app/ws-schema";
+import { AllianceStation, AllianceStationId, AllianceStationUpdate, ArenaState, DiagnosisCode, DriverStationConnection, DriverStationReport, DriverStationVersionStatus, Match, SerialisedLoadedMatch, SupportTicket, Team } from "@/app/ws-schema";
 import { IconDefinition } from "@fortawesome/fontawesome-svg-core";
 import { faBattery, faCheck, faCode, faFlag, faNetworkWired, faRobot, faSign, faTimes, faWifi } from "@fortawesome/free-solid-svg-icons";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
 import _ from "lodash";
 import React, { useEffect, useState } from "react";
-import { Button, Card, Col, InputGroup, ListGroup, Row } from "react-bootstrap";
+import { Alert, Button, Card, Col, InputGroup, ListGroup, Row } from "react-bootstrap";
 import { capitalise } from "@/app/support/strings";
 import { useToasts } from "@/app/support/errors";
 import update from "immutability-helper";
 import BufferedFormControl from "@/app/components/BufferedFormControl";
-import { MatchFlow } from "../match_flow";
+import { MatchFlow, describeStopFault } from "../match_flow";
+import { TimeoutControl } from "../timeout_control";
 import MatchScheduleControl from "../../match_schedule";
 import { newTicketModal } from "@/app/csa/tickets";
 import FloatingActionButton from "@/app/components/FloatingActionButton";
@@ -29,6 +30,8 @@ export default withPermission(["FTA", "FTAA"], function FTAView() {
 
   const [ allianceStations, setAllianceStations ] = useState<AllianceStation[]>([]);
   const [ dsReports, setDsReports ] = useState<{ [k: number]: DriverStationReport }>({});
+  const [ dsVersions, setDsVersions ] = useState<{ [k: number]: DriverStationVersionStatus }>({});
+  const [ dsConnections, setDsConnections ] = useState<DriverStationConnection[]>([]);
   const [ state, setState ] = useState<ArenaState>({ state: "Init" });
   const [ currentMatch, setCurrentMatch ] = useState<SerialisedLoadedMatch | null>(null);
   const [ matches, setMatches ] = useState<Match[]>([]);
@@ -42,6 +45,8 @@ export default withPermission(["FTA", "FTAA"], function FTAView() {
     let cb = [
       subscribe<"arena/stations">("arena/stations", setAllianceStations),
       subscribe<"arena/ds">("arena/ds", (reports) => setDsReports(_.keyBy(reports, "team"))),
+      subscribe<"arena/ds_connections">("arena/ds_connections", setDsConnections),
+      subscribe<"arena/ds_versions">("arena/ds_versions", (versions) => setDsVersions(_.keyBy(versions, v => v.versions.team))),
       subscribe<"arena/state">("arena/state", setState),
       subscribe<"arena/current_match">("arena/current_match", setCurrentMatch),
       subscribe<"matches/matches">("matches/matches", setMatches),
@@ -74,7 +79,9 @@ export default withPermission(["FTA", "FTAA"], function FTAView() {
     }
   }
 
-  const stations = _.zip(allianceStations, stationReports).map(([stn, report], i) => [stn!.id, <FTAAllianceStation key={i} station={stn!} match_id={currentMatch?.match_id} report={report || null} call={call} addError={addError} teams={teams} />] as [AllianceStationId, React.ReactNode]);
+  const latchedStops = allianceStations.filter(s => s.physical_estop || s.stop_fault);
+
+  const stations = _.zip(allianceStations, stationReports).map(([stn, report], i) => [stn!.id, <FTAAllianceStation key={i} station={stn!} match_id={currentMatch?.match_id} report={report || null} versions={stn!.team ? dsVersions[stn!.team] : undefined} connection={stationConnection(stn!, dsConnections)} call={call} addError={addError} teams={teams} />] as [AllianceStationId, React.ReactNode]);
   const landscape = width > height;
 
   return <div style={{ marginLeft: '1em', marginRight: '1em' }}>
@@ -96,10 +103,35 @@ export default withPermission(["FTA", "FTAA"], function FTAView() {
         </Row>
       })
     }
+    {
+      latchedStops.length > 0 && <Alert variant="danger" className="mt-3">
+        <Row>
+          <Col>
+            {
+              latchedStops.map((s, i) => <div key={i}>
+                <strong>{ capitalise(s.id.alliance) } { s.id.station }:</strong> &nbsp;
+                { s.physical_estop && "Physical E-Stop latched. " }
+                { s.stop_fault && describeStopFault(s.stop_fault) }
+              </div>)
+            }
+          </Col>
+          <PermissionGate permissions={["FTA"]}>
+            <Col md="auto">
+              <Button variant="danger" onClick={() => call<"electronics/reset_estops">("electronics/reset_estops", null).catch(addError)}>
+                Reset Physical Stops
+              </Button>
+            </Col>
+          </PermissionGate>
+        </Row>
+      </Alert>
+    }
     <PermissionGate permissions={["FTA", "Scorekeeper"]}>
       <Row className="mt-3">
         <MatchFlow state={state} current_match={currentMatch} />
       </Row>
+      <div className="mt-3">
+        <TimeoutControl state={state} />
+      </div>
     </PermissionGate>
     <br />
     <MatchScheduleControl currentMatch={currentMatch || undefined} matches={matches} isLoadDisabled={state.state !== "Idle"} canLoad teams={teams} />
@@ -113,9 +145,14 @@ export default withPermission(["FTA", "FTAA"], function FTAView() {
   </div>
 });
 
-function FTAAllianceStation({ station, report, match_id, call, addError, teams }: { station: AllianceStation, report: DriverStationReport | null, match_id?: string, call: JmsWebsocket["call"], addError: (e: string) => void , teams?: Team[] }) {
+// The DS connection for a station, if it belongs to the team that's meant to be there
+function stationConnection(station: AllianceStation, connections: DriverStationConnection[]) {
+  return connections.find(c => c.station.alliance === station.id.alliance && c.station.station === station.id.station && c.team === station.team);
+}
+
+function FTAAllianceStation({ station, report, versions, connection, match_id, call, addError, teams }: { station: AllianceStation, report: DriverStationReport | null, versions?: DriverStationVersionStatus, connection?: DriverStationConnection, match_id?: string, call: JmsWebsocket["call"], addError: (e: string) => void , teams?: Team[] }) {
   const [ tickets, setTickets ] = useState<SupportTicket[]>([]);
-  const diagnosis = ftaDiagnosis(station, report);
+  const diagnosis = ftaDiagnosis(station, report, versions, connection);
 
   const display_team = teams?.find(x => x.number === station.team)?.display_number;
 
@@ -129,7 +166,7 @@ function FTAAllianceStation({ station, report, match_id, call, addError, teams }
     }
   }, [ station.team ]);
 
-  return <Col onClick={() => editStationModal(station, match_id, tickets, call, addError)} className="fta-alliance-station-col" data-alliance={station.id.alliance} data-bypass={station.bypass} data-estop={station.estop} data-astop={station.astop}>
+  return <Col onClick={() => editStationModal(station, match_id, tickets, versions, connection, call, addError)} className="fta-alliance-station-col" data-alliance={station.id.alliance} data-bypass={station.bypass} data-estop={station.estop} data-astop={station.astop}>
     <Row className="mx-0">
       <Col md="auto"> <FTATeamIndicator ok={report?.rio_ping} icon={faRobot} /> </Col>
       <Col className="fta-alliance-station-team" data-has-administrative={station.team && display_team !== ("" + station.team)}>
@@ -169,23 +206,37 @@ function FTAAllianceStation({ station, report, match_id, call, addError, teams }
   </Col>
 }
 
-export function ftaDiagnosis(station: AllianceStation, report: DriverStationReport | null) {
+// Mirrors DiagnosisCode::code(), so the FTA view matches the alliance station panels
+export const DIAGNOSIS_CODES: { [k in DiagnosisCode]: { code: string, name: string, unit?: string } } = {
+  Estop: { code: "R-EST", name: "Robot E-Stopped" },
+  WrongStation: { code: "MOVE", name: "Wrong Station" },
+  Radio: { code: "RADIO", name: "No Radio" },
+  Rio: { code: "RIO", name: "No RoboRIO" },
+  Code: { code: "CODE", name: "No Robot Code" },
+  HighLatency: { code: "L8NC", name: "High Latency", unit: "ms" },
+  PacketLoss: { code: "LOSS", name: "Packet Loss", unit: "%" },
+  LowBattery: { code: "LBATT", name: "Low Battery", unit: "V" },
+  Brownout: { code: "BRWN", name: "Brownout" },
+};
+
+export function ftaDiagnosis(station: AllianceStation, report: DriverStationReport | null, versions?: DriverStationVersionStatus, connection?: DriverStationConnection) {
   if (station.bypass) return "BYP";
   if (station.astop) return "ASTOP";
   if (station.estop) return "ESTOP";
-  if (report?.estop) return "R-EST";
+  if (station.stop_fault) return "STPFLT";
 
   if (station.team === null) return "NOTEAM";
 
-  if (report === null) return "NODS";
-  if (report.actual_station !== null && station.id.alliance !== report.actual_station?.alliance && station.id.station != report.actual_station?.station) return "MOVE";
-
-  if (!report.radio_ping) return "NORAD";
-  if (!report.rio_ping) return "NORIO";
-  if (!report.robot_ping) return "NOCODE";
+  if (report === null) {
+    if (connection?.connected && connection.status === "Bad") return "MOVE";
+    if (connection?.connected && connection.status === "Waiting") return "DSWAIT";
+    if (connection && !connection.connected) return "DSDC";
+    return "NODS";
+  }
 
-  if (report.rtt > 100) return "L8NC";
-  if (report.battery_voltage < 9) return "LBATT";
+  // Worked out by the DS service, using the rules in the Driver Station settings
+  if (report.diagnosis) return DIAGNOSIS_CODES[report.diagnosis].code;
+  if (versions && versions.outdated.length > 0) return "OLDSW";
 
   return null;
 }
@@ -225,7 +276,7 @@ class FTATeamIndicator extends React.PureComponent<FTATeamIndicatorProps> {
   }
 }
 
-async function editStationModal(station: AllianceStation, match_id: string | undefined, tickets: SupportTicket[], call: JmsWebsocket["call"], addError: (e: string) => void) {
+async function editStationModal(station: AllianceStation, match_id: string | undefined, tickets: SupportTicket[], versions: DriverStationVersionStatus | undefined, connection: DriverStationConnection | undefined, call: JmsWebsocket["call"], addError: (e: string) => void) {
   let new_station = await confirmModal("", {
     title: `Edit ${capitalise(station.id.alliance)} ${station.id.station}`,
     data: station,
@@ -257,6 +308,26 @@ async function editStationModal(station: AllianceStation, match_id: string | und
         </Button> }
       </Col>
       <Col>
+        {
+          connection && <React.Fragment>
+            <h5> Connection </h5>
+            <p className="mb-1">
+              { connection.connected ? "Connected" : "Disconnected" } from { connection.address } { connection.ip_team !== null && connection.ip_team !== connection.team && `(IP is for team ${connection.ip_team})` }
+            </p>
+            <p className={connection.connected && connection.status === "Good" ? "text-muted" : "text-danger"}>
+              Station Status: { connection.status } &nbsp;|&nbsp; {
+                connection.connected ? `Connected ${moment(connection.connected_at).fromNow()}` : `Disconnected ${moment(connection.disconnected_at).fromNow()} (${connection.disconnect_reason})`
+              }
+            </p>
+          </React.Fragment>
+        }
+        {
+          versions && <React.Fragment>
+            <h5> Software </h5>
+            <p className="mb-1"> DS: { versions.versions.ds || "Unknown" } &nbsp;|&nbsp; RIO: { versions.versions.rio || "Unknown" } &nbsp;|&nbsp; WPILib: { versions.versions.wpilib || "Unknown" } </p>
+            { versions.outdated.length > 0 && <p className="text-danger"> Out of date: { versions.outdated.join(", ") } </p> }
+          </React.Fragment>
+        }
         <h5> Previous Issues </h5>
         { tickets.length > 0 ? <Paginate itemsPerPage={5}>
           {
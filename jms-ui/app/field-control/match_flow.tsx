@@ -1,14 +1,25 @@
 import "./match_flow.scss";
-import React, { useState } from "react";
-import { Button, ButtonProps, Col, Row } from "react-bootstrap";
-import { ArenaSignal, ArenaState, SerialisedLoadedMatch } from "../ws-schema";
-import { withPermission } from "../support/permissions";
+import React, { useEffect, useState } from "react";
+import { Alert, Button, ButtonProps, Col, Row } from "react-bootstrap";
+import { AllianceStationId, ArenaSignal, ArenaState, ArmBlocker, SerialisedLoadedMatch, StopButtonFault } from "../ws-schema";
+import { user_has_permission, withPermission } from "../support/permissions";
+import { withConfirm } from "../components/Confirm";
 import { useWebsocket } from "../support/ws-component";
 import { useToasts } from "../support/errors";
 
 export function MatchFlow({ state, current_match }: { state: ArenaState, current_match: SerialisedLoadedMatch | null }) {
-  const { call } = useWebsocket();
+  const { call, subscribe, unsubscribe, user } = useWebsocket();
   const { addError } = useToasts();
+  const [ blockers, setBlockers ] = useState<ArmBlocker[]>([]);
+
+  useEffect(() => {
+    let cbs = [
+      subscribe<"arena/arm_blockers">("arena/arm_blockers", setBlockers)
+    ];
+    return () => unsubscribe(cbs);
+  }, []);
+
+  const canForce = user != null && user_has_permission(["FTA"], user);
   
   const signal = (signal: ArenaSignal) => {
     call<"arena/signal">("arena/signal", { signal })
@@ -28,76 +39,115 @@ export function MatchFlow({ state, current_match }: { state: ArenaState, current
       </Col>
     </Row>
 
-  return <Row className="match-flow">
-    <Col>
-      <MatchFlowButton
-        arenaState={state}
-        targetState="Prestart"
-        onClick={() => signal(state?.state == "Prestart" ? "PrestartUndo" : "Prestart")}
-        disabled={!(current_match && ((state?.state === "Idle") || (state?.state === "Prestart")))}
-      >
-        { state?.state == "Prestart" ? "Revert Prestart" : "Prestart Match" }
-      </MatchFlowButton>
-    </Col>
-    <Col>
-      <Button
-        className="match-flow-btn"
-        data-target="MatchPreview"
-        variant="warning"
-        disabled={!current_match}
-        onClick={() => call<"audience/set">("audience/set", { scene: { scene: "MatchPreview" } }).catch(addError)}
-      >
-        Match Preview
-      </Button>
-    </Col>
-    <Col>
-      <MatchFlowButton 
-        arenaState={state} 
-        targetState="MatchArmed"
-        variant="hazard-yellow"
-        onClick={() => signal({ MatchArm: { force: false } })}
-        disabled={!(state?.state === "Prestart")}
-      >
-        Arm Match
-      </MatchFlowButton>
-    </Col>
-    <Col>
-      <MatchFlowButton
-        arenaState={state}
-        targetState="MatchPlay"
-        variant="hazard-yellow"
-        onClick={() => {
-          call<"audience/set">("audience/set", { scene: { scene: "MatchPlay" } });
-          signal("MatchPlay");
-        }}
-        disabled={state?.state !== "MatchArmed"}
-      >
-        Match Play
-      </MatchFlowButton>
-    </Col>
-    <Col>
-      <MatchFlowButton
-        arenaState={state}
-        onClick={() => signal("MatchCommit")}
-        disabled={state?.state !== "MatchComplete"}
-      >
-        Commit Scores
-      </MatchFlowButton>
-    </Col>
-    <Col>
-      <Button
-        className="match-flow-btn"
-        data-target="MatchShowScores"
-        variant="warning"
-        disabled={state?.state !== "Idle"}
-        onClick={() => call<"audience/set_latest_scores">("audience/set_latest_scores", {}).catch(addError)}
-      >
-        Show Scores
-      </Button>
-    </Col>
-  </Row>
+  return <React.Fragment>
+    <Row className="match-flow">
+      <Col>
+        <MatchFlowButton
+          arenaState={state}
+          targetState="Prestart"
+          onClick={() => signal(state?.state == "Prestart" ? "PrestartUndo" : "Prestart")}
+          disabled={!(current_match && ((state?.state === "Idle") || (state?.state === "Prestart")))}
+        >
+          { state?.state == "Prestart" ? "Revert Prestart" : "Prestart Match" }
+        </MatchFlowButton>
+      </Col>
+      <Col>
+        <Button
+          className="match-flow-btn"
+          data-target="MatchPreview"
+          variant="warning"
+          disabled={!current_match}
+          onClick={() => call<"audience/set">("audience/set", { scene: { scene: "MatchPreview" } }).catch(addError)}
+        >
+          Match Preview
+        </Button>
+      </Col>
+      <Col>
+        <MatchFlowButton 
+          arenaState={state} 
+          targetState="MatchArmed"
+          variant="hazard-yellow"
+          onClick={() => {
+            if (blockers.length > 0 && canForce)
+              withConfirm(() => signal({ MatchArm: { force: true } }), <React.Fragment>
+                <p> The following will be overridden and recorded in the audit log: </p>
+                <ul> { blockers.map((b, i) => <li key={i}> { describeBlocker(b) } </li>) } </ul>
+              </React.Fragment>, { title: "Force Arm Match?", okText: "Force Arm" });
+            else
+              signal({ MatchArm: { force: false } });
+          }}
+          disabled={!(state?.state === "Prestart") || (blockers.length > 0 && !canForce)}
+        >
+          { blockers.length > 0 && canForce ? "Force Arm" : "Arm Match" }
+        </MatchFlowButton>
+      </Col>
+      <Col>
+        <MatchFlowButton
+          arenaState={state}
+          targetState="MatchPlay"
+          variant="hazard-yellow"
+          onClick={() => {
+            call<"audience/set">("audience/set", { scene: { scene: "MatchPlay" } });
+            signal("MatchPlay");
+          }}
+          disabled={state?.state !== "MatchArmed"}
+        >
+          Match Play
+        </MatchFlowButton>
+      </Col>
+      <Col>
+        <MatchFlowButton
+          arenaState={state}
+          onClick={() => signal("MatchCommit")}
+          disabled={state?.state !== "MatchComplete"}
+        >
+          Commit Scores
+        </MatchFlowButton>
+      </Col>
+      <Col>
+        <Button
+          className="match-flow-btn"
+          data-target="MatchShowScores"
+          variant="warning"
+          disabled={state?.state !== "Idle"}
+          onClick={() => call<"audience/set_latest_scores">("audience/set_latest_scores", {}).catch(addError)}
+        >
+          Show Scores
+        </Button>
+      </Col>
+    </Row>
+    {
+      state?.state === "Prestart" && blockers.length > 0 && <Alert variant="warning" className="mt-2 mb-0 py-2">
+        <strong>Can't Arm:</strong> { blockers.map(describeBlocker).join(", ") }
+      </Alert>
+    }
+  </React.Fragment>
 };
 
+const stationName = (s: AllianceStationId) => `${s.alliance} ${s.station}`;
+
+export function describeBlocker(b: ArmBlocker) {
+  switch (b.blocker) {
+    case "NoDriverStation": return `${stationName(b.station)} (${b.team}): Driver Station not connected`;
+    case "DriverStationNotReady": return `${stationName(b.station)} (${b.team}): Driver Station ${b.reason}`;
+    case "NoRobotComms": return `${stationName(b.station)} (${b.team}): No robot comms`;
+    case "WrongStation": return `${stationName(b.station)} (${b.team}): Driver Station is in ${stationName(b.actual)}`;
+    case "EntryCondition": return `Field entry condition is ${b.condition}`;
+    case "HookPending": return `Waiting on ${b.hook}`;
+    case "HookFailed": return `${b.hook} failed: ${b.reason}`;
+    case "PhysicalStop": return `${stationName(b.station)}: Physical E-Stop needs to be reset`;
+    case "StopFault": return `${stationName(b.station)}: ${describeStopFault(b.fault)}`;
+  }
+}
+
+export function describeStopFault(fault: StopButtonFault) {
+  switch (fault) {
+    case "EstopCircuitOpen": return "E-Stop wiring open";
+    case "AstopCircuitOpen": return "A-Stop wiring open";
+    case "Offline": return "Stop buttons offline";
+  }
+}
+
 class MatchFlowButton extends React.PureComponent<ButtonProps & { arenaState?: ArenaState, targetState?: ArenaState["state"] }> {
   render() {
     let { arenaState, targetState, className, ...props } = this.props;
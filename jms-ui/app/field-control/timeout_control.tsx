@@ -0,0 +1,84 @@
+import React, { useEffect, useState } from "react";
+import { Button, Col, InputGroup, Row } from "react-bootstrap";
+import { ArenaState, PlayoffAlliance, SerialisedTimeout, Timeout } from "../ws-schema";
+import { useWebsocket } from "../support/ws-component";
+import { useToasts } from "../support/errors";
+import BufferedFormControl from "../components/BufferedFormControl";
+import { withConfirm } from "../components/Confirm";
+
+export function timeoutName(timeout: Timeout) {
+  return timeout.kind.type === "Field" ? "Field Timeout" : `Alliance ${timeout.kind.alliance} Timeout`;
+}
+
+export function formatCountdown(ms: number) {
+  const secs = Math.ceil(ms / 1000);
+  return `${Math.floor(secs / 60)}:${(secs % 60).toString().padStart(2, "0")}`;
+}
+
+export function TimeoutControl({ state }: { state: ArenaState }) {
+  const [ activeTimeout, setActiveTimeout ] = useState<SerialisedTimeout | null>(null);
+  const [ timeouts, setTimeouts ] = useState<Timeout[]>([]);
+  const [ alliances, setAlliances ] = useState<PlayoffAlliance[]>([]);
+  const [ fieldMinutes, setFieldMinutes ] = useState<number>(5);
+
+  const { call, subscribe, unsubscribe } = useWebsocket();
+  const { addError } = useToasts();
+
+  useEffect(() => {
+    let cbs = [
+      subscribe<"arena/timeout">("arena/timeout", setActiveTimeout),
+      subscribe<"arena/timeouts">("arena/timeouts", setTimeouts),
+      subscribe<"alliances/alliances">("alliances/alliances", setAlliances),
+    ];
+    return () => unsubscribe(cbs);
+  }, []);
+
+  if (activeTimeout)
+    return <Row className="align-items-center">
+      <Col md="auto">
+        <h4 className="m-0"> { timeoutName(activeTimeout.timeout) }: { formatCountdown(activeTimeout.remaining) } </h4>
+      </Col>
+      <Col md="auto">
+        <Button variant="danger" onClick={() => withConfirm(() => call<"arena/end_timeout">("arena/end_timeout", null).catch(addError))}>
+          End Timeout
+        </Button>
+      </Col>
+    </Row>
+
+  const idle = state.state === "Idle";
+  const used = (alliance: number) => timeouts.some(t => t.kind.type === "Alliance" && t.kind.alliance === alliance);
+
+  return <Row>
+    <Col md="auto">
+      <InputGroup>
+        <BufferedFormControl
+          auto
+          style={{ maxWidth: '6em' }}
+          type="number"
+          min={1}
+          value={fieldMinutes}
+          onUpdate={v => setFieldMinutes(Math.max(1, v as number))}
+        />
+        <InputGroup.Text>mins</InputGroup.Text>
+        <Button
+          variant="warning"
+          disabled={!idle}
+          onClick={() => call<"arena/start_timeout">("arena/start_timeout", { kind: { type: "Field" }, duration: fieldMinutes * 60 * 1000 }).catch(addError)}
+        >
+          Field Timeout
+        </Button>
+      </InputGroup>
+    </Col>
+    {
+      alliances.filter(a => a.teams.length > 0).map(a => <Col md="auto" key={a.number}>
+        <Button
+          variant="secondary"
+          disabled={!idle || used(a.number)}
+          onClick={() => withConfirm(() => call<"arena/start_timeout">("arena/start_timeout", { kind: { type: "Alliance", alliance: a.number }, duration: null }).catch(addError))}
+        >
+          A{ a.number } Timeout { used(a.number) && "(used)" }
+        </Button>
+      </Col>)
+    }
+  </Row>
+}
@@ -1,8 +1,18 @@
 import React, { useEffect, useRef, useState } from "react";
-import { MatchLog } from "../ws-schema";
-import { Col, Row } from "react-bootstrap";
+import { LossCause, MatchLog, MatchPlayState } from "../ws-schema";
+import { Col, Row, Table } from "react-bootstrap";
 import { Area, CartesianGrid, ComposedChart, Legend, Line, ReferenceArea, Tooltip, XAxis, YAxis } from "recharts";
 
+const LOSS_CAUSES: { [k in LossCause]: string } = {
+  NoDriverStation: "DS Disconnected",
+  Radio: "Radio Lost",
+  Rio: "RIO Lost",
+  Code: "Code Lost",
+  HighLatency: "High Latency",
+  LowBattery: "Low Battery",
+  Brownout: "Brownout",
+};
+
 export default function MatchLogView({ matchLog }: { matchLog: MatchLog }) {
   const ref = useRef<HTMLDivElement>(null);
 
@@ -21,6 +31,21 @@ export default function MatchLogView({ matchLog }: { matchLog: MatchLog }) {
     setTimeout(() => recalcSize(), 100);
   }, [])
 
+  // The phases are taken from the log itself, since the match timing can differ between match types and events.
+  // Older logs don't record a phase, but were only ever captured with the default timing.
+  const phaseSpan = (phase: MatchPlayState, fallback: [number, number]): [number, number] | undefined => {
+    if (!matchLog.timeseries.some(e => e.phase)) return fallback;
+    const times = matchLog.timeseries.filter(e => e.phase === phase).map(e => e.time / 1000);
+    return times.length > 0 ? [times[0], times[times.length - 1]] : undefined;
+  };
+  const auto = phaseSpan("Auto", [0, 15]);
+  const teleop = phaseSpan("Teleop", [16, 135]);
+
+  const renderPhases = (scale: [number, number], axisId?: string) => <React.Fragment>
+    { auto && <ReferenceArea yAxisId={axisId} x1={auto[0]} x2={auto[1]} y1={scale[0]} y2={scale[1]} fill="purple" fillOpacity={0.15} /> }
+    { teleop && <ReferenceArea yAxisId={axisId} x1={teleop[0]} x2={teleop[1]} y1={scale[0]} y2={scale[1]} fill="orange" fillOpacity={0.15} /> }
+  </React.Fragment>
+
   const renderReferences = (scale: [number, number], axisId?: string) => {
     return <React.Fragment>
       <defs>
@@ -32,14 +57,37 @@ export default function MatchLogView({ matchLog }: { matchLog: MatchLog }) {
         </linearGradient>
       </defs>
 
-      <ReferenceArea yAxisId={axisId} x1={0} x2={15} y1={scale[0]} y2={scale[1]} fill="purple" fillOpacity={0.15} />
-      <ReferenceArea yAxisId={axisId} x1={16} x2={135} y1={scale[0]} y2={scale[1]} fill="orange" fillOpacity={0.15} />
-      <Area yAxisId={axisId} dataKey={d => d.report?.estop ? scale[1] : undefined} fill="url(#estop)" strokeWidth="0" legendType="none" tooltipType="none" stroke="red" />
+      { renderPhases(scale, axisId) }
+      <Area yAxisId={axisId} dataKey={d => (d.estop || d.report?.estop) ? scale[1] : undefined} fill="url(#estop)" strokeWidth="0" legendType="none" tooltipType="none" stroke="red" />
 
     </React.Fragment>
   }
 
   return <div ref={ref} style={{ width: "100%", maxWidth: "100%" }}>
+    {
+      matchLog.analysis && matchLog.analysis.summary.length > 0 && <Row className="mb-3">
+        <Col>
+          <Table size="sm" striped>
+            <thead>
+              <tr>
+                <th> Cause </th>
+                <th> Time Lost </th>
+                <th> Occurrences </th>
+              </tr>
+            </thead>
+            <tbody>
+              {
+                matchLog.analysis.summary.map(s => <tr key={s.cause} className={s.exceeds_threshold ? "text-danger" : undefined}>
+                  <td> { LOSS_CAUSES[s.cause] } </td>
+                  <td> { (s.total / 1000).toFixed(1) }s </td>
+                  <td> { s.windows } </td>
+                </tr>)
+              }
+            </tbody>
+          </Table>
+        </Col>
+      </Row>
+    }
     <Row>
       <Col>
         <ComposedChart data={matchLog.timeseries} syncId="record" height={250} width={width}>
@@ -72,10 +120,64 @@ export default function MatchLogView({ matchLog }: { matchLog: MatchLog }) {
           <Line name="Radio"    dataKey={d => Number(d.report?.radio_ping || 0) + 0.04}  strokeOpacity={0.5} strokeWidth={2} stroke="#00e3fc" dot={false} />
           <Line name="RIO"      dataKey={d => Number(d.report?.rio_ping || 0) + 0.06}    strokeOpacity={0.5} strokeWidth={2} stroke="#8f8f8f" dot={false} />
           <Line name="Code"     dataKey={d => Number(d.report?.robot_ping || 0) + 0.08}  strokeOpacity={0.5} strokeWidth={2} stroke="#ffd000" dot={false} />
+          <Line name="E-Stop"   dataKey={d => Number(d.estop || false) + 0.10}  strokeOpacity={0.5} strokeWidth={2} stroke="#ff0000" dot={false} />
+          <Line name="A-Stop"   dataKey={d => Number(d.astop || false) + 0.12}  strokeOpacity={0.5} strokeWidth={2} stroke="#b56cff" dot={false} />
 
           <Legend />
         </ComposedChart>
       </Col>
     </Row>
+    {
+      matchLog.log_data.length > 0 && <Row className="mt-3">
+        <Col>
+          <ComposedChart data={matchLog.log_data} syncId="record" height={250} width={width}>
+            <CartesianGrid />
+            <XAxis type="number" dataKey={d => d.time / 1000} tickCount={20} interval="preserveStartEnd" domain={['dataMin', 'dataMax']} />
+            <YAxis label={{value: "Usage (%)", offset: 20, position: "insideLeft", angle: -90, fill: "#888"}} yAxisId="percent" type="number" domain={[0, 100]} />
+            <YAxis label={{value: "Bandwidth (Mbps) / Signal (dB)", offset: 20, position: "insideRight", angle: -90, fill: "#888"}} yAxisId="radio" type="number" orientation="right" />
+
+            { renderPhases([0, 100], "percent") }
+            <Area name="Brownout" yAxisId="percent" dataKey={d => d.data.brownout ? 100 : undefined} fill="#ff0000" fillOpacity={0.3} strokeWidth={0} />
+            <Area name="Watchdog" yAxisId="percent" dataKey={d => d.data.watchdog ? 100 : undefined} fill="#ffd000" fillOpacity={0.3} strokeWidth={0} />
+
+            <Tooltip formatter={(v: number) => Math.round(v * 100) / 100} contentStyle={ { backgroundColor: "#202020"} } itemStyle={{ paddingBottom: 0 }} />
+
+            <Line name="RIO CPU" yAxisId="percent" dataKey={d => d.data.rio_cpu} strokeWidth={2} stroke="#00e3fc" dot={false} />
+            <Line name="CAN Usage" yAxisId="percent" dataKey={d => d.data.can_usage} strokeWidth={2} stroke="#b56cff" dot={false} />
+            <Line name="Wi-Fi dB" yAxisId="radio" dataKey={d => d.data.wifi_db} strokeWidth={2} stroke="#00e300" dot={false} />
+            <Line name="Bandwidth" yAxisId="radio" dataKey={d => d.data.bandwidth} strokeWidth={2} stroke="#ff8c00" dot={false} />
+
+            <Legend />
+          </ComposedChart>
+        </Col>
+      </Row>
+    }
+    {
+      matchLog.messages.length > 0 && <Row className="mt-3">
+        <Col>
+          <h5> Robot Messages </h5>
+          <Table size="sm" striped>
+            <thead>
+              <tr>
+                <th> Match Time </th>
+                <th> Phase </th>
+                <th> Robot Time </th>
+                <th> Message </th>
+              </tr>
+            </thead>
+            <tbody>
+              {
+                matchLog.messages.map((m, i) => <tr key={i}>
+                  <td> { (m.time / 1000).toFixed(1) }s </td>
+                  <td> { m.phase || "-" } </td>
+                  <td> { new Date(m.message.timestamp).toLocaleTimeString() } </td>
+                  <td className="font-monospace" style={{ whiteSpace: "pre-wrap" }}> { m.message.message } </td>
+                </tr>)
+              }
+            </tbody>
+          </Table>
+        </Col>
+      </Row>
+    }
   </div>
 }
\ No newline at end of file
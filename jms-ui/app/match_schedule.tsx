@@ -1,11 +1,11 @@
 import "./match_schedule.scss";
-import { Button, Col, Row, Table } from "react-bootstrap";
+import { Button, Col, Form, Row, Table } from "react-bootstrap";
 import { Match, SerialisedLoadedMatch, Team } from "./ws-schema";
 import React from "react";
 import moment from "moment";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
-import { faCheck, faTrash } from "@fortawesome/free-solid-svg-icons";
-import { withConfirm } from "./components/Confirm";
+import { faCheck, faRepeat, faTrash } from "@fortawesome/free-solid-svg-icons";
+import { confirmModal, withConfirm } from "./components/Confirm";
 import { useWebsocket } from "./support/ws-component";
 import { useToasts } from "./support/errors";
 
@@ -15,14 +15,31 @@ interface MatchScheduleProps {
   canLoad?: boolean,
   isLoadDisabled?: boolean,
   canDelete?: boolean,
+  canReplay?: boolean,
   filter?: (m: Match) => boolean,
   teams?: Team[]
 }
 
-export default function MatchSchedule({ matches, currentMatch, canLoad, isLoadDisabled, canDelete, filter, teams }: MatchScheduleProps) {
+export default function MatchSchedule({ matches, currentMatch, canLoad, isLoadDisabled, canDelete, canReplay, filter, teams }: MatchScheduleProps) {
   const { call } = useWebsocket();
   const { addError } = useToasts();
 
+  const replay = async (match: Match) => {
+    const reason = await confirmModal<string>("", {
+      title: `Replay ${match.name}`,
+      data: "",
+      okText: "Schedule Replay",
+      okVariant: "warning",
+      renderInner: (data, onUpdate) => <Form.Control
+        type="text"
+        placeholder="Reason for the replay"
+        value={data}
+        onChange={e => onUpdate(e.target.value)}
+      />
+    });
+    call<"matches/replay">("matches/replay", { match_id: match.id, reason }).catch(addError);
+  }
+
   return <React.Fragment>
     {
       canLoad && <Row className="mb-2">
@@ -55,7 +72,10 @@ export default function MatchSchedule({ matches, currentMatch, canLoad, isLoadDi
             {
               matches.filter(filter || ((m: Match) => true)).map(match => <tr key={match.id} className="schedule-row" data-played={match.played}>
                 <td> { moment(match.start_time).format("ddd HH:mm:ss") } </td>
-                <td> { match.name } { match.played && <span className="text-success">&nbsp;<FontAwesomeIcon icon={faCheck} /></span> } </td>
+                <td>
+                  { match.name } { match.played && <span className="text-success">&nbsp;<FontAwesomeIcon icon={faCheck} /></span> }
+                  { match.replay_reason && <small className="text-muted d-block"> { match.replay_reason } </small> }
+                </td>
                 <td data-alliance="blue"> <strong>{ match.blue_alliance ? `#${match.blue_alliance}` : "" }</strong> </td>
                 {
                   match.blue_teams.map((t, i) => <td key={i} data-alliance="blue">{ teams?.find(x => x.number === t)?.display_number || t }</td>)
@@ -68,6 +88,9 @@ export default function MatchSchedule({ matches, currentMatch, canLoad, isLoadDi
                   { canDelete && <Button variant="danger" size="sm" disabled={match.played} onClick={() => withConfirm(() => call<"matches/delete">("matches/delete", { match_id: match.id }).catch(addError))}>
                     <FontAwesomeIcon icon={faTrash} />
                   </Button>}
+                  { canReplay && match.match_type === "Qualification" && <Button variant="warning" size="sm" disabled={!match.played} onClick={() => replay(match).catch(() => {})}>
+                    <FontAwesomeIcon icon={faRepeat} />
+                  </Button>}
                   { canLoad && <Button variant="primary" size="sm" disabled={isLoadDisabled || !match.ready} onClick={() => call<"arena/load_match">("arena/load_match", { match_id: match.id }).catch(addError)}>
                     LOAD
                   </Button> }
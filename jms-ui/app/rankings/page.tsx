@@ -2,12 +2,14 @@
 import React, { useEffect, useState } from "react";
 import { useWebsocket } from "../support/ws-component";
 import { useToasts } from "../support/errors";
-import { Match, PlayoffMode, Team, TeamRanking } from "@/app/ws-schema";
+import { GameDescriptor, Match, PlayoffMode, RoundRobinStanding, Team, TeamRanking, TeamRankingDetail, Tiebreaker } from "@/app/ws-schema";
 import { Col, Row, Table } from "react-bootstrap";
 import { Element, scroller } from "react-scroll";
 import moment from "moment";
 import _ from "lodash";
 import PlayoffBracketGraph from "../components/playoff-graphs/PlayoffBracket";
+import { confirmModal } from "../components/Confirm";
+import JmsWebsocket from "../support/ws";
 
 const SCROLL_TIME = 20000;
 const SCROLL_RESET_TIME = 2500;
@@ -18,8 +20,11 @@ export default function TeamRankings() {
   const [ matches, setMatches ] = useState<Match[]>([]);
   const [ nextMatch, setNextMatch ] = useState<Match | null>(null);
   const [ playoffMode, setPlayoffMode ] = useState<PlayoffMode>();
+  const [ game, setGame ] = useState<GameDescriptor>();
+  const [ roundRobin, setRoundRobin ] = useState<RoundRobinStanding[]>([]);
 
   const { call, subscribe, unsubscribe } = useWebsocket();
+  const { addError } = useToasts();
 
   const scrollDown = () => {
     scroller.scrollTo("bottom", {
@@ -51,7 +56,9 @@ export default function TeamRankings() {
         setMatches(m);
         refreshPlayoffMode();
       }),
-      subscribe<"team/teams">("team/teams", setTeams)
+      subscribe<"team/teams">("team/teams", setTeams),
+      subscribe<"scoring/game">("scoring/game", setGame),
+      subscribe<"matches/round_robin_standings">("matches/round_robin_standings", setRoundRobin)
     ];
     refreshPlayoffMode();
     // scrollDown();
@@ -74,8 +81,9 @@ export default function TeamRankings() {
         <Element name="top" />
           { 
             nextMatch?.match_type === "Playoff" ? 
-              (playoffMode && <PlayoffBracketGraph matches={matches} dark_mode next_match={nextMatch} teams={teams} playoff_mode={playoffMode.mode} />)
-              : <QualificationTeamRankings rankings={rankings} teams={teams} /> 
+              (playoffMode?.mode === "RoundRobin" ? <RoundRobinStandings standings={roundRobin} />
+                : playoffMode && <PlayoffBracketGraph matches={matches} dark_mode next_match={nextMatch} teams={teams} playoff_mode={playoffMode.mode} />)
+              : <QualificationTeamRankings rankings={rankings} teams={teams} tiebreakers={game?.tiebreakers || []} onSelect={team => showRankingDetail(team, call).catch(addError)} /> 
           }
         <Element name="bottom" />
       </Col>
@@ -83,7 +91,34 @@ export default function TeamRankings() {
   </React.Fragment>
 }
 
-function QualificationTeamRankings({ rankings, teams }: { rankings: TeamRanking[], teams: Team[] }) {
+function RoundRobinStandings({ standings }: { standings: RoundRobinStanding[] }) {
+  return <Table striped bordered className="rankings">
+    <thead>
+      <tr>
+        <th> Rank </th>
+        <th> Alliance </th>
+        <th> Played </th>
+        <th> Points </th>
+        <th> Total Score </th>
+        <th> Win-Loss-Tie </th>
+      </tr>
+    </thead>
+    <tbody>
+      {
+        standings.map((s, i) => <tr key={s.alliance}>
+          <td> { i + 1 } </td>
+          <td> Alliance { s.alliance } </td>
+          <td> { s.played } </td>
+          <td> { s.points } </td>
+          <td> { s.total_score } </td>
+          <td> { s.win } - { s.loss } - { s.tie } </td>
+        </tr>)
+      }
+    </tbody>
+  </Table>
+}
+
+function QualificationTeamRankings({ rankings, teams, tiebreakers, onSelect }: { rankings: TeamRanking[], teams: Team[], tiebreakers: Tiebreaker[], onSelect: (team: number) => void }) {
   const team_map = _.keyBy(teams, "number");
   return <Table striped bordered className="rankings">
     <thead>
@@ -92,25 +127,71 @@ function QualificationTeamRankings({ rankings, teams }: { rankings: TeamRanking[
         <th> Team </th>
         <th> Played </th>
         <th> RP </th>
-        <th> Auto </th>
-        <th> Teleop </th>
-        <th> Endgame </th>
+        { tiebreakers.map(tb => <th key={tb.name}> { tb.name } </th>) }
         <th> Win-Loss-Tie </th>
       </tr>
     </thead>
     <tbody>
       {
-        rankings.map((r, i) => <tr key={i} data-rank={i + 1}>
+        rankings.map((r, i) => <tr key={i} data-rank={i + 1} onClick={() => onSelect(r.team)}>
           <td> {i + 1} </td>
           <td> { team_map[r.team] ? team_map[r.team].display_number : r.team } </td>
           <td> { r.played } </td>
           <td> { r.rp } </td>
-          <td> { r.auto_points } </td>
-          <td> { r.teleop_points } </td>
-          <td> { r.endgame_points } </td>
+          { tiebreakers.map((tb, j) => <td key={tb.name}> { r.tiebreakers[j] || 0 } </td>) }
           <td> { r.win } - { r.loss } - { r.tie } </td>
         </tr>)
       }
     </tbody>
   </Table>
+}
+
+// Why a team is ranked where they are - what they got out of each of their qualification matches
+async function showRankingDetail(team: number, call: JmsWebsocket["call"]) {
+  const detail: TeamRankingDetail = await call<"scoring/ranking_detail">("scoring/ranking_detail", { team });
+  await confirmModal("", {
+    title: `Team ${team} - ${detail.rank ? `Rank ${detail.rank}` : "Unranked"}`,
+    okText: "Close",
+    cancelBtn: { style: { display: "none" } },
+    size: "xl",
+    renderInner: () => <React.Fragment>
+      {
+        detail.ranking && <p>
+          { detail.ranking.rp } RP over { detail.ranking.played } matches ({ (detail.ranking.rp / (detail.ranking.played || 1)).toFixed(2) } avg)
+          { detail.ranking.dqs > 0 && <span className="text-danger"> &nbsp; { detail.ranking.dqs } DQ </span> }
+        </p>
+      }
+      <Table size="sm" striped>
+        <thead>
+          <tr>
+            <th> Match </th>
+            <th> Alliance </th>
+            <th> Score </th>
+            <th> Result </th>
+            <th> RP </th>
+            { detail.tiebreakers.map(tb => <th key={tb.name}> { tb.name } { tb.sort !== "Average" && <span className="text-muted">({ tb.sort === "Total" ? "Total" : "Best" })</span> } </th>) }
+          </tr>
+        </thead>
+        <tbody>
+          {
+            detail.matches.map(m => <tr key={m.match_id} className={m.surrogate || m.dq ? "text-muted" : undefined}>
+              <td> { m.match_name } { m.surrogate && <em>(Surrogate)</em> } { m.dq && <strong className="text-danger">DQ</strong> } </td>
+              <td> { m.alliance } </td>
+              <td> { m.score } - { m.opponent_score } </td>
+              <td> { m.win_status } </td>
+              <td> { m.dq ? 0 : m.rp.win_rp + m.rp.bonus_rp.reduce((a, [_, rp]) => a + rp, 0) } { m.rp.bonus_rp.filter(([_, rp]) => rp > 0).map(([name]) => <span key={name} className="text-muted"> +{ name } </span>) } </td>
+              { detail.tiebreakers.map((tb, j) => <td key={tb.name}> { m.tiebreakers[j] || 0 } </td>) }
+            </tr>)
+          }
+        </tbody>
+        <tfoot>
+          <tr>
+            <th colSpan={4}> Average </th>
+            <th> { detail.ranking ? (detail.ranking.rp / (detail.ranking.played || 1)).toFixed(2) : "--" } </th>
+            { detail.tiebreakers.map((tb, j) => <th key={tb.name}> { detail.ranking ? ((detail.ranking.tiebreakers[j] || 0) / (detail.ranking.played || 1)).toFixed(2) : "--" } </th>) }
+          </tr>
+        </tfoot>
+      </Table>
+    </React.Fragment>
+  }).catch(() => {});
 }
\ No newline at end of file
@@ -1,11 +1,15 @@
 "use client"
-import { Button } from "react-bootstrap";
+import { Button, Form, InputGroup, Table } from "react-bootstrap";
 import UserPage from "../userpage";
 import { useWebsocket } from "../support/ws-component";
 import { useToasts } from "../support/errors";
-import { MatchType, ReportData, WebsocketRpcRequest } from "../ws-schema";
-import React, { useState } from "react";
+import { ArenaAuditEntry, Match, MatchLogFormat, MatchType, WebsocketRpcRequest } from "../ws-schema";
+import React, { useEffect, useState } from "react";
+import moment from "moment";
+import { describeBlocker, describeStopFault } from "../field-control/match_flow";
 import { PermissionGate } from "../support/permissions";
+import { openReport } from "../support/util";
+import BufferedFormControl from "../components/BufferedFormControl";
 
 const MATCH_TYPE_VARIANT: { [k in MatchType]: string } = {
   Test: "danger",
@@ -16,25 +20,29 @@ const MATCH_TYPE_VARIANT: { [k in MatchType]: string } = {
 
 export default function Reports() {
   const [loading, setLoading] = useState<boolean>(false);
+  const [matches, setMatches] = useState<Match[]>([]);
+  const [journalMatch, setJournalMatch] = useState<string | null>(null);
+  const [journal, setJournal] = useState<ArenaAuditEntry[] | null>(null);
+  const [logTeam, setLogTeam] = useState<number | null>(null);
 
-  const { call } = useWebsocket();
+  const { call, subscribe, unsubscribe } = useWebsocket();
   const { addError } = useToasts();
 
-  const open = (report: ReportData) => {
-    console.log("Report: ", report)
-    let file = new Blob([new Uint8Array(report.data).buffer], { type: report.mime });
-    let file_url = URL.createObjectURL(file);
-    window.open(file_url);
-  }
+  useEffect(() => {
+    let cbs = [
+      subscribe<"matches/matches">("matches/matches", m => setMatches(m.filter(x => x.played)))
+    ];
+    return () => unsubscribe(cbs);
+  }, []);
 
   const report = <Path extends WebsocketRpcRequest["path"]>
          (path: Path, args: Extract<WebsocketRpcRequest, { path: Path }>["data"]) =>
   {
     setLoading(true);
     call<Path>(path, args as any).then(data => {
-      open(data as any);
+      openReport(data as any);
       setLoading(false);
-    }).catch(addError)
+    }).catch(e => { setLoading(false); addError(e) })
   }
 
   return <UserPage container>
@@ -67,6 +75,64 @@ export default function Reports() {
       </React.Fragment>)
     }
 
+    <PermissionGate permissions={["FTA", "FTAA", "Scorekeeper"]}>
+      <h4 className="mt-2"> Arena Journal </h4>
+      <InputGroup className="mb-2" style={{ maxWidth: '40em' }}>
+        <Form.Select value={journalMatch || ""} onChange={e => { setJournalMatch(e.target.value || null); setJournal(null); }}>
+          <option value=""> All Matches </option>
+          { matches.map(m => <option key={m.id} value={m.id}> { m.name } </option>) }
+        </Form.Select>
+        <Button disabled={loading} onClick={() => call<"arena/journal">("arena/journal", { match_id: journalMatch }).then(setJournal).catch(addError)}>
+          View
+        </Button>
+        <Button disabled={loading} variant="secondary" onClick={() => report<"arena/journal_csv">("arena/journal_csv", { match_id: journalMatch })}>
+          Export (csv)
+        </Button>
+      </InputGroup>
+      {
+        journal && <Table size="sm" striped bordered>
+          <thead>
+            <tr>
+              <th> Time </th>
+              <th> Match </th>
+              <th> Source </th>
+              <th> Event </th>
+            </tr>
+          </thead>
+          <tbody>
+            {
+              journal.map(e => <tr key={e.id}>
+                <td> { moment(e.time).format("ddd HH:mm:ss.SSS") } </td>
+                <td> { e.match_id } </td>
+                <td> { e.source } </td>
+                <td> <strong>{ e.event.event }</strong> &nbsp; { journalDetail(e) } </td>
+              </tr>)
+            }
+          </tbody>
+        </Table>
+      }
+    </PermissionGate>
+
+    <PermissionGate permissions={["Ticketing", "FTA", "FTAA"]}>
+      <h4 className="mt-2"> Match Logs </h4>
+      <InputGroup className="mb-2" style={{ maxWidth: '40em' }}>
+        <InputGroup.Text>Team</InputGroup.Text>
+        <BufferedFormControl
+          instant
+          type="number"
+          min={0}
+          step={1}
+          value={logTeam || ""}
+          onUpdate={v => setLogTeam(Math.floor(v as number) || null)}
+        />
+        {
+          (["DSLog", "CSV"] as MatchLogFormat[]).map(format => <Button key={format} disabled={loading || !logTeam} variant="secondary" onClick={() => report<"tickets/export_team_logs">("tickets/export_team_logs", { team: logTeam!, format })}>
+            All Matches ({ format === "DSLog" ? "dslog" : "csv" })
+          </Button>)
+        }
+      </InputGroup>
+    </PermissionGate>
+
     <PermissionGate permissions={["FTA"]}>
       <h4>WPA Keys</h4>
       <Button disabled={loading} size="lg" variant="danger" onClick={() => report<"reports/wpa_key">("reports/wpa_key", { csv: false })}>
@@ -77,4 +143,21 @@ export default function Reports() {
       </Button>
     </PermissionGate>
   </UserPage>
+}
+
+function journalDetail(e: ArenaAuditEntry) {
+  const ev = e.event;
+  switch (ev.event) {
+    case "Signal": return <span> { JSON.stringify(ev.signal) } { ev.error && <span className="text-danger"> (rejected: { ev.error }) </span> } </span>;
+    case "StateChange": return `${ev.from.state} -> ${ev.to.state}`;
+    case "StationEstop": return `${ev.station.alliance} ${ev.station.station} - E-Stop: ${ev.estop}, A-Stop: ${ev.astop}`;
+    case "StationBypass": return `${ev.station.alliance} ${ev.station.station} - Bypass: ${ev.bypass}`;
+    case "HookFailed": return `${ev.hook}: ${ev.reason}`;
+    case "MatchArmForced": return `Overriding ${ev.blockers.map(describeBlocker).join("; ")}`;
+    case "TimeoutStart": return `${ev.kind.type === "Field" ? "Field" : `Alliance ${ev.kind.alliance}`} (${Math.round(ev.duration / 1000)}s)`;
+    case "TimeoutEnd": return ev.early ? "Ended early" : "";
+    case "PhysicalStop": return `${ev.station.alliance} ${ev.station.station} - ${ev.astop ? "A-Stop" : "E-Stop"} pressed`;
+    case "StopFault": return `${ev.station.alliance} ${ev.station.station} - ${ev.fault ? describeStopFault(ev.fault) : "Cleared"}`;
+    default: return "";
+  }
 }
\ No newline at end of file
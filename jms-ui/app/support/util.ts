@@ -1,4 +1,5 @@
 import { useEffect, useRef } from "react";
+import { ReportData } from "../ws-schema";
 
 export function withVal<T,R>(val: T|null|undefined, fn: (v: T) => R|null) {
   if (val !== null && val !== undefined)
@@ -56,4 +57,18 @@ export function usePrevious<T>(value: T) {
     ref.current = value;
   });
   return ref.current;
+}
+
+// Reports with a filename are downloaded, the rest are opened in a new tab
+export function openReport(report: ReportData) {
+  let file = new Blob([new Uint8Array(report.data).buffer], { type: report.mime });
+  let file_url = URL.createObjectURL(file);
+  if (report.filename) {
+    let a = document.createElement("a");
+    a.href = file_url;
+    a.download = report.filename;
+    a.click();
+  } else {
+    window.open(file_url);
+  }
 }
\ No newline at end of file
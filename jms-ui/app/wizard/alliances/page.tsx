@@ -4,9 +4,9 @@ import { withConfirm } from "@/app/components/Confirm"
 import { useToasts } from "@/app/support/errors"
 import { withPermission } from "@/app/support/permissions"
 import { useWebsocket } from "@/app/support/ws-component"
-import { Match, PlayoffAlliance, Team, TeamRanking } from "@/app/ws-schema"
+import { AllianceSelection, Match, PlayoffAlliance, Team, TeamRanking } from "@/app/ws-schema"
 import React, { useEffect, useState } from "react"
-import { Alert, Button, Table } from "react-bootstrap"
+import { Alert, Button, ButtonGroup, Card, Dropdown, DropdownButton, Table } from "react-bootstrap"
 import { Typeahead } from "react-bootstrap-typeahead"
 import update from "immutability-helper";
 
@@ -15,6 +15,9 @@ export default withPermission(["ManageAlliances"], function EventWizardAlliances
   const [ matches, setMatches ] = useState<Match[]>([]);
   const [ teams, setTeams ] = useState<Team[]>([]);
   const [ rankings, setRankings ] = useState<TeamRanking[]>([]);
+  const [ backupPool, setBackupPool ] = useState<number[]>([]);
+  const [ selection, setSelection ] = useState<AllianceSelection>();
+  const [ available, setAvailable ] = useState<number[]>([]);
 
   const { call, subscribe, unsubscribe } = useWebsocket();
   const { addError } = useToasts();
@@ -24,7 +27,10 @@ export default withPermission(["ManageAlliances"], function EventWizardAlliances
       subscribe<"alliances/alliances">("alliances/alliances", setAlliances),
       subscribe<"matches/matches">("matches/matches", setMatches),
       subscribe<"team/teams">("team/teams", setTeams),
-      subscribe<"scoring/rankings">("scoring/rankings", setRankings)
+      subscribe<"scoring/rankings">("scoring/rankings", setRankings),
+      subscribe<"alliances/backup_pool">("alliances/backup_pool", setBackupPool),
+      subscribe<"alliances/selection">("alliances/selection", setSelection),
+      subscribe<"alliances/selection_available">("alliances/selection_available", setAvailable)
     ];
     return () => unsubscribe(cbs);
   }, [])
@@ -37,6 +43,7 @@ export default withPermission(["ManageAlliances"], function EventWizardAlliances
   const has_alliances = alliances.length > 0;
 
   const not_chosen = rankings?.filter(t => alliances.filter(a => a.teams.includes(t.team)).length == 0 );
+  const display = (team: number) => teams.find(t => t.number === team)?.display_number || team;
 
   return <React.Fragment>
     <h3> Alliance Selections </h3>
@@ -57,12 +64,55 @@ export default withPermission(["ManageAlliances"], function EventWizardAlliances
         Create Alliances
       </Button>
     }
+    &nbsp;
+    <ButtonGroup>
+      {
+        [2, 3].map(rounds => <Button key={rounds} variant="primary" disabled={disabled || selection?.active} onClick={() => withConfirm(() => call<"alliances/start_selection">("alliances/start_selection", { rounds }).then(setSelection).catch(addError))}>
+          Start Selection ({ rounds } Rounds)
+        </Button>)
+      }
+    </ButtonGroup>
     <br /> <br />
+    {
+      selection?.active && <Card className="mb-3">
+        <Card.Body>
+          <Card.Title>
+            Round { selection.round }: Alliance { selection.picking } is picking
+            <Button className="float-end" size="sm" variant="secondary" disabled={selection.history.length === 0} onClick={() => call<"alliances/undo_selection">("alliances/undo_selection", null).then(setSelection).catch(addError)}>
+              Undo
+            </Button>
+          </Card.Title>
+          <Table size="sm" striped>
+            <tbody>
+              {
+                available.map(team => <tr key={team}>
+                  <td> { display(team) } </td>
+                  <td className="text-end">
+                    <Button size="sm" variant="success" onClick={() => call<"alliances/pick">("alliances/pick", { team }).then(setSelection).catch(addError)}>
+                      Accept
+                    </Button> &nbsp;
+                    <Button size="sm" variant="danger" onClick={() => call<"alliances/decline">("alliances/decline", { team }).then(setSelection).catch(addError)}>
+                      Decline
+                    </Button>
+                  </td>
+                </tr>)
+              }
+            </tbody>
+          </Table>
+        </Card.Body>
+      </Card>
+    }
+    {
+      selection && !selection.active && selection.history.length > 0 && <Button className="mb-3" size="sm" variant="secondary" disabled={disabled} onClick={() => call<"alliances/undo_selection">("alliances/undo_selection", null).then(setSelection).catch(addError)}>
+        Undo Last Pick
+      </Button>
+    }
     <Table striped hover bordered>
       <thead>
         <tr>
           <th>#</th>
           <th>Teams</th>
+          <th>Backup</th>
         </tr>
       </thead>
       <tbody>
@@ -84,6 +134,28 @@ export default withPermission(["ManageAlliances"], function EventWizardAlliances
                 }}
               />
             </td>
+            <td>
+              {
+                alliance.backup ? <React.Fragment>
+                  { display(alliance.backup.team) } <span className="text-muted">(for { display(alliance.backup.replaces) })</span> &nbsp;
+                  <Button size="sm" variant="danger" onClick={() => withConfirm(() => call<"alliances/clear_backup">("alliances/clear_backup", { number: alliance.number })
+                    .then(a => setAlliances(update(alliances, { [i]: { $set: a } })))
+                    .catch(addError)
+                  )}>
+                    Clear
+                  </Button>
+                </React.Fragment> : <DropdownButton size="sm" variant="warning" title="Call Backup" disabled={alliance.teams.length === 0 || backupPool.length === 0}>
+                  {
+                    alliance.teams.map(t => <Dropdown.Item key={t} onClick={() => withConfirm(() => call<"alliances/call_backup">("alliances/call_backup", { number: alliance.number, replaces: t, team: null })
+                      .then(a => setAlliances(update(alliances, { [i]: { $set: a } })))
+                      .catch(addError)
+                    )}>
+                      Replace { display(t) } with { display(backupPool[0]) }
+                    </Dropdown.Item>)
+                  }
+                </DropdownButton>
+              }
+            </td>
           </tr>)
         }
       </tbody>
@@ -0,0 +1,153 @@
+"use client"
+
+import BufferedFormControl from "@/app/components/BufferedFormControl";
+import { useToasts } from "@/app/support/errors";
+import { withPermission } from "@/app/support/permissions"
+import { nullIfEmpty } from "@/app/support/strings";
+import { useWebsocket } from "@/app/support/ws-component";
+import { DiagnosisCode, DiagnosisRule, DiagnosisSettings, DriverStationSettings, DriverStationSettingsUpdate, DriverStationVersionStatus } from "@/app/ws-schema";
+import { faArrowDown, faArrowUp } from "@fortawesome/free-solid-svg-icons";
+import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
+import React, { useEffect, useState } from "react"
+import { Button, Col, Form, InputGroup, Row, Table } from "react-bootstrap";
+import { DIAGNOSIS_CODES } from "@/app/field-control/fta/page";
+
+export default withPermission(["FTA", "ManageEvent"], function DriverStations() {
+  const [ settings, setSettings ] = useState<DriverStationSettings>();
+  const [ versions, setVersions ] = useState<DriverStationVersionStatus[]>([]);
+  const [ diagnosis, setDiagnosis ] = useState<DiagnosisSettings>();
+
+  const { call, subscribe, unsubscribe } = useWebsocket();
+  const { addError } = useToasts();
+
+  useEffect(() => {
+    call<"arena/ds_settings">("arena/ds_settings", null)
+      .then(setSettings)
+      .catch(addError);
+    call<"arena/diagnosis_settings">("arena/diagnosis_settings", null)
+      .then(setDiagnosis)
+      .catch(addError);
+
+    let cbs = [
+      subscribe<"arena/ds_versions">("arena/ds_versions", setVersions)
+    ];
+    return () => unsubscribe(cbs);
+  }, []);
+
+  const update = (update: DriverStationSettingsUpdate) => {
+    call<"arena/update_ds_settings">("arena/update_ds_settings", { update }).then(setSettings).catch(addError);
+  }
+
+  const updateRules = (rules: DiagnosisRule[]) => {
+    call<"arena/update_diagnosis_settings">("arena/update_diagnosis_settings", { update: { rules } }).then(setDiagnosis).catch(addError);
+  }
+
+  const updateRule = (i: number, rule: Partial<DiagnosisRule>) => {
+    updateRules(diagnosis!.rules.map((r, j) => i === j ? { ...r, ...rule } : r));
+  }
+
+  const moveRule = (i: number, by: number) => {
+    let rules = [ ...diagnosis!.rules ];
+    rules.splice(i + by, 0, rules.splice(i, 1)[0]);
+    updateRules(rules);
+  }
+
+  return <React.Fragment>
+    <h3> Driver Stations </h3>
+
+    {
+      settings && <Row>
+        <Col>
+          <h4 className="mt-3"> Minimum Versions </h4>
+          <p className="text-muted"> Teams running older software are flagged on the FTA view. Leave blank to allow any version. </p>
+          <InputGroup>
+            <InputGroup.Text>Driver Station</InputGroup.Text>
+            <BufferedFormControl type="text" placeholder="24.0" value={settings.min_ds_version || ""} onUpdate={v => update({ min_ds_version: nullIfEmpty(String(v)) })} />
+          </InputGroup>
+          <InputGroup className="mt-2">
+            <InputGroup.Text>RoboRIO Image</InputGroup.Text>
+            <BufferedFormControl type="text" placeholder="2024_v2.1" value={settings.min_rio_version || ""} onUpdate={v => update({ min_rio_version: nullIfEmpty(String(v)) })} />
+          </InputGroup>
+          <InputGroup className="mt-2">
+            <InputGroup.Text>WPILib</InputGroup.Text>
+            <BufferedFormControl type="text" placeholder="2024.3.2" value={settings.min_wpilib_version || ""} onUpdate={v => update({ min_wpilib_version: nullIfEmpty(String(v)) })} />
+          </InputGroup>
+          <Form.Check
+            className="mt-2"
+            type="switch"
+            label="Block Prestart for stations running out of date software (unless bypassed)"
+            checked={settings.block_outdated}
+            onChange={e => update({ block_outdated: e.target.checked })}
+          />
+        </Col>
+      </Row>
+    }
+
+    {
+      diagnosis && <Row>
+        <Col>
+          <h4 className="mt-4"> Diagnosis Rules </h4>
+          <p className="text-muted"> Shown on the FTA view and the alliance station panels. Rules are in priority order - only the first one that's raised is shown. </p>
+          <Table size="sm" striped>
+            <thead>
+              <tr>
+                <th> Code </th>
+                <th> Enabled </th>
+                <th> Threshold </th>
+                <th> Raise After (ms) </th>
+                <th> Clear After (ms) </th>
+                <th> Priority </th>
+              </tr>
+            </thead>
+            <tbody>
+              {
+                diagnosis.rules.map((rule, i) => <tr key={rule.code}>
+                  <td> <strong>{ DIAGNOSIS_CODES[rule.code].code }</strong> &nbsp; <span className="text-muted">{ DIAGNOSIS_CODES[rule.code].name }</span> </td>
+                  <td> <Form.Check type="switch" checked={rule.enabled} onChange={e => updateRule(i, { enabled: e.target.checked })} /> </td>
+                  <td>
+                    {
+                      DIAGNOSIS_CODES[rule.code].unit && <InputGroup size="sm">
+                        <BufferedFormControl size="sm" type="number" step={0.1} value={rule.threshold} onUpdate={v => updateRule(i, { threshold: Number(v) })} />
+                        <InputGroup.Text>{ DIAGNOSIS_CODES[rule.code].unit }</InputGroup.Text>
+                      </InputGroup>
+                    }
+                  </td>
+                  <td> <BufferedFormControl size="sm" type="number" min={0} value={rule.raise_after_ms} onUpdate={v => updateRule(i, { raise_after_ms: Math.max(0, Math.round(Number(v))) })} /> </td>
+                  <td> <BufferedFormControl size="sm" type="number" min={0} value={rule.clear_after_ms} onUpdate={v => updateRule(i, { clear_after_ms: Math.max(0, Math.round(Number(v))) })} /> </td>
+                  <td>
+                    <Button size="sm" variant="secondary" disabled={i === 0} onClick={() => moveRule(i, -1)}> <FontAwesomeIcon icon={faArrowUp} /> </Button> &nbsp;
+                    <Button size="sm" variant="secondary" disabled={i === diagnosis.rules.length - 1} onClick={() => moveRule(i, 1)}> <FontAwesomeIcon icon={faArrowDown} /> </Button>
+                  </td>
+                </tr>)
+              }
+            </tbody>
+          </Table>
+        </Col>
+      </Row>
+    }
+
+    <h4 className="mt-4"> Team Versions </h4>
+    <Table size="sm" striped>
+      <thead>
+        <tr>
+          <th> Team </th>
+          <th> Driver Station </th>
+          <th> RoboRIO </th>
+          <th> WPILib </th>
+          <th> Out of Date </th>
+        </tr>
+      </thead>
+      <tbody>
+        {
+          versions.sort((a, b) => a.versions.team - b.versions.team).map(v => <tr key={v.versions.team}>
+            <td> { v.versions.team } </td>
+            <td> { v.versions.ds || "--" } </td>
+            <td> { v.versions.rio || "--" } </td>
+            <td> { v.versions.wpilib || "--" } </td>
+            <td className={v.outdated.length > 0 ? "text-danger" : undefined}> { v.outdated.join(", ") } </td>
+          </tr>)
+        }
+      </tbody>
+    </Table>
+  </React.Fragment>
+})
@@ -6,7 +6,7 @@ import { withPermission } from "@/app/support/permissions"
 import { useWebsocket } from "@/app/support/ws-component";
 import { EstopMode, FieldElectronicsSettings, FieldElectronicsSettingsUpdate, JmsComponent } from "@/app/ws-schema";
 import React, { useEffect, useState } from "react"
-import { Alert, Button, Col, InputGroup, Row } from "react-bootstrap";
+import { Alert, Button, Col, Form, InputGroup, Row } from "react-bootstrap";
 
 export default withPermission(["ManageElectronics"], function Electronics() {
   const [ settings, setSettings ] = useState<FieldElectronicsSettings>();
@@ -55,6 +55,13 @@ export default withPermission(["ManageElectronics"], function Electronics() {
               variant="secondary"
             />
           </InputGroup>
+          <Form.Check
+            className="mt-2"
+            type="switch"
+            label="Stations have a dedicated A-Stop button (IO 1). Otherwise, the E-Stop button acts as an A-Stop during Auto."
+            checked={settings.astop_button}
+            onChange={e => update({ astop_button: e.target.checked })}
+          />
         </Col>
         <Col md="4">
           <Button variant="danger" onClick={() => call<"electronics/reset_estops">("electronics/reset_estops", null).catch(addError)}>
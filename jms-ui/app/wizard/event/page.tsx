@@ -5,9 +5,9 @@ import { useToasts } from "@/app/support/errors";
 import { withPermission } from "@/app/support/permissions"
 import { nullIfEmpty } from "@/app/support/strings";
 import { useWebsocket } from "@/app/support/ws-component";
-import { Award, EventDetails, PlayoffMode } from "@/app/ws-schema";
+import { Award, EventDetails, MatchTiming, MatchTimingConfig, MatchType, PlayoffMode } from "@/app/ws-schema";
 import React, { useEffect, useState } from "react";
-import { Button, Card, Col, Form, InputGroup, Row } from "react-bootstrap";
+import { Button, Card, Col, Form, InputGroup, Row, Table } from "react-bootstrap";
 import update, { Spec } from "immutability-helper";
 import { SketchPicker } from 'react-color';
 import EnumToggleGroup from "@/app/components/EnumToggleGroup";
@@ -20,17 +20,25 @@ import { withConfirm } from "@/app/components/Confirm";
 const PLAYOFF_MODES: { [k in PlayoffMode["mode"]]: string } = {
   Bracket: "Bracket",
   DoubleBracket: "Double Bracket",
+  RoundRobin: "Round Robin",
 };
 
 const DEFAULT_PLAYOFF_MODES: { [k in PlayoffMode["mode"]]: PlayoffMode } = {
-  Bracket: { mode: "Bracket", n_alliances: 8, awards: [], time_per_award: 5*60*1000, minimum_round_break: 8*60*1000 },
-  DoubleBracket: { mode: "DoubleBracket", n_alliances: 8, awards: [], time_per_award: 5*60*1000, minimum_round_break: 8*60*1000 },
+  Bracket: { mode: "Bracket", n_alliances: 8, awards: [], time_per_award: 5*60*1000, minimum_round_break: 8*60*1000, playoff_best_of: 3, final_best_of: 3 },
+  DoubleBracket: { mode: "DoubleBracket", n_alliances: 8, awards: [], time_per_award: 5*60*1000, minimum_round_break: 8*60*1000, playoff_best_of: 1, final_best_of: 3 },
+  RoundRobin: { mode: "RoundRobin", n_alliances: 4, awards: [], time_per_award: 5*60*1000, minimum_round_break: 8*60*1000, playoff_best_of: 1, final_best_of: 3 },
 };
 
+const TIMING_PHASES: [keyof MatchTiming, string][] = [
+  [ "warmup", "Warmup" ], [ "auto", "Auto" ], [ "pause", "Pause" ], [ "teleop", "Teleop" ], [ "cooldown", "Cooldown" ], [ "endgame", "Endgame (remaining)" ]
+];
+const TIMING_MATCH_TYPES: MatchType[] = [ "Test", "Qualification", "Playoff", "Final" ];
+
 export default withPermission(["ManageEvent"], function EventWizardUsers() {
   const [ details, setDetails ] = useState<EventDetails | null>(null);
   const [ playoffMode, setPlayoffMode ] = useState<PlayoffMode | null>(null);
   const [ awards, setAwards ] = useState<Award[]>([]);
+  const [ timing, setTiming ] = useState<MatchTimingConfig | null>(null);
   const { call, subscribe, unsubscribe } = useWebsocket();
   const { addError } = useToasts();
 
@@ -41,11 +49,18 @@ export default withPermission(["ManageEvent"], function EventWizardUsers() {
     
     let cbs = [
       subscribe<"event/details">("event/details", setDetails),
-      subscribe<"awards/awards">("awards/awards", setAwards)
+      subscribe<"awards/awards">("awards/awards", setAwards),
+      subscribe<"event/match_timing">("event/match_timing", setTiming)
     ];
     return () => unsubscribe(cbs)
   }, []);
 
+  const updateTiming = (spec: Spec<MatchTimingConfig>) => {
+    call<"event/update_match_timing">("event/update_match_timing", { config: update(timing!, spec) })
+      .then(setTiming)
+      .catch(addError);
+  }
+
   const updateDetails = (spec: Spec<EventDetails>) => {
     call<"event/update">("event/update", { details: update(details!, spec) })
       .then(setDetails)
@@ -144,6 +159,33 @@ export default withPermission(["ManageEvent"], function EventWizardUsers() {
           />
         </InputGroup>
 
+        <InputGroup className="mt-2">
+          {
+            playoffMode.mode === "Bracket" && <React.Fragment>
+              <InputGroup.Text>Playoffs Best Of</InputGroup.Text>
+              <BufferedFormControl
+                auto
+                style={{ maxWidth: '6em' }}
+                type="number"
+                min={1}
+                step={2}
+                value={playoffMode.playoff_best_of}
+                onUpdate={v => call<"matches/set_playoff_mode">("matches/set_playoff_mode", { mode: { ...playoffMode, playoff_best_of: Math.max(1, v as number) } }).then(setPlayoffMode).catch(addError)}
+              />
+            </React.Fragment>
+          }
+          <InputGroup.Text>Finals Best Of</InputGroup.Text>
+          <BufferedFormControl
+            auto
+            style={{ maxWidth: '6em' }}
+            type="number"
+            min={1}
+            step={2}
+            value={playoffMode.final_best_of}
+            onUpdate={v => call<"matches/set_playoff_mode">("matches/set_playoff_mode", { mode: { ...playoffMode, final_best_of: Math.max(1, v as number) } }).then(setPlayoffMode).catch(addError)}
+          />
+        </InputGroup>
+
         <InputGroup className="mt-2">
           <InputGroup.Text>Awards</InputGroup.Text>
           <Typeahead
@@ -199,6 +241,87 @@ export default withPermission(["ManageEvent"], function EventWizardUsers() {
 
     <hr />
 
+    {
+      timing && <React.Fragment>
+        <h5>Match Timing</h5>
+        <Table size="sm" bordered>
+          <thead>
+            <tr>
+              <th> Match Type </th>
+              { TIMING_PHASES.map(([k, name]) => <th key={k}> { name } (s) </th>) }
+            </tr>
+          </thead>
+          <tbody>
+            <tr>
+              <td> <strong>Default</strong> </td>
+              {
+                TIMING_PHASES.map(([k]) => <td key={k}>
+                  <BufferedFormControl
+                    auto size="sm" type="number" min={0}
+                    value={timing.default[k] / 1000}
+                    onUpdate={v => updateTiming({ default: { [k]: { $set: Math.max(0, v as number) * 1000 } } })}
+                  />
+                </td>)
+              }
+            </tr>
+            {
+              TIMING_MATCH_TYPES.map(mt => <tr key={mt}>
+                <td>
+                  <Form.Check
+                    type="switch"
+                    label={mt}
+                    checked={timing.overrides[mt] !== undefined}
+                    onChange={e => updateTiming({ overrides: e.target.checked ? { [mt]: { $set: timing.default } } : { $unset: [mt] } })}
+                  />
+                </td>
+                {
+                  TIMING_PHASES.map(([k]) => <td key={k}>
+                    {
+                      timing.overrides[mt] ? <BufferedFormControl
+                        auto size="sm" type="number" min={0}
+                        value={timing.overrides[mt]![k] / 1000}
+                        onUpdate={v => updateTiming({ overrides: { [mt]: { [k]: { $set: Math.max(0, v as number) * 1000 } } } })}
+                      /> : <span className="text-muted"> { timing.default[k] / 1000 } </span>
+                    }
+                  </td>)
+                }
+              </tr>)
+            }
+          </tbody>
+        </Table>
+        <Form.Text>
+          <i>Changes take effect when the next match is loaded. Use overrides for shortened practice or demo matches.</i>
+        </Form.Text>
+
+        <InputGroup className="mt-2">
+          <InputGroup.Text>Alliance Timeout</InputGroup.Text>
+          <BufferedFormControl
+            auto
+            style={{ maxWidth: '8em' }}
+            type="number"
+            min={0.5}
+            step={0.5}
+            value={timing.alliance_timeout / 1000 / 60}
+            onUpdate={v => updateTiming({ alliance_timeout: { $set: Math.max(0.5, v as number) * 60 * 1000 } })}
+          />
+          <InputGroup.Text>mins</InputGroup.Text>
+          <InputGroup.Text>Default Field Timeout</InputGroup.Text>
+          <BufferedFormControl
+            auto
+            style={{ maxWidth: '8em' }}
+            type="number"
+            min={0.5}
+            step={0.5}
+            value={timing.field_timeout / 1000 / 60}
+            onUpdate={v => updateTiming({ field_timeout: { $set: Math.max(0.5, v as number) * 60 * 1000 } })}
+          />
+          <InputGroup.Text>mins</InputGroup.Text>
+        </InputGroup>
+      </React.Fragment>
+    }
+
+    <hr />
+
     {/* TODO: Webcast Links for TBA */}
 
     <Row className="mt-3">
@@ -4,19 +4,23 @@ import BufferedFormControl from "@/app/components/BufferedFormControl";
 import { confirmModal, withConfirm } from "@/app/components/Confirm";
 import { withPermission } from "@/app/support/permissions";
 import { useWebsocket } from "@/app/support/ws-component";
-import { Match, QualsMatchGeneratorParams } from "@/app/ws-schema";
+import { Match, QualsImportResult, QualsMatchGeneratorParams, QualsScheduleReport, RetimeSettings, ScheduleSlip } from "@/app/ws-schema";
 import React, { useEffect, useState } from "react";
-import { Button, InputGroup } from "react-bootstrap";
+import { Button, Form, InputGroup, Table } from "react-bootstrap";
 import update from "immutability-helper";
 import JmsWebsocket from "@/app/support/ws";
 import { useToasts } from "@/app/support/errors";
 import { FontAwesomeIcon } from "@fortawesome/react-fontawesome";
 import { faSpinner } from "@fortawesome/free-solid-svg-icons";
 import MatchSchedule from "@/app/match_schedule";
+import moment from "moment";
 
 export default withPermission(["ManageSchedule"], function EventWizardQuals() {
   const [ matches, setMatches ] = useState<Match[]>([]);
   const [ generationInProgress, setGenerationInProgress ] = useState<boolean>(false);
+  const [ reports, setReports ] = useState<QualsScheduleReport[]>([]);
+  const [ slip, setSlip ] = useState<ScheduleSlip | null>(null);
+  const [ retimeSettings, setRetimeSettings ] = useState<RetimeSettings>({ auto_retime: false });
   const { call, subscribe, unsubscribe } = useWebsocket();
   const { addError } = useToasts();
 
@@ -24,6 +28,9 @@ export default withPermission(["ManageSchedule"], function EventWizardQuals() {
     const cb = [
       subscribe<"matches/matches">("matches/matches", m => setMatches(m.filter(x => x.match_type === "Qualification"))),
       subscribe<"matches/generator_busy">("matches/generator_busy", setGenerationInProgress),
+      subscribe<"matches/quals_reports">("matches/quals_reports", setReports),
+      subscribe<"matches/slip">("matches/slip", setSlip),
+      subscribe<"matches/retime_settings">("matches/retime_settings", setRetimeSettings),
     ];
     return () => unsubscribe(cb);
   }, []);
@@ -42,11 +49,141 @@ export default withPermission(["ManageSchedule"], function EventWizardQuals() {
     >
       Delete Unplayed
     </Button>
-    <br /><br />
-    <MatchSchedule matches={matches} canDelete />
+    <Form.Group className="mt-3">
+      <Form.Label>Import Schedule (MatchMaker or CSV)</Form.Label>
+      <Form.Control
+        type="file"
+        accept=".txt,.csv"
+        disabled={generationInProgress}
+        onChange={e => {
+          const files = (e.target as HTMLInputElement).files;
+          if (files && files.length > 0) {
+            files[0].text().then(data => importQualsModal(data, call, addError));
+            (e.target as HTMLInputElement).value = "";
+          }
+        }}
+      />
+    </Form.Group>
+    {
+      matches.some(m => m.played) && slip && <React.Fragment>
+        <h5 className="mt-3"> Schedule Timing </h5>
+        <p>
+          { slipText(slip.delta) }
+          { slip.last_started && <span className="text-muted"> (last started: { slip.last_started }) </span> }
+        </p>
+        <Button
+          size="sm"
+          disabled={generationInProgress}
+          onClick={() => call<"matches/retime">("matches/retime", null).catch(addError)}
+        >
+          Re-time Remaining Matches
+        </Button> &nbsp;
+        <Form.Check
+          inline
+          type="switch"
+          label="Automatically re-time after each match"
+          checked={retimeSettings.auto_retime}
+          onChange={e => call<"matches/update_retime_settings">("matches/update_retime_settings", { settings: { auto_retime: e.target.checked } }).catch(addError)}
+        />
+      </React.Fragment>
+    }
+    <br />
+    {
+      reports.length > 0 && <React.Fragment>
+        <h5>
+          Candidate Schedules &nbsp;
+          <Button size="sm" variant="secondary" onClick={() => call<"matches/clear_quals_reports">("matches/clear_quals_reports", null).catch(addError)}>
+            Clear
+          </Button>
+        </h5>
+        <Table size="sm" striped bordered>
+          <thead>
+            <tr>
+              <th> Seed </th>
+              <th> Teams </th>
+              <th> Matches </th>
+              <th> Partners (min-max) </th>
+              <th> Opponents (min-max) </th>
+              <th> Station Imbalance </th>
+              <th> Min Turnaround (violations) </th>
+              <th> Action </th>
+            </tr>
+          </thead>
+          <tbody>
+            {
+              reports.map(r => <tr key={r.seed}>
+                <td> { r.seed } </td>
+                <td> { r.num_teams } </td>
+                <td> { r.num_matches } </td>
+                <td> { r.min_partner } - { r.max_partner } </td>
+                <td> { r.min_opponent } - { r.max_opponent } </td>
+                <td> { r.max_station_imbalance } </td>
+                <td> { r.min_turnaround ?? "-" } ({ r.turnaround_violations }) </td>
+                <td>
+                  {
+                    r.committed ? <span className="text-success">Committed</span>
+                      : <Button size="sm" disabled={generationInProgress} onClick={() => call<"matches/gen_quals">("matches/gen_quals", { params: { ...r.params, seed: r.seed, dry_run: false } }).catch(addError)}>
+                        Commit
+                      </Button>
+                  }
+                </td>
+              </tr>)
+            }
+          </tbody>
+        </Table>
+      </React.Fragment>
+    }
+    <MatchSchedule matches={matches} canDelete canReplay />
   </React.Fragment>
 });
 
+function slipText(delta: number) {
+  const minutes = Math.round(Math.abs(delta) / 60000);
+  if (minutes === 0)
+    return <span className="text-success"> On Schedule </span>;
+  else if (delta > 0)
+    return <span className="text-danger"> Running { minutes } minutes behind </span>;
+  else
+    return <span className="text-success"> Running { minutes } minutes ahead </span>;
+}
+
+async function importQualsModal(data: string, call: JmsWebsocket["call"], addError: (e: string) => void) {
+  let result: QualsImportResult;
+  try {
+    result = await call<"matches/import_quals">("matches/import_quals", { data, dry_run: true });
+  } catch (e) {
+    addError(e as string);
+    return;
+  }
+
+  const changes = result.diff.filter(d => d.kind !== "Unchanged");
+  const teams = (m?: Match | null) => m ? `${m.red_teams.join(" ")} vs ${m.blue_teams.join(" ")}` : "";
+
+  await confirmModal("", {
+    title: "Import Qualification Schedule",
+    okText: "Import",
+    size: "lg",
+    renderInner: () => <React.Fragment>
+      {
+        result.warnings.map((w, i) => <p key={i} className="text-warning"> { w } </p>)
+      }
+      <p> { result.diff.filter(d => d.after).length } matches will be imported, { changes.length } changes to the existing schedule. </p>
+      <Table size="sm" striped>
+        <tbody>
+          {
+            changes.map(d => <tr key={d.name}>
+              <td> <strong>{ d.kind }</strong> </td>
+              <td> { d.name } </td>
+              <td> { d.before && <span className="text-muted"> { moment(d.before.start_time).format("ddd HH:mm") } { teams(d.before) } </span> } </td>
+              <td> { d.after && <span> { moment(d.after.start_time).format("ddd HH:mm") } { teams(d.after) } </span> } </td>
+            </tr>)
+          }
+        </tbody>
+      </Table>
+    </React.Fragment>
+  }).then(() => call<"matches/import_quals">("matches/import_quals", { data, dry_run: false }).catch(addError), () => {});
+}
+
 async function genQualsModal(call: JmsWebsocket["call"], addError: (e: string) => void) {
   let params = await confirmModal("", {
     title: "Generate Qualification Schedule",
@@ -54,6 +191,10 @@ async function genQualsModal(call: JmsWebsocket["call"], addError: (e: string) =
     data: {
       station_anneal_steps: 50_000,
       team_anneal_steps: 100_000,
+      seed: null,
+      dry_run: false,
+      min_turnaround: 3,
+      hard_turnaround: false,
     } as QualsMatchGeneratorParams,
     renderInner: (data, onUpdate) => <React.Fragment>
       <InputGroup>
@@ -72,6 +213,38 @@ async function genQualsModal(call: JmsWebsocket["call"], addError: (e: string) =
           onUpdate={v => onUpdate(update(data, { station_anneal_steps: { $set: Math.max(1000, v as number) } }))}
         />
       </InputGroup>
+      <InputGroup>
+        <InputGroup.Text>Seed</InputGroup.Text>
+        <BufferedFormControl
+          auto
+          type="number"
+          placeholder="Random"
+          value={data.seed ?? ""}
+          onUpdate={v => onUpdate(update(data, { seed: { $set: (v === "" || v == null) ? null : Math.max(0, Math.floor(v as number)) } }))}
+        />
+      </InputGroup>
+      <InputGroup>
+        <InputGroup.Text>Minimum Turnaround (matches)</InputGroup.Text>
+        <BufferedFormControl
+          auto
+          type="number"
+          min={0}
+          value={data.min_turnaround}
+          onUpdate={v => onUpdate(update(data, { min_turnaround: { $set: Math.max(0, Math.floor(v as number)) } }))}
+        />
+        <InputGroup.Checkbox
+          checked={data.hard_turnaround}
+          onChange={(e: React.ChangeEvent<HTMLInputElement>) => onUpdate(update(data, { hard_turnaround: { $set: e.target.checked } }))}
+        />
+        <InputGroup.Text>Strict</InputGroup.Text>
+      </InputGroup>
+      <Form.Check
+        className="mt-2"
+        type="switch"
+        label="Dry Run (only generate a report, so candidate schedules can be compared)"
+        checked={data.dry_run}
+        onChange={e => onUpdate(update(data, { dry_run: { $set: e.target.checked } }))}
+      />
     </React.Fragment>
   });
 
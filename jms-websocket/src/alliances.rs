@@ -1,4 +1,4 @@
-use jms_core_lib::{models::{PlayoffAlliance, MaybeToken, PlayoffMode, Permission}, db::{Singleton, Table}};
+use jms_core_lib::{models::{PlayoffAlliance, MaybeToken, PlayoffMode, Permission, AllianceSelection, AudienceDisplay, AudienceDisplayScene}, db::{Singleton, Table}, schedule::generators::MatchGeneratorRPCClient};
 
 use crate::ws::WebsocketContext;
 
@@ -39,4 +39,75 @@ pub trait AlliancesWebsocket {
     alliance.insert(&ctx.kv)?;
     Ok(alliance)
   }
+
+  // Alliance Selection
+
+  #[publish]
+  async fn selection(&self, ctx: &WebsocketContext) -> anyhow::Result<AllianceSelection> {
+    AllianceSelection::get(&ctx.kv)
+  }
+
+  #[publish]
+  async fn selection_available(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<usize>> {
+    AllianceSelection::get(&ctx.kv)?.available(&ctx.kv)
+  }
+
+  #[endpoint]
+  async fn start_selection(&self, ctx: &WebsocketContext, token: &MaybeToken, rounds: usize) -> anyhow::Result<AllianceSelection> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageAlliances])?;
+    let n = PlayoffMode::get(&ctx.kv)?.n_alliances;
+    let selection = AllianceSelection::start(n, rounds, &ctx.kv)?;
+    AudienceDisplay::set_scene(AudienceDisplayScene::AllianceSelection, &ctx.kv)?;
+    Ok(selection)
+  }
+
+  #[endpoint]
+  async fn pick(&self, ctx: &WebsocketContext, token: &MaybeToken, team: usize) -> anyhow::Result<AllianceSelection> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageAlliances])?;
+    let mut selection = AllianceSelection::get(&ctx.kv)?;
+    selection.pick(team, &ctx.kv)?;
+    Ok(selection)
+  }
+
+  #[endpoint]
+  async fn decline(&self, ctx: &WebsocketContext, token: &MaybeToken, team: usize) -> anyhow::Result<AllianceSelection> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageAlliances])?;
+    let mut selection = AllianceSelection::get(&ctx.kv)?;
+    selection.decline(team, &ctx.kv)?;
+    Ok(selection)
+  }
+
+  #[endpoint]
+  async fn undo_selection(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<AllianceSelection> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageAlliances])?;
+    let mut selection = AllianceSelection::get(&ctx.kv)?;
+    selection.undo(&ctx.kv)?;
+    Ok(selection)
+  }
+
+  // Backups
+
+  #[publish]
+  async fn backup_pool(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<usize>> {
+    PlayoffAlliance::backup_pool(&ctx.kv)
+  }
+
+  #[endpoint]
+  async fn call_backup(&self, ctx: &WebsocketContext, token: &MaybeToken, number: usize, replaces: usize, team: Option<usize>) -> anyhow::Result<PlayoffAlliance> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageAlliances, Permission::HeadReferee])?;
+    let alliance = PlayoffAlliance::call_backup(number, replaces, team, &ctx.kv)?;
+    // Regenerate the unplayed playoff matches so they pick up the new lineup
+    MatchGeneratorRPCClient::update_playoffs(&ctx.mq).await?.map_err(|e| anyhow::anyhow!(e))?;
+    Ok(alliance)
+  }
+
+  #[endpoint]
+  async fn clear_backup(&self, ctx: &WebsocketContext, token: &MaybeToken, number: usize) -> anyhow::Result<PlayoffAlliance> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageAlliances, Permission::HeadReferee])?;
+    let mut alliance = PlayoffAlliance::get(&number, &ctx.kv)?;
+    alliance.backup = None;
+    alliance.insert(&ctx.kv)?;
+    MatchGeneratorRPCClient::update_playoffs(&ctx.mq).await?.map_err(|e| anyhow::anyhow!(e))?;
+    Ok(alliance)
+  }
 }
\ No newline at end of file
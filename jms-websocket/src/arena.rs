@@ -1,8 +1,8 @@
 use std::time::Duration;
 
-use jms_arena_lib::{AllianceStation, AllianceStationUpdate, ArenaEntryCondition, ArenaRPCClient, ArenaSignal, ArenaState, SerialisedLoadedMatch, ARENA_MATCH_KEY, ARENA_STATE_KEY};
-use jms_core_lib::{db::{Singleton, Table}, models::{AllianceStationId, Match, MatchType, MaybeToken, Permission}};
-use jms_driverstation_lib::DriverStationReport;
+use jms_arena_lib::{AllianceStation, AllianceStationUpdate, ArenaAuditEntry, ArenaAuditEvent, ArenaEntryCondition, ArenaRPCClient, ArenaSignal, ArenaState, ArmBlocker, SerialisedLoadedMatch, SerialisedTimeout, ARENA_ARM_BLOCKERS_KEY, ARENA_MATCH_KEY, ARENA_STATE_KEY, ARENA_TIMEOUT_KEY};
+use jms_core_lib::{db::{DBDuration, Singleton, Table}, models::{AllianceStationId, AudienceDisplay, AudienceDisplayScene, Match, MatchType, MaybeToken, Permission, Timeout, TimeoutKind}, reports::ReportData, schedule::generators::MatchGeneratorRPCClient};
+use jms_driverstation_lib::{diagnosis::{DiagnosisSettings, DiagnosisSettingsUpdate}, DriverStationConnection, DriverStationReport, DriverStationSettings, DriverStationSettingsUpdate, DriverStationVersionStatus, DriverStationVersions};
 
 use crate::ws::WebsocketContext;
 
@@ -21,11 +21,18 @@ pub trait ArenaWebsocket {
     Ok(ArenaEntryCondition::get(&ctx.kv)?)
   }
 
+  #[publish]
+  async fn arm_blockers(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<ArmBlocker>> {
+    Ok(ctx.kv.json_get(ARENA_ARM_BLOCKERS_KEY, "$").unwrap_or(vec![]))
+  }
+
   #[endpoint]
   async fn signal(&self, ctx: &WebsocketContext, token: &MaybeToken, signal: ArenaSignal) -> anyhow::Result<()> {
     let user = token.auth(&ctx.kv)?;
     if signal == ArenaSignal::Estop {
       user.require_permission(&[Permission::MatchFlow, Permission::Estop])?;
+    } else if signal == (ArenaSignal::MatchArm { force: true }) {
+      user.require_permission(&[Permission::FTA])?;
     } else {
       user.require_permission(&[Permission::MatchFlow])?;
     }
@@ -53,13 +60,15 @@ pub trait ArenaWebsocket {
 
   #[endpoint]
   async fn load_match(&self, ctx: &WebsocketContext, token: &MaybeToken, match_id: String) -> anyhow::Result<()> {
-    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
-    ArenaRPCClient::load_match(&ctx.mq, match_id).await.map_err(|e| anyhow::anyhow!(e))?.map_err(|e| anyhow::anyhow!(e))
+    let user = token.auth(&ctx.kv)?;
+    user.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
+    ArenaRPCClient::load_match(&ctx.mq, match_id, user.username).await.map_err(|e| anyhow::anyhow!(e))?.map_err(|e| anyhow::anyhow!(e))
   }
 
   #[endpoint]
   async fn load_test_match(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<()> {
-    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
+    let user = token.auth(&ctx.kv)?;
+    user.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
     let max_test_match = Match::all(&ctx.kv)?.iter().filter(|m| m.match_type == MatchType::Test).map(|x| x.set_number).max().unwrap_or(0);
     let m = Match {
       id: Match::gen_id(MatchType::Test, 1, max_test_match + 1, 1),
@@ -74,19 +83,53 @@ pub trait ArenaWebsocket {
       blue_alliance: None,
       red_alliance: None,
       dqs: vec![],
+      surrogates: vec![],
+      replay_reason: None,
+      actual_start_time: None,
+      original_start_time: None,
       played: false,
       ready: true
     };
     m.insert(&ctx.kv)?;
-    ArenaRPCClient::load_match(&ctx.mq, m.id.clone()).await.map_err(|e| anyhow::anyhow!(e))?.map_err(|e| anyhow::anyhow!(e))
+    ArenaRPCClient::load_match(&ctx.mq, m.id.clone(), user.username).await.map_err(|e| anyhow::anyhow!(e))?.map_err(|e| anyhow::anyhow!(e))
   }
 
   #[endpoint]
   async fn unload_match(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<()> {
-    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
-    ArenaRPCClient::unload_match(&ctx.mq).await.map_err(|e| anyhow::anyhow!(e))?.map_err(|e| anyhow::anyhow!(e))
+    let user = token.auth(&ctx.kv)?;
+    user.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
+    ArenaRPCClient::unload_match(&ctx.mq, user.username).await.map_err(|e| anyhow::anyhow!(e))?.map_err(|e| anyhow::anyhow!(e))
   }
   
+  /* Timeouts */
+
+  #[publish]
+  async fn timeout(&self, ctx: &WebsocketContext) -> anyhow::Result<Option<SerialisedTimeout>> {
+    Ok(ctx.kv.json_get(ARENA_TIMEOUT_KEY, "$").ok())
+  }
+
+  #[publish]
+  async fn timeouts(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<Timeout>> {
+    Timeout::sorted(&ctx.kv)
+  }
+
+  #[endpoint]
+  async fn start_timeout(&self, ctx: &WebsocketContext, token: &MaybeToken, kind: TimeoutKind, duration: Option<DBDuration>) -> anyhow::Result<()> {
+    let user = token.auth(&ctx.kv)?;
+    user.require_permission(&[Permission::FTA, Permission::Scorekeeper, Permission::HeadReferee])?;
+    ArenaRPCClient::start_timeout(&ctx.mq, kind, duration, user.username).await?.map_err(|e| anyhow::anyhow!(e))?;
+    AudienceDisplay::set_scene(AudienceDisplayScene::Timeout, &ctx.kv)?;
+    retime_playoffs(ctx).await
+  }
+
+  #[endpoint]
+  async fn end_timeout(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<()> {
+    let user = token.auth(&ctx.kv)?;
+    user.require_permission(&[Permission::FTA, Permission::Scorekeeper, Permission::HeadReferee])?;
+    ArenaRPCClient::end_timeout(&ctx.mq, user.username).await?.map_err(|e| anyhow::anyhow!(e))?;
+    retime_playoffs(ctx).await
+  }
+
   /* Alliance Stations */
 
   #[publish]
@@ -97,30 +140,116 @@ pub trait ArenaWebsocket {
   // TODO: Should DBPartialUpdate take in the same enum that's generated by Updateable? Would make this easier.
   #[endpoint]
   async fn update_station(&self, ctx: &WebsocketContext, token: &MaybeToken, station_id: AllianceStationId, updates: Vec<AllianceStationUpdate>) -> anyhow::Result<()> {
-    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
+    let user = token.auth(&ctx.kv)?;
+    user.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
     let mut stn = AllianceStation::get(&station_id, &ctx.kv)?;
+    let before = stn.clone();
     for update in updates {
       update.apply(&mut stn);
     }
     stn.insert(&ctx.kv)?;
+
+    if stn.estop != before.estop || stn.astop != before.astop {
+      ArenaAuditEntry::record_current(user.username.clone(), ArenaAuditEvent::StationEstop { station: station_id, estop: stn.estop, astop: stn.astop }, &ctx.kv)?;
+    }
+    if stn.bypass != before.bypass {
+      ArenaAuditEntry::record_current(user.username.clone(), ArenaAuditEvent::StationBypass { station: station_id, bypass: stn.bypass }, &ctx.kv)?;
+    }
     Ok(())
   }
 
   #[endpoint]
-  async fn estop_station(&self, ctx: &WebsocketContext, _token: &MaybeToken, station_id: AllianceStationId, astop: bool) -> anyhow::Result<()> {
+  async fn estop_station(&self, ctx: &WebsocketContext, token: &MaybeToken, station_id: AllianceStationId, astop: bool) -> anyhow::Result<()> {
     let mut stn = AllianceStation::get(&station_id, &ctx.kv)?;
     if astop { stn.set_astop(true, &ctx.kv)?; }
     else     { stn.set_estop(true, &ctx.kv)?; }
     stn.insert(&ctx.kv)?;
+
+    // Stations can be stopped without logging in, so the source may not be known
+    let source = token.auth(&ctx.kv).map(|u| u.username).unwrap_or(format!("Station {}", station_id.to_string()));
+    ArenaAuditEntry::record_current(source, ArenaAuditEvent::StationEstop { station: station_id, estop: stn.estop, astop: stn.astop }, &ctx.kv)?;
     Ok(())
   }
 
+  /* Journal */
+
+  #[endpoint]
+  async fn journal(&self, ctx: &WebsocketContext, token: &MaybeToken, match_id: Option<String>) -> anyhow::Result<Vec<ArenaAuditEntry>> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
+    match match_id {
+      Some(match_id) => ArenaAuditEntry::for_match(&match_id, &ctx.kv),
+      None => ArenaAuditEntry::sorted(&ctx.kv)
+    }
+  }
+
+  #[endpoint]
+  async fn journal_csv(&self, ctx: &WebsocketContext, token: &MaybeToken, match_id: Option<String>) -> anyhow::Result<ReportData> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::FTAA, Permission::Scorekeeper])?;
+    let entries = match match_id {
+      Some(match_id) => ArenaAuditEntry::for_match(&match_id, &ctx.kv)?,
+      None => ArenaAuditEntry::sorted(&ctx.kv)?
+    };
+    Ok(ReportData::csv(ArenaAuditEntry::to_csv(&entries).into_bytes()))
+  }
+
   /* Driver Station */
 
   #[publish]
   async fn ds(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<DriverStationReport>> {
     DriverStationReport::all(&ctx.kv)
   }
+
+  #[publish]
+  async fn ds_connections(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<DriverStationConnection>> {
+    DriverStationConnection::all(&ctx.kv)
+  }
+
+  #[publish]
+  async fn ds_versions(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<DriverStationVersionStatus>> {
+    let settings = DriverStationSettings::get(&ctx.kv)?;
+    Ok(DriverStationVersions::all(&ctx.kv)?.into_iter().map(|versions| DriverStationVersionStatus {
+      outdated: versions.outdated(&settings),
+      versions
+    }).collect())
+  }
+
+  #[endpoint]
+  async fn ds_settings(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<DriverStationSettings> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::ManageEvent])?;
+    DriverStationSettings::get(&ctx.kv)
+  }
+
+  #[endpoint]
+  async fn update_ds_settings(&self, ctx: &WebsocketContext, token: &MaybeToken, update: DriverStationSettingsUpdate) -> anyhow::Result<DriverStationSettings> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::ManageEvent])?;
+    let mut settings = DriverStationSettings::get(&ctx.kv)?;
+    update.apply(&mut settings);
+    settings.update(&ctx.kv)?;
+    Ok(settings)
+  }
+
+  #[endpoint]
+  async fn diagnosis_settings(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<DiagnosisSettings> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::ManageEvent])?;
+    DiagnosisSettings::get(&ctx.kv)
+  }
+
+  #[endpoint]
+  async fn update_diagnosis_settings(&self, ctx: &WebsocketContext, token: &MaybeToken, update: DiagnosisSettingsUpdate) -> anyhow::Result<DiagnosisSettings> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::ManageEvent])?;
+    let mut settings = DiagnosisSettings::get(&ctx.kv)?;
+    update.apply(&mut settings);
+    settings.update(&ctx.kv)?;
+    Ok(settings)
+  }
+}
+
+// Timeouts hold up the playoff schedule, so the remaining playoff matches need to be re-timed when one is called or ended.
+async fn retime_playoffs(ctx: &WebsocketContext) -> anyhow::Result<()> {
+  if Match::all(&ctx.kv)?.iter().any(|m| !m.played && (m.match_type == MatchType::Playoff || m.match_type == MatchType::Final)) {
+    MatchGeneratorRPCClient::update_playoffs(&ctx.mq).await?.map_err(|e| anyhow::anyhow!(e))?;
+  }
+  Ok(())
 }
 
 // define_websocket_msg!($ArenaMessage {
@@ -18,7 +18,7 @@ pub trait ElectronicsWebsocket {
 
   #[endpoint]
   async fn reset_estops(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<()> {
-    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageElectronics])?;
+    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::ManageElectronics])?;
     FieldElectronicsServiceRPCClient::reset_estops(&ctx.mq).await?.map_err(|e| anyhow::anyhow!(e))
   }
 
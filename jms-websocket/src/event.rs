@@ -1,4 +1,4 @@
-use jms_core_lib::{models::{EventDetails, MaybeToken, Permission, ScheduleBlock, ScheduleBlockType, ScheduleBlockUpdate}, db::{Singleton, Table}};
+use jms_core_lib::{models::{EventDetails, MatchTimingConfig, MaybeToken, Permission, ScheduleBlock, ScheduleBlockType, ScheduleBlockUpdate}, db::{Singleton, Table}};
 
 use crate::ws::WebsocketContext;
 
@@ -16,6 +16,21 @@ pub trait EventWebsocket {
     Ok(details)
   }
 
+  // Match Timing
+
+  #[publish]
+  async fn match_timing(&self, ctx: &WebsocketContext) -> anyhow::Result<MatchTimingConfig> {
+    Ok(MatchTimingConfig::get(&ctx.kv)?)
+  }
+
+  #[endpoint]
+  async fn update_match_timing(&self, ctx: &WebsocketContext, token: &MaybeToken, config: MatchTimingConfig) -> anyhow::Result<MatchTimingConfig> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageEvent])?;
+    config.validate()?;
+    config.update(&ctx.kv)?;
+    Ok(config)
+  }
+
   // Schedule
 
   #[endpoint]
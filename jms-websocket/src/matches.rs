@@ -1,4 +1,4 @@
-use jms_core_lib::{models::{Match, MaybeToken, Permission, PlayoffMode, CommittedMatchScores, TeamRanking, MatchType}, db::{Table, Singleton}, schedule::generators::{QualsMatchGeneratorParams, MatchGeneratorRPCClient, MATCH_GENERATOR_JOB_KEY}};
+use jms_core_lib::{models::{Match, MaybeToken, Permission, PlayoffMode, ScheduleSlip, RetimeSettings, CommittedMatchScores, TeamRanking, MatchType, PlayoffModeType, RoundRobinStanding}, db::{Table, Singleton}, schedule::generators::{QualsMatchGeneratorParams, QualsScheduleReport, QualsImportResult, MatchGeneratorRPCClient, MATCH_GENERATOR_JOB_KEY}, scoring::scores::ScoringConfig};
 
 use crate::ws::WebsocketContext;
 
@@ -16,6 +16,29 @@ pub trait MatchesWebsocket {
     Ok(Match::sorted(&ctx.kv)?.into_iter().find(|m| m.match_type != MatchType::Test && !m.played))
   }
 
+  #[publish]
+  async fn slip(&self, ctx: &WebsocketContext) -> anyhow::Result<ScheduleSlip> {
+    Ok(ScheduleSlip::compute(&Match::all(&ctx.kv)?))
+  }
+
+  #[publish]
+  async fn retime_settings(&self, ctx: &WebsocketContext) -> anyhow::Result<RetimeSettings> {
+    RetimeSettings::get(&ctx.kv)
+  }
+
+  #[endpoint]
+  async fn update_retime_settings(&self, ctx: &WebsocketContext, token: &MaybeToken, settings: RetimeSettings) -> anyhow::Result<RetimeSettings> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageSchedule])?;
+    settings.update(&ctx.kv)?;
+    Ok(settings)
+  }
+
+  #[endpoint]
+  async fn retime(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<usize> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageSchedule, Permission::FTA])?;
+    MatchGeneratorRPCClient::retime_schedule(&ctx.mq).await?.map_err(|e| anyhow::anyhow!(e))
+  }
+
   #[publish]
   async fn generator_busy(&self, ctx: &WebsocketContext) -> anyhow::Result<bool> {
     ctx.kv.exists(MATCH_GENERATOR_JOB_KEY)
@@ -33,6 +56,31 @@ pub trait MatchesWebsocket {
     Ok(())
   }
 
+  #[endpoint]
+  async fn replay(&self, ctx: &WebsocketContext, token: &MaybeToken, match_id: String, reason: String) -> anyhow::Result<Match> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::FTA, Permission::HeadReferee, Permission::ManageSchedule])?;
+    let matches = Match::sorted(&ctx.kv)?;
+    let m = Match::get(&match_id, &ctx.kv)?;
+
+    if !m.played {
+      anyhow::bail!("Can't replay a match that hasn't been played yet!")
+    }
+    if matches.iter().any(|x| x.match_type == m.match_type && x.set_number == m.set_number && x.match_number > m.match_number) {
+      anyhow::bail!("This match has already been replayed!")
+    }
+
+    // Slot the replay in as the next match to be played
+    let now = chrono::Local::now();
+    let start_time = match matches.iter().find(|x| x.match_type != MatchType::Test && !x.played) {
+      Some(next) => now.min(next.start_time - chrono::Duration::seconds(1)),
+      None => now
+    };
+
+    let replay = m.replay(reason, start_time)?;
+    replay.insert(&ctx.kv)?;
+    Ok(replay)
+  }
+
   #[endpoint]
   async fn debug_delete_all(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<()> {
     token.auth(&ctx.kv)?.require_permission(&[Permission::FTA])?;
@@ -67,6 +115,25 @@ pub trait MatchesWebsocket {
     Ok(())
   }
 
+  #[endpoint]
+  async fn import_quals(&self, ctx: &WebsocketContext, token: &MaybeToken, data: String, dry_run: bool) -> anyhow::Result<QualsImportResult> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageSchedule])?;
+    MatchGeneratorRPCClient::import_quals(&ctx.mq, data, dry_run).await?.map_err(|e| anyhow::anyhow!(e))
+  }
+
+  #[publish]
+  async fn quals_reports(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<QualsScheduleReport>> {
+    let mut reports = QualsScheduleReport::all(&ctx.kv)?;
+    reports.sort_by(|a, b| b.generated_at.cmp(&a.generated_at));
+    Ok(reports)
+  }
+
+  #[endpoint]
+  async fn clear_quals_reports(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<()> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::ManageSchedule])?;
+    QualsScheduleReport::clear(&ctx.kv)
+  }
+
   // Playoffs
 
   #[endpoint]
@@ -83,6 +150,19 @@ pub trait MatchesWebsocket {
     Ok(mode)
   }
 
+  #[publish]
+  async fn round_robin_standings(&self, ctx: &WebsocketContext) -> anyhow::Result<Vec<RoundRobinStanding>> {
+    let playoff_mode = PlayoffMode::get(&ctx.kv)?;
+    if playoff_mode.mode != PlayoffModeType::RoundRobin {
+      return Ok(vec![])
+    }
+
+    let matches = Match::all(&ctx.kv)?;
+    let scores = CommittedMatchScores::all_map(&ctx.kv)?;
+    let config = ScoringConfig::get(&ctx.kv)?;
+    RoundRobinStanding::calculate(playoff_mode.n_alliances, &matches, &scores, &config)
+  }
+
   #[endpoint]
   async fn reset_playoffs(&self, ctx: &WebsocketContext, token: &MaybeToken) -> anyhow::Result<()> {
     token.auth(&ctx.kv)?.require_permission(&[Permission::ManagePlayoffs])?;
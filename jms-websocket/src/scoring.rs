@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use jms_base::kv;
-use jms_core_lib::{db::{Singleton, Table}, models::{Alliance, CommittedMatchScores, Match, MatchType, MaybeToken, Permission, TeamRanking}, schedule::generators::MatchGeneratorRPCClient, scoring::scores::{LiveScore, MatchScore, MatchScoreSnapshot, ScoreUpdateData, ScoringConfig}};
+use jms_core_lib::{db::{Singleton, Table}, models::{Alliance, CommittedMatchScores, EventDetails, Match, MatchType, MaybeToken, Permission, TeamRanking, TeamRankingDetail}, schedule::generators::MatchGeneratorRPCClient, scoring::{game::GameDescriptor, scores::{MatchScore, MatchScoreSnapshot, ScoreUpdateData, ScoringConfig}}};
 use uuid::Uuid;
 
 use crate::ws::WebsocketContext;
@@ -9,6 +9,11 @@ use crate::ws::WebsocketContext;
 #[jms_websocket_macros::websocket_handler]
 pub trait ScoringWebsocket {
   // Config
+  #[publish]
+  async fn game(&self, ctx: &WebsocketContext) -> anyhow::Result<GameDescriptor> {
+    Ok(EventDetails::get(&ctx.kv)?.game.descriptor())
+  }
+
   #[publish]
   async fn config(&self, ctx: &WebsocketContext) -> anyhow::Result<ScoringConfig> {
     let config = ScoringConfig::get(&ctx.kv)?;
@@ -25,18 +30,18 @@ pub trait ScoringWebsocket {
   #[publish]
   async fn current(&self, ctx: &WebsocketContext) -> anyhow::Result<MatchScoreSnapshot> {
     let config = ScoringConfig::get(&ctx.kv)?;
-    Ok(MatchScore::get(&ctx.kv)?.derive(config))
+    MatchScore::get(&ctx.kv)?.derive(&config)
   }
 
   async fn do_score_update(kv: &kv::KVConnection, update: ScoreUpdateData) -> anyhow::Result<MatchScoreSnapshot> {
     let config = ScoringConfig::get(&kv)?;
     let mut live_score = MatchScore::get(kv)?;
     match update.alliance {
-      Alliance::Red => live_score.red.update(update.update),
-      Alliance::Blue => live_score.blue.update(update.update)
+      Alliance::Red => live_score.red.update(update.update)?,
+      Alliance::Blue => live_score.blue.update(update.update)?
     }
     live_score.update(kv)?;
-    Ok(live_score.derive(config))
+    live_score.derive(&config)
   }
 
   #[endpoint]
@@ -47,10 +52,9 @@ pub trait ScoringWebsocket {
     };
     
     // Check permissions
-    match update.update {
-      jms_core_lib::scoring::scores::ScoreUpdate::Coop => token.auth(&ctx.kv)?.require_permission(&[hp_permission])?,
-      jms_core_lib::scoring::scores::ScoreUpdate::Amplify => token.auth(&ctx.kv)?.require_permission(&[hp_permission])?,
-      _ => token.auth(&ctx.kv)?.require_permission(&[Permission::Scoring])?
+    match update.update.is_human_player_update() {
+      true => token.auth(&ctx.kv)?.require_permission(&[hp_permission])?,
+      false => token.auth(&ctx.kv)?.require_permission(&[Permission::Scoring])?
     };
     
     let my_id = Uuid::new_v4().to_string();
@@ -87,7 +91,7 @@ pub trait ScoringWebsocket {
 
     ctx.kv.del("__score_update_lock")?;
 
-    Ok(score.derive(config))
+    score.derive(&config)
   }
 
   // Historical Scores
@@ -134,31 +138,33 @@ pub trait ScoringWebsocket {
   }
 
   #[endpoint]
-  async fn get_default_scores(&self, _ctx: &WebsocketContext, _token: &MaybeToken) -> anyhow::Result<MatchScore> {
-    Ok(MatchScore::default())
+  async fn get_default_scores(&self, ctx: &WebsocketContext, _token: &MaybeToken) -> anyhow::Result<MatchScore> {
+    let game = EventDetails::get(&ctx.kv)?.game;
+    Ok(MatchScore::new(game, 3, 3))
   }
 
   #[endpoint]
   async fn derive_score(&self, ctx: &WebsocketContext, _token: &MaybeToken, score: MatchScore) -> anyhow::Result<MatchScoreSnapshot> {
     let config = ScoringConfig::get(&ctx.kv)?;
-    Ok(score.derive(config))
+    score.derive(&config)
   }
 
   #[endpoint]
   async fn debug_random_fill(&self, ctx: &WebsocketContext, token: &MaybeToken, ty: MatchType) -> anyhow::Result<()> {
     token.auth(&ctx.kv)?.require_permission(&[Permission::FTA])?;
+    let game = EventDetails::get(&ctx.kv)?.game;
     for m in Match::all(&ctx.kv)? {
       let match_id = m.id;
       if m.match_type == ty && m.ready {
         match CommittedMatchScores::get(&match_id, &ctx.kv) {
           Ok(mut cms) => {
             if cms.scores.len() == 0 {
-              cms.push_and_insert(MatchScore { red: LiveScore::randomise(), blue: LiveScore::randomise() }, &ctx.kv)?;
+              cms.push_and_insert(MatchScore::randomise(game), &ctx.kv)?;
             }
           },
           Err(_) => {
             let mut c = CommittedMatchScores { match_id: match_id, scores: vec![], last_update: chrono::Local::now() };
-            c.push_and_insert(MatchScore { red: LiveScore::randomise(), blue: LiveScore::randomise() }, &ctx.kv)?;
+            c.push_and_insert(MatchScore::randomise(game), &ctx.kv)?;
           }
         }
       }
@@ -179,4 +185,9 @@ pub trait ScoringWebsocket {
     TeamRanking::update(&ctx.kv)?;
     Ok(TeamRanking::sorted(&ctx.kv)?)
   }
+
+  #[endpoint]
+  async fn ranking_detail(&self, ctx: &WebsocketContext, _token: &MaybeToken, team: usize) -> anyhow::Result<TeamRankingDetail> {
+    TeamRanking::detail(team, &ctx.kv)
+  }
 }
\ No newline at end of file
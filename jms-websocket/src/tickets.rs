@@ -1,7 +1,8 @@
+use std::io::Write;
+
 use chrono::Local;
-use jms_core_lib::{models::{MaybeToken, SupportTicket, TicketComment, Permission}, db::Table};
-use jms_match_logs_lib::MatchLog;
-use uuid::Uuid;
+use jms_core_lib::{models::{Match, MaybeToken, SupportTicket, TicketComment, Permission}, db::Table, reports::ReportData};
+use jms_match_logs_lib::{MatchLog, MatchLogFormat};
 
 use crate::ws::WebsocketContext;
 
@@ -22,21 +23,7 @@ pub trait TicketWebsocket {
     let author = token.auth(&ctx.kv)?;
     author.require_permission(&[Permission::Ticketing])?;
 
-    let ticket = SupportTicket {
-      id: Uuid::new_v4().to_string(),
-      team,
-      match_id,
-      issue_type,
-      author: author.username.clone(),
-      notes: vec![TicketComment {
-        author: author.username.clone(),
-        time: Local::now(),
-        comment: format!("Ticket Opened by {}", author.username)
-      }],
-      assigned_to: None,
-      resolved: false
-    };
-
+    let ticket = SupportTicket::new(team, match_id, issue_type, author.username.clone());
     ticket.insert(&ctx.kv)?;
 
     Ok(ticket)
@@ -83,4 +70,46 @@ pub trait TicketWebsocket {
   async fn get_match_log(&self, ctx: &WebsocketContext, _token: &MaybeToken, match_id: String, team: usize) -> anyhow::Result<MatchLog> {
     MatchLog::get(&format!("{}:{}", match_id, team), &ctx.kv)
   }
+
+  #[endpoint]
+  async fn export_match_log(&self, ctx: &WebsocketContext, token: &MaybeToken, match_id: String, team: usize, format: MatchLogFormat) -> anyhow::Result<ReportData> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::Ticketing, Permission::FTA, Permission::FTAA])?;
+    let log = MatchLog::get(&format!("{}:{}", match_id, team), &ctx.kv)?;
+    let data = render_log(&log, format, &ctx)?;
+    Ok(match format {
+      MatchLogFormat::DSLog => ReportData::binary(data),
+      MatchLogFormat::CSV => ReportData::csv(data),
+    }.with_filename(log_filename(&log, format)))
+  }
+
+  #[endpoint]
+  async fn export_team_logs(&self, ctx: &WebsocketContext, token: &MaybeToken, team: usize, format: MatchLogFormat) -> anyhow::Result<ReportData> {
+    token.auth(&ctx.kv)?.require_permission(&[Permission::Ticketing, Permission::FTA, Permission::FTAA])?;
+    let logs: Vec<MatchLog> = MatchLog::all(&ctx.kv)?.into_iter().filter(|l| l.team == team).collect();
+    if logs.is_empty() {
+      anyhow::bail!("No match logs for team {}", team);
+    }
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+    for log in logs {
+      zip.start_file(log_filename(&log, format), zip::write::FileOptions::default())?;
+      zip.write_all(&render_log(&log, format, &ctx)?)?;
+    }
+    Ok(ReportData::zip(zip.finish()?.into_inner()).with_filename(format!("{}-{}.zip", team, format.extension())))
+  }
+}
+
+fn render_log(log: &MatchLog, format: MatchLogFormat, ctx: &WebsocketContext) -> anyhow::Result<Vec<u8>> {
+  Ok(match format {
+    MatchLogFormat::DSLog => {
+      // The log starts when the match did
+      let start = Match::get(&log.match_id, &ctx.kv).map(|m| m.actual_start_time.unwrap_or(m.start_time)).unwrap_or_else(|_| Local::now());
+      log.to_dslog(start)
+    },
+    MatchLogFormat::CSV => log.to_csv().into_bytes(),
+  })
+}
+
+fn log_filename(log: &MatchLog, format: MatchLogFormat) -> String {
+  format!("{}-{}.{}", log.match_id, log.team, format.extension())
 }
\ No newline at end of file